@@ -1,11 +1,34 @@
 //! A concrete implementation of the [`SurfaceHandler`] (and [`Surface`](crate::Surface)) in the form of a [`Bitmap`] for convenience.
-use crate::widget::surface::SurfaceHandler;
-
+pub mod brush;
+pub mod codec;
+pub mod composite;
+pub mod draw;
+pub mod filter;
+pub mod history;
+#[cfg(feature = "image")]
+pub mod io;
+pub mod load;
+pub mod pixel;
+pub mod resample;
+pub mod selection;
+pub mod snapshot;
+pub mod swapchain;
+pub mod transform;
+pub mod view;
+
+pub use pixel::Rgba;
+pub use snapshot::BitmapSnapshot;
+pub use view::{BitmapView, BitmapViewMut};
+
+use crate::widget::surface::{SurfaceHandler, SurfaceId};
+use selection::Mask;
+use transform::Anchor;
+
+use std::collections::VecDeque;
 use std::num::NonZeroU32;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Weak};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
 
-use iced_core::Size;
+use iced_core::{Rectangle, Size};
 
 /// Create an empty [`Bitmap`] image.
 ///
@@ -21,7 +44,9 @@ pub fn bitmap(width: u32, height: u32) -> Bitmap {
 /// A [`Bitmap`] can be freely edited and resized.
 ///
 /// **Note**:
-/// While it contains an [`Arc`], cloning this type will create a new allocation.
+/// While it contains an [`Arc`], cloning this type will create a new surface. Its pixels are
+/// shared until either copy is written to, like a [`snapshot`](Self::snapshot).
+#[derive(Debug)]
 pub struct Bitmap(pub(crate) Arc<SurfaceInner>);
 
 impl Bitmap {
@@ -34,10 +59,11 @@ impl Bitmap {
         let buffer = vec![0; width as usize * height as usize];
 
         Self(Arc::new(SurfaceInner {
-            buffer,
+            buffer: Arc::new(buffer),
             width: NonZeroU32::new(width).expect("width must be greater than 0"),
             height: NonZeroU32::new(height).expect("height must be greater than 0"),
-            dirty: AtomicBool::new(false),
+            id: SurfaceId::unique(),
+            dirty: Damage::default(),
         }))
     }
 
@@ -60,7 +86,10 @@ impl Bitmap {
         this
     }
 
-    /// Resize the [`Bitmap`].
+    /// Resize the [`Bitmap`], keeping the existing pixels in the top left corner.
+    ///
+    /// New pixels are transparent. See [`resize_canvas`](Self::resize_canvas) to choose where
+    /// the existing pixels are placed.
     ///
     /// # Panics
     ///
@@ -70,11 +99,7 @@ impl Bitmap {
             return;
         }
 
-        let this = Arc::make_mut(&mut self.0);
-
-        this.buffer.resize(width as usize * height as usize, 0);
-        this.width = NonZeroU32::new(width).expect("width must be greater than 0");
-        this.height = NonZeroU32::new(height).expect("height must be greater than 0");
+        self.resize_canvas(width, height, Anchor::TopLeft, Rgba::TRANSPARENT);
     }
 
     /// Get the width of the [`Bitmap`]
@@ -121,6 +146,136 @@ impl Bitmap {
         self.raw_mut().copy_from_slice(data);
     }
 
+    /// Get an immutable slice of the pixels, row by row.
+    pub fn pixels(&self) -> &[Rgba] {
+        bytemuck::cast_slice(self.buffer())
+    }
+
+    /// Get a mutable slice of the pixels, row by row.
+    pub fn pixels_mut(&mut self) -> &mut [Rgba] {
+        bytemuck::cast_slice_mut(self.buffer_mut())
+    }
+
+    /// Get the pixel at the given coordinates, if it's inside of the [`Bitmap`].
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<Rgba> {
+        self.row(y)?.get(x as usize).copied()
+    }
+
+    /// Set the pixel at the given coordinates, marking it as modified.
+    ///
+    /// Returns `false` if the pixel is outside of the [`Bitmap`].
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: impl Into<Rgba>) -> bool {
+        if x >= self.width() || y >= self.height() {
+            return false;
+        }
+
+        let index = y as usize * self.width() as usize + x as usize;
+        self.buffer_mut_untracked()[index] = color.into().to_u32();
+        self.mark_dirty(Rectangle {
+            x,
+            y,
+            width: 1,
+            height: 1,
+        });
+
+        true
+    }
+
+    /// Get a row of pixels, if it's inside of the [`Bitmap`].
+    pub fn row(&self, y: u32) -> Option<&[Rgba]> {
+        let width = self.width() as usize;
+        (y < self.height()).then(|| &self.pixels()[y as usize * width..][..width])
+    }
+
+    /// Get a mutable row of pixels, marking it as modified.
+    pub fn row_mut(&mut self, y: u32) -> Option<&mut [Rgba]> {
+        self.view_mut(Rectangle {
+            x: 0,
+            y,
+            width: self.width(),
+            height: 1,
+        })
+        .and_then(|view| view.into_row_mut(0))
+    }
+
+    /// Iterate over the rows of pixels from top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item = &[Rgba]> {
+        self.pixels().chunks_exact(self.width() as usize)
+    }
+
+    /// Iterate mutably over the rows of pixels from top to bottom, marking the [`Bitmap`] as modified.
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [Rgba]> {
+        let width = self.width() as usize;
+        self.pixels_mut().chunks_exact_mut(width)
+    }
+
+    /// Iterate over the pixels along with their `(x, y)` coordinates.
+    pub fn enumerate_pixels(&self) -> impl Iterator<Item = (u32, u32, Rgba)> {
+        self.rows()
+            .zip(0..)
+            .flat_map(|(row, y)| row.iter().zip(0..).map(move |(pixel, x)| (x, y, *pixel)))
+    }
+
+    /// Iterate mutably over the pixels along with their `(x, y)` coordinates, marking the [`Bitmap`] as modified.
+    pub fn enumerate_pixels_mut(&mut self) -> impl Iterator<Item = (u32, u32, &mut Rgba)> {
+        self.rows_mut()
+            .zip(0..)
+            .flat_map(|(row, y)| row.iter_mut().zip(0..).map(move |(pixel, x)| (x, y, pixel)))
+    }
+
+    /// Apply an edit to the [`Bitmap`], discarding any changes made to pixels outside of the [`Mask`].
+    ///
+    /// Changes are kept as is if the edit resizes the [`Bitmap`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the [`Mask`] doesn't match the [`Bitmap`].
+    pub fn edit_masked<T>(&mut self, mask: &Mask, edit: impl FnOnce(&mut Self) -> T) -> T {
+        assert!(
+            mask.width() == self.width() && mask.height() == self.height(),
+            "Size mismatch!"
+        );
+
+        let original = self.buffer().to_vec();
+        let output = edit(self);
+
+        if self.width() != mask.width() || self.height() != mask.height() {
+            return output;
+        }
+
+        let width = self.width() as usize;
+
+        for (y, (row, original)) in self
+            .buffer_mut()
+            .chunks_exact_mut(width)
+            .zip(original.chunks_exact(width))
+            .enumerate()
+        {
+            for (x, (pixel, original)) in row.iter_mut().zip(original).enumerate() {
+                if !mask.contains(x as u32, y as u32) {
+                    *pixel = *original;
+                }
+            }
+        }
+
+        output
+    }
+
+    /// Mark a region of the [`Bitmap`] as modified.
+    ///
+    /// Only modified regions are uploaded to the GPU. The whole [`Bitmap`] is
+    /// marked as modified when its buffer is borrowed mutably.
+    pub fn mark_dirty(&mut self, region: Rectangle<u32>) {
+        Arc::make_mut(&mut self.0).dirty.add(region);
+    }
+
+    /// Get a mutable u32 slice of the raw `RGBA` image data without marking anything as modified.
+    ///
+    /// The caller is responsible for calling [`mark_dirty`](Self::mark_dirty) with the region it changed.
+    pub(crate) fn buffer_mut_untracked(&mut self) -> &mut [u32] {
+        Arc::make_mut(&mut Arc::make_mut(&mut self.0).buffer).as_mut_slice()
+    }
+
     pub(crate) fn create_weak(&self) -> Weak<SurfaceInner> {
         Arc::downgrade(&self.0)
     }
@@ -144,15 +299,24 @@ impl SurfaceHandler for Bitmap {
 
 impl Clone for Bitmap {
     fn clone(&self) -> Self {
-        Self(Arc::new(SurfaceInner::clone(&self.0)))
+        Self(Arc::new(SurfaceInner {
+            buffer: Arc::clone(&self.0.buffer),
+            width: self.0.width,
+            height: self.0.height,
+            id: SurfaceId::unique(),
+            dirty: Damage::default(),
+        }))
     }
 }
 
 pub struct SurfaceInner {
-    buffer: Vec<u32>,
+    /// Shared with any [`BitmapSnapshot`] until the next write.
+    buffer: Arc<Vec<u32>>,
     width: NonZeroU32,
     height: NonZeroU32,
-    dirty: AtomicBool,
+    /// Kept when the [`Bitmap`] is copied on write, since it's still the same surface.
+    id: SurfaceId,
+    dirty: Damage,
 }
 
 impl super::Surface for SurfaceInner {
@@ -168,13 +332,17 @@ impl super::Surface for SurfaceInner {
         self.raw()
     }
 
-    fn run_if_modified(&self, update: impl FnOnce(u32, u32, &[u8])) {
-        if let Ok(true) =
-            self.dirty
-                .compare_exchange(true, false, Ordering::Relaxed, Ordering::Relaxed)
-        {
-            update(self.width(), self.height(), self.raw())
-        }
+    fn id(&self) -> SurfaceId {
+        self.id
+    }
+
+    fn version(&self) -> u64 {
+        self.dirty.version()
+    }
+
+    fn modified_since(&self, version: u64) -> Option<Rectangle<u32>> {
+        self.dirty
+            .modified_since(version, self.width(), self.height())
     }
 }
 
@@ -188,8 +356,13 @@ impl SurfaceInner {
     }
 
     pub fn buffer_mut(&mut self) -> &mut [u32] {
-        self.dirty.store(true, Ordering::Relaxed);
-        &mut self.buffer
+        self.dirty.add(Rectangle {
+            x: 0,
+            y: 0,
+            width: self.width(),
+            height: self.height(),
+        });
+        Arc::make_mut(&mut self.buffer).as_mut_slice()
     }
 
     pub fn buffer(&self) -> &[u32] {
@@ -215,7 +388,8 @@ impl Clone for SurfaceInner {
             buffer: self.buffer.clone(),
             width: self.width,
             height: self.height,
-            dirty: AtomicBool::new(self.dirty.load(Ordering::Relaxed)),
+            id: self.id,
+            dirty: self.dirty.clone(),
         }
     }
 }
@@ -229,3 +403,272 @@ impl std::fmt::Debug for SurfaceInner {
             .finish()
     }
 }
+
+/// The regions modified in each version of a [`Bitmap`].
+///
+/// Modified regions are first merged into a pending bounding box, which can be added to through a
+/// shared reference, even from another thread. The pending region becomes a new version the next
+/// time the version is read.
+#[derive(Debug, Default)]
+pub(crate) struct Damage {
+    /// The bounding box of every region modified since the version was last read.
+    pending: Mutex<Option<Rectangle<u32>>>,
+    versions: Mutex<Versions>,
+}
+
+/// The number of versions to remember the modified region of.
+///
+/// Textures holding an older version upload the whole surface.
+const MAX_VERSIONS: usize = 32;
+
+#[derive(Debug, Clone, Default)]
+struct Versions {
+    current: u64,
+    /// The region modified in each of the latest versions, oldest first.
+    regions: VecDeque<(u64, Rectangle<u32>)>,
+}
+
+impl Damage {
+    pub(crate) fn add(&self, region: Rectangle<u32>) {
+        if region.width == 0 || region.height == 0 {
+            return;
+        }
+
+        let mut pending = lock(&self.pending);
+        *pending = Some(pending.map_or(region, |pending| union(pending, region)));
+    }
+
+    /// Get the current version, turning any pending region into a new one.
+    pub(crate) fn version(&self) -> u64 {
+        self.versions().current
+    }
+
+    /// Get the region modified since the given version, clipped to the given size.
+    pub(crate) fn modified_since(
+        &self,
+        version: u64,
+        width: u32,
+        height: u32,
+    ) -> Option<Rectangle<u32>> {
+        let versions = self.versions();
+
+        if version == versions.current {
+            return None;
+        }
+
+        let remembered = versions
+            .regions
+            .front()
+            .is_some_and(|(oldest, _)| *oldest <= version + 1);
+
+        if version > versions.current || !remembered {
+            return Some(Rectangle {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            });
+        }
+
+        let region = versions
+            .regions
+            .iter()
+            .filter(|(modified, _)| *modified > version)
+            .map(|(_, region)| *region)
+            .reduce(union)?;
+
+        clip(region, width, height)
+    }
+
+    fn versions(&self) -> MutexGuard<'_, Versions> {
+        let mut versions = lock(&self.versions);
+
+        if let Some(region) = lock(&self.pending).take() {
+            versions.current += 1;
+            let current = versions.current;
+
+            versions.regions.push_back((current, region));
+
+            if versions.regions.len() > MAX_VERSIONS {
+                versions.regions.pop_front();
+            }
+        }
+
+        versions
+    }
+}
+
+impl Clone for Damage {
+    fn clone(&self) -> Self {
+        let versions = self.versions().clone();
+        let pending = *lock(&self.pending);
+
+        Self {
+            pending: Mutex::new(pending),
+            versions: Mutex::new(versions),
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|error| error.into_inner())
+}
+
+/// The bounding box of two regions.
+fn union(a: Rectangle<u32>, b: Rectangle<u32>) -> Rectangle<u32> {
+    let x = a.x.min(b.x);
+    let y = a.y.min(b.y);
+    let right = a.x.saturating_add(a.width).max(b.x.saturating_add(b.width));
+    let bottom =
+        a.y.saturating_add(a.height)
+            .max(b.y.saturating_add(b.height));
+
+    Rectangle {
+        x,
+        y,
+        width: right - x,
+        height: bottom - y,
+    }
+}
+
+/// Clip a region to the given size.
+fn clip(region: Rectangle<u32>, width: u32, height: u32) -> Option<Rectangle<u32>> {
+    let right = region.x.saturating_add(region.width).min(width);
+    let bottom = region.y.saturating_add(region.height).min(height);
+
+    (region.x < right && region.y < bottom).then(|| Rectangle {
+        x: region.x,
+        y: region.y,
+        width: right - region.x,
+        height: bottom - region.y,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(x: u32, y: u32, width: u32, height: u32) -> Rectangle<u32> {
+        Rectangle {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn modified_since() {
+        let damage = Damage::default();
+        assert_eq!(damage.version(), 0);
+        assert_eq!(damage.modified_since(0, 100, 100), None);
+
+        damage.add(region(10, 10, 5, 5));
+        damage.add(region(20, 0, 1, 1));
+        assert_eq!(damage.version(), 1);
+        assert_eq!(
+            damage.modified_since(0, 100, 100),
+            Some(region(10, 0, 11, 15))
+        );
+        assert_eq!(damage.modified_since(1, 100, 100), None);
+
+        damage.add(region(0, 50, 1, 1));
+        damage.add(region(0, 0, 0, 10));
+        assert_eq!(damage.version(), 2);
+        assert_eq!(
+            damage.modified_since(1, 100, 100),
+            Some(region(0, 50, 1, 1))
+        );
+        assert_eq!(
+            damage.modified_since(0, 100, 100),
+            Some(region(0, 0, 21, 51))
+        );
+
+        // Unknown versions are treated as fully modified.
+        assert_eq!(
+            damage.modified_since(3, 100, 100),
+            Some(region(0, 0, 100, 100))
+        );
+    }
+
+    #[test]
+    fn modified_since_clipped() {
+        let damage = Damage::default();
+        damage.add(region(90, 90, u32::MAX, u32::MAX));
+        assert_eq!(
+            damage.modified_since(0, 100, 95),
+            Some(region(90, 90, 10, 5))
+        );
+        assert_eq!(damage.modified_since(0, 50, 50), None);
+    }
+
+    #[test]
+    fn modified_since_forgotten() {
+        let damage = Damage::default();
+
+        for x in 0..MAX_VERSIONS as u32 + 2 {
+            damage.add(region(x, 0, 1, 1));
+            damage.version();
+        }
+
+        let current = damage.version();
+        assert_eq!(current, MAX_VERSIONS as u64 + 2);
+
+        // The oldest versions are forgotten, and uploaded whole.
+        assert_eq!(
+            damage.modified_since(0, 100, 100),
+            Some(region(0, 0, 100, 100))
+        );
+        assert_eq!(
+            damage.modified_since(1, 100, 100),
+            Some(region(0, 0, 100, 100))
+        );
+
+        // The remembered ones are merged.
+        assert_eq!(
+            damage.modified_since(2, 100, 100),
+            Some(region(2, 0, MAX_VERSIONS as u32, 1))
+        );
+        assert_eq!(
+            damage.modified_since(current - 1, 100, 100),
+            Some(region(current as u32 - 1, 0, 1, 1))
+        );
+    }
+
+    #[test]
+    fn concurrent_damage() {
+        let damage = Arc::new(Damage::default());
+        let mut seen = None;
+        let mut version = 0;
+
+        let writers: Vec<_> = (0..4)
+            .map(|thread| {
+                let damage = Arc::clone(&damage);
+
+                std::thread::spawn(move || {
+                    for y in 0..1000 {
+                        damage.add(region(thread, y, 1, 1));
+                    }
+                })
+            })
+            .collect();
+
+        let mut merge = |version: &mut u64| {
+            let current = damage.version();
+
+            if let Some(modified) = damage.modified_since(*version, 100, 1000) {
+                seen = Some(seen.map_or(modified, |seen| union(seen, modified)));
+            }
+
+            *version = current;
+        };
+
+        while writers.iter().any(|writer| !writer.is_finished()) {
+            merge(&mut version);
+        }
+
+        merge(&mut version);
+
+        assert_eq!(seen, Some(region(0, 0, 4, 1000)));
+    }
+}
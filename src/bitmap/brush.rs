@@ -0,0 +1,436 @@
+//! A brush engine for painting strokes onto a [`Bitmap`].
+//!
+//! A [`Stroke`] stamps the tip of a [`Brush`] at regular intervals along a smooth
+//! Catmull-Rom curve through the points it's given, which makes it a good fit for the
+//! [`on_press`](crate::TextureCanvas::on_press), [`on_move`](crate::TextureCanvas::on_move)
+//! and [`on_release`](crate::TextureCanvas::on_release) callbacks of a
+//! [`TextureCanvas`](crate::TextureCanvas):
+//!
+//! ```no_run
+//! # use iced_texture_canvas::Bitmap;
+//! # use iced_texture_canvas::bitmap::brush::{Brush, Stroke};
+//! # use iced_core::Point;
+//! # let mut bitmap = Bitmap::new(100, 100);
+//! # let (pressed, moved, released) = (Point::ORIGIN, Point::ORIGIN, Point::ORIGIN);
+//! // on_press
+//! let mut stroke = Stroke::new(Brush::default());
+//! stroke.add_point(&mut bitmap, pressed);
+//!
+//! // on_move
+//! stroke.add_point(&mut bitmap, moved);
+//!
+//! // on_release
+//! stroke.add_point(&mut bitmap, released);
+//! stroke.finish(&mut bitmap);
+//! ```
+use super::draw::blend;
+use super::{Bitmap, Rgba};
+
+use iced_core::{Point, Rectangle, Vector};
+
+use std::collections::{HashMap, VecDeque};
+
+/// The size of the tiles used to remember the pixels under a [`Stroke`].
+const TILE_SIZE: u32 = 64;
+
+/// The shape of the stamps of a [`Brush`].
+#[derive(Debug, Clone, Default)]
+pub enum Tip {
+    /// A circle, softened by the `hardness` of the [`Brush`].
+    #[default]
+    Round,
+    /// The alpha channel of a [`Bitmap`], scaled so its largest side matches the `size` of the [`Brush`].
+    Bitmap(Bitmap),
+}
+
+/// The settings of a brush.
+#[derive(Debug, Clone)]
+pub struct Brush {
+    /// The shape of each stamp.
+    pub tip: Tip,
+    /// The color painted by the brush.
+    pub color: Rgba,
+    /// The diameter of the brush in pixels.
+    pub size: f32,
+    /// How much of a [`Tip::Round`] is fully opaque, between `0.0` and `1.0`.
+    ///
+    /// The rest of the tip fades out smoothly towards its edge.
+    pub hardness: f32,
+    /// The maximum opacity of a whole [`Stroke`], between `0.0` and `1.0`.
+    ///
+    /// Overlapping stamps never build up past it.
+    pub opacity: f32,
+    /// The opacity of each stamp, between `0.0` and `1.0`.
+    ///
+    /// Overlapping stamps build up towards the `opacity` of the brush.
+    pub flow: f32,
+    /// The distance between stamps as a fraction of the `size`.
+    pub spacing: f32,
+    /// The number of input points averaged together to smooth out shaky strokes.
+    ///
+    /// Stabilization is disabled when this is `0` or `1`.
+    pub stabilization: usize,
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Self {
+            tip: Tip::Round,
+            color: Rgba::BLACK,
+            size: 8.0,
+            hardness: 0.8,
+            opacity: 1.0,
+            flow: 1.0,
+            spacing: 0.1,
+            stabilization: 0,
+        }
+    }
+}
+
+/// A single stroke of a [`Brush`], from the moment it touches the [`Bitmap`] until it's lifted.
+///
+/// The [`Bitmap`] must not be resized or edited by anything else while the [`Stroke`] is in progress.
+#[derive(Debug)]
+pub struct Stroke {
+    brush: Brush,
+    /// The most recent raw points, averaged for stabilization.
+    inputs: VecDeque<Point>,
+    /// The last few points of the curve. The segment before the last point is drawn once the next one arrives.
+    points: Vec<Point>,
+    /// The distance travelled along the curve since the last stamp.
+    distance: f32,
+    /// The stroke opacity and the original pixels under the stroke.
+    tiles: HashMap<(u32, u32), Tile>,
+}
+
+impl Stroke {
+    /// Start a new [`Stroke`] with the given [`Brush`].
+    pub fn new(brush: Brush) -> Self {
+        Self {
+            brush,
+            inputs: VecDeque::new(),
+            points: Vec::with_capacity(4),
+            distance: 0.0,
+            tiles: HashMap::new(),
+        }
+    }
+
+    /// The [`Brush`] of the [`Stroke`].
+    pub fn brush(&self) -> &Brush {
+        &self.brush
+    }
+
+    /// Continue the [`Stroke`] to a point in image coordinates.
+    ///
+    /// The first point is stamped right away. Because the curve needs to know where the stroke
+    /// goes next, the rest of the [`Stroke`] lags one point behind until it's [`finish`](Self::finish)ed.
+    ///
+    /// Returns the region of pixels that was painted, which is also marked as modified.
+    pub fn add_point(&mut self, bitmap: &mut Bitmap, point: Point) -> Option<Rectangle<u32>> {
+        let mut region = Region::default();
+
+        let point = self.stabilize(point);
+        self.push(bitmap, point, &mut region);
+
+        mark(bitmap, region)
+    }
+
+    /// Paint the rest of the [`Stroke`], catching up with the last point that was added.
+    ///
+    /// This ends the [`Stroke`]; start a new one for the next stroke of the [`Brush`].
+    ///
+    /// Returns the region of pixels that was painted, which is also marked as modified.
+    pub fn finish(mut self, bitmap: &mut Bitmap) -> Option<Rectangle<u32>> {
+        let mut region = Region::default();
+
+        // Feed the last point until the average settles on it.
+        if let Some(last) = self.inputs.back().copied() {
+            for _ in 1..self.inputs.len() {
+                let point = self.stabilize(last);
+                self.push(bitmap, point, &mut region);
+            }
+        }
+
+        if let [.., p1, p2] = self.points[..] {
+            let p0 = self
+                .points
+                .len()
+                .checked_sub(3)
+                .map_or(p1, |i| self.points[i]);
+            self.segment(bitmap, [p0, p1, p2, p2], &mut region);
+        }
+
+        mark(bitmap, region)
+    }
+
+    /// Average the most recent points when stabilization is enabled.
+    fn stabilize(&mut self, point: Point) -> Point {
+        let window = self.brush.stabilization.max(1);
+
+        self.inputs.push_back(point);
+
+        while self.inputs.len() > window {
+            self.inputs.pop_front();
+        }
+
+        let sum = self
+            .inputs
+            .iter()
+            .fold(Vector::new(0.0, 0.0), |sum, point| {
+                sum + Vector::new(point.x, point.y)
+            });
+
+        let count = self.inputs.len() as f32;
+        Point::new(sum.x / count, sum.y / count)
+    }
+
+    fn push(&mut self, bitmap: &mut Bitmap, point: Point, region: &mut Region) {
+        let Some(last) = self.points.last() else {
+            self.points.push(point);
+            self.stamp(bitmap, point, region);
+            return;
+        };
+
+        // Skip points that barely moved, they would only distort the curve.
+        if last.distance(point) < 0.5 {
+            return;
+        }
+
+        self.points.push(point);
+
+        if let [.., p1, p2, p3] = self.points[..] {
+            let p0 = self
+                .points
+                .len()
+                .checked_sub(4)
+                .map_or(p1, |i| self.points[i]);
+            self.segment(bitmap, [p0, p1, p2, p3], region);
+        }
+
+        if self.points.len() > 3 {
+            self.points.remove(0);
+        }
+    }
+
+    /// Stamp along the Catmull-Rom curve between the middle two points.
+    fn segment(&mut self, bitmap: &mut Bitmap, points: [Point; 4], region: &mut Region) {
+        let step = (self.brush.spacing * self.brush.size).max(0.25);
+        let length = points[0].distance(points[1])
+            + points[1].distance(points[2])
+            + points[2].distance(points[3]);
+
+        // Flatten the curve into pieces about half a pixel long.
+        let pieces = (length * 2.0).ceil().clamp(1.0, 4096.0) as usize;
+        let mut previous = points[1];
+
+        for i in 1..=pieces {
+            let next = catmull_rom(points, i as f32 / pieces as f32);
+            let piece = previous.distance(next);
+
+            if piece > 0.0 {
+                let direction = (next - previous) * (1.0 / piece);
+                let mut travelled = 0.0;
+
+                while self.distance + (piece - travelled) >= step {
+                    travelled += step - self.distance;
+                    self.distance = 0.0;
+                    self.stamp(bitmap, previous + direction * travelled, region);
+                }
+
+                self.distance += piece - travelled;
+            }
+
+            previous = next;
+        }
+    }
+
+    /// Stamp the tip of the [`Brush`] centered on the point.
+    fn stamp(&mut self, bitmap: &mut Bitmap, center: Point, region: &mut Region) {
+        let (width, height) = (bitmap.width(), bitmap.height());
+        let brush = &self.brush;
+
+        let half = match &brush.tip {
+            Tip::Round => Vector::new(brush.size / 2.0, brush.size / 2.0),
+            Tip::Bitmap(tip) => {
+                let scale = brush.size / tip.width().max(tip.height()) as f32;
+                Vector::new(
+                    tip.width() as f32 * scale / 2.0,
+                    tip.height() as f32 * scale / 2.0,
+                )
+            }
+        };
+        let half = Vector::new(half.x.max(0.5), half.y.max(0.5));
+
+        let left = (center.x - half.x).floor().clamp(0.0, width as f32) as u32;
+        let right = (center.x + half.x).ceil().clamp(0.0, width as f32) as u32;
+        let top = (center.y - half.y).floor().clamp(0.0, height as f32) as u32;
+        let bottom = (center.y + half.y).ceil().clamp(0.0, height as f32) as u32;
+
+        let flow = brush.flow.clamp(0.0, 1.0);
+        let opacity = brush.opacity.clamp(0.0, 1.0);
+        let row = width as usize;
+        let buffer = bitmap.buffer_mut_untracked();
+
+        for y in top..bottom {
+            for x in left..right {
+                let offset = Point::new(x as f32 + 0.5, y as f32 + 0.5) - center;
+
+                let alpha = match &brush.tip {
+                    Tip::Round => round(offset, half.x, brush.hardness),
+                    Tip::Bitmap(tip) => sampled(tip, offset, half),
+                } * flow;
+
+                if alpha <= 0.0 {
+                    continue;
+                }
+
+                let tile = self
+                    .tiles
+                    .entry((x / TILE_SIZE, y / TILE_SIZE))
+                    .or_insert_with(|| Tile::new(buffer, row, x / TILE_SIZE, y / TILE_SIZE));
+
+                let index = ((y % TILE_SIZE) * TILE_SIZE + x % TILE_SIZE) as usize;
+                let coverage = &mut tile.coverage[index];
+                *coverage += (1.0 - *coverage) * alpha;
+
+                let pixel = &mut buffer[y as usize * row + x as usize];
+                *pixel = tile.original[index];
+                blend(pixel, brush.color.to_u32(), *coverage * opacity);
+
+                region.add(x, y);
+            }
+        }
+    }
+}
+
+/// The pixels of a tile before the [`Stroke`] touched them, and how much the stroke covers them.
+#[derive(Debug)]
+struct Tile {
+    coverage: Vec<f32>,
+    original: Vec<u32>,
+}
+
+impl Tile {
+    fn new(buffer: &[u32], row: usize, column: u32, line: u32) -> Self {
+        let size = TILE_SIZE as usize;
+        let height = buffer.len() / row;
+        let (left, top) = (column as usize * size, line as usize * size);
+
+        let mut original = vec![0; size * size];
+
+        for (y, tile_row) in original.chunks_exact_mut(size).enumerate() {
+            if top + y >= height {
+                break;
+            }
+
+            let start = (top + y) * row + left;
+            let end = (top + y) * row + row.min(left + size);
+
+            tile_row[..end - start].copy_from_slice(&buffer[start..end]);
+        }
+
+        Self {
+            coverage: vec![0.0; size * size],
+            original,
+        }
+    }
+}
+
+/// The bounding box of the painted pixels.
+#[derive(Debug, Clone, Copy)]
+struct Region {
+    left: u32,
+    top: u32,
+    right: u32,
+    bottom: u32,
+}
+
+impl Default for Region {
+    fn default() -> Self {
+        Self {
+            left: u32::MAX,
+            top: u32::MAX,
+            right: 0,
+            bottom: 0,
+        }
+    }
+}
+
+impl Region {
+    fn add(&mut self, x: u32, y: u32) {
+        self.left = self.left.min(x);
+        self.top = self.top.min(y);
+        self.right = self.right.max(x + 1);
+        self.bottom = self.bottom.max(y + 1);
+    }
+}
+
+/// Mark the painted region as modified.
+fn mark(bitmap: &mut Bitmap, region: Region) -> Option<Rectangle<u32>> {
+    let region = (region.left < region.right && region.top < region.bottom).then(|| Rectangle {
+        x: region.left,
+        y: region.top,
+        width: region.right - region.left,
+        height: region.bottom - region.top,
+    })?;
+
+    bitmap.mark_dirty(region);
+    Some(region)
+}
+
+/// The opacity of a round tip at an offset from its center.
+fn round(offset: Vector, radius: f32, hardness: f32) -> f32 {
+    let distance = offset.x.hypot(offset.y);
+    let solid = radius * hardness.clamp(0.0, 1.0);
+
+    // Anti-alias the edge over a single pixel.
+    let edge = (radius - distance + 0.5).clamp(0.0, 1.0);
+
+    if distance <= solid || solid >= radius {
+        return edge;
+    }
+
+    let t = ((distance - solid) / (radius - solid)).clamp(0.0, 1.0);
+    let falloff = 1.0 - t * t * (3.0 - 2.0 * t);
+
+    falloff * edge
+}
+
+/// The opacity of a bitmap tip at an offset from its center, sampled bilinearly from its alpha channel.
+fn sampled(tip: &Bitmap, offset: Vector, half: Vector) -> f32 {
+    let (width, height) = (tip.width() as f32, tip.height() as f32);
+
+    let u = (offset.x / half.x + 1.0) / 2.0 * width - 0.5;
+    let v = (offset.y / half.y + 1.0) / 2.0 * height - 0.5;
+
+    let alpha = |x: f32, y: f32| {
+        if x < 0.0 || y < 0.0 || x >= width || y >= height {
+            return 0.0;
+        }
+
+        let pixel = tip.buffer()[y as usize * tip.width() as usize + x as usize];
+        pixel.to_ne_bytes()[3] as f32 / 255.0
+    };
+
+    let (x, y) = (u.floor(), v.floor());
+    let (fx, fy) = (u - x, v - y);
+
+    let top = alpha(x, y) * (1.0 - fx) + alpha(x + 1.0, y) * fx;
+    let bottom = alpha(x, y + 1.0) * (1.0 - fx) + alpha(x + 1.0, y + 1.0) * fx;
+
+    top * (1.0 - fy) + bottom * fy
+}
+
+/// A point along a uniform Catmull-Rom curve between `p1` and `p2`.
+fn catmull_rom([p0, p1, p2, p3]: [Point; 4], t: f32) -> Point {
+    let (t2, t3) = (t * t, t * t * t);
+
+    let axis = |a: f32, b: f32, c: f32, d: f32| {
+        0.5 * (2.0 * b
+            + (c - a) * t
+            + (2.0 * a - 5.0 * b + 4.0 * c - d) * t2
+            + (3.0 * b - a - 3.0 * c + d) * t3)
+    };
+
+    Point::new(axis(p0.x, p1.x, p2.x, p3.x), axis(p0.y, p1.y, p2.y, p3.y))
+}
@@ -0,0 +1,151 @@
+//! Dependency free encoding and decoding of [`Bitmap`] images.
+//!
+//! Supports the [QOI](https://qoiformat.org) format and the binary Netpbm formats
+//! (PPM, PGM and PAM), reading and writing the `RGBA` buffer of a [`Bitmap`] directly.
+//!
+//! Encoders write each row as soon as it's given to them, so images can be streamed
+//! into any [`Write`]r without being encoded in memory first.
+//! Decoders read exactly one image, so several images can be read back to back from the same stream.
+//!
+//! QOI and PAM round-trip exactly. PPM drops the alpha channel, and PGM also drops the color.
+pub mod netpbm;
+pub mod qoi;
+
+use super::Bitmap;
+
+use std::io::{self, BufRead, Write};
+
+/// The largest number of pixels a decoded image may have.
+const MAX_PIXELS: u64 = 400_000_000;
+
+/// An image format supported by the [`codec`](self) module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// The Quite OK Image format. Lossless.
+    Qoi,
+    /// A binary Portable PixMap (`P6`). Discards the alpha channel.
+    Ppm,
+    /// A binary Portable GrayMap (`P5`). Stores the luma of each pixel and discards the alpha channel.
+    Pgm,
+    /// A Portable Arbitrary Map (`P7`) with the `RGB_ALPHA` tuple type. Lossless.
+    Pam,
+}
+
+impl Format {
+    /// Guess the [`Format`] of an encoded image from its first few bytes.
+    ///
+    /// Plain (ASCII) Netpbm images are detected as their binary counterparts.
+    pub fn guess(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [b'q', b'o', b'i', b'f', ..] => Some(Format::Qoi),
+            [b'P', b'3' | b'6', ..] => Some(Format::Ppm),
+            [b'P', b'2' | b'5', ..] => Some(Format::Pgm),
+            [b'P', b'7', ..] => Some(Format::Pam),
+            _ => None,
+        }
+    }
+}
+
+/// An error that can occur when decoding a [`Bitmap`].
+#[derive(Debug)]
+pub enum Error {
+    /// The image could not be read, or ended early.
+    Io(io::Error),
+    /// The data isn't in any of the supported formats.
+    UnknownFormat,
+    /// The header of the image is malformed.
+    InvalidHeader,
+    /// The image is valid, but uses a feature that isn't supported.
+    Unsupported,
+    /// The image has more than 400 million pixels.
+    TooLarge,
+    /// The pixel data of the image is malformed.
+    InvalidData,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "io error: {error}"),
+            Error::UnknownFormat => write!(f, "unknown image format"),
+            Error::InvalidHeader => write!(f, "invalid image header"),
+            Error::Unsupported => write!(f, "unsupported image"),
+            Error::TooLarge => write!(f, "the image is too large"),
+            Error::InvalidData => write!(f, "invalid image data"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl Bitmap {
+    /// Decode a [`Bitmap`] from a QOI or Netpbm image, guessing the [`Format`] from its contents.
+    ///
+    /// Only a single image is read, leaving the rest of the `reader` untouched.
+    pub fn decode(reader: impl BufRead) -> Result<Self, Error> {
+        decode_with(reader, &mut |_, _, _| {})
+    }
+
+    /// Encode the [`Bitmap`] into the `writer` with the given [`Format`].
+    pub fn encode(&self, format: Format, writer: impl Write) -> io::Result<()> {
+        match format {
+            Format::Qoi => qoi::encode(self, writer),
+            Format::Ppm => netpbm::encode(self, netpbm::Kind::Ppm, writer),
+            Format::Pgm => netpbm::encode(self, netpbm::Kind::Pgm, writer),
+            Format::Pam => netpbm::encode(self, netpbm::Kind::Pam, writer),
+        }
+    }
+}
+
+/// Decode an image of any supported [`Format`],
+/// calling `rows` with the pixels decoded so far, the width and the height after every row.
+pub(crate) fn decode_with(
+    mut reader: impl BufRead,
+    rows: &mut dyn FnMut(&[u32], u32, u32),
+) -> Result<Bitmap, Error> {
+    match Format::guess(reader.fill_buf()?) {
+        Some(Format::Qoi) => qoi::decode_with(reader, rows),
+        Some(Format::Ppm | Format::Pgm | Format::Pam) => netpbm::decode_with(reader, rows),
+        None => Err(Error::UnknownFormat),
+    }
+}
+
+/// Allocate a [`Bitmap`] for an image described by a header.
+fn allocate(width: u32, height: u32) -> Result<Bitmap, Error> {
+    if width == 0 || height == 0 {
+        return Err(Error::InvalidHeader);
+    }
+
+    if width as u64 * height as u64 > MAX_PIXELS {
+        return Err(Error::TooLarge);
+    }
+
+    Ok(Bitmap::new(width, height))
+}
+
+fn read_u8(reader: &mut impl BufRead) -> io::Result<u8> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+/// The error returned by [`Encoder::finish`](qoi::Encoder::finish) when rows are missing.
+fn incomplete() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "not all rows of the image were written",
+    )
+}
@@ -0,0 +1,456 @@
+//! The [Netpbm](https://netpbm.sourceforge.net/doc/) formats.
+//!
+//! Binary PPM, PGM and PAM images can be written, and their plain (ASCII) variants can also be read.
+//! Samples with a maximum value other than 255 are rescaled to 8 bits when decoding.
+use super::{Bitmap, Error, allocate, incomplete, read_u8};
+
+use std::io::{self, BufRead, Write};
+
+/// The kind of Netpbm image written by an [`Encoder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// A binary Portable PixMap (`P6`). Discards the alpha channel.
+    Ppm,
+    /// A binary Portable GrayMap (`P5`). Stores the luma of each pixel and discards the alpha channel.
+    Pgm,
+    /// A Portable Arbitrary Map (`P7`) with the `RGB_ALPHA` tuple type. Lossless.
+    Pam,
+}
+
+impl Kind {
+    fn channels(self) -> usize {
+        match self {
+            Kind::Ppm => 3,
+            Kind::Pgm => 1,
+            Kind::Pam => 4,
+        }
+    }
+}
+
+/// Encode a [`Bitmap`] as a Netpbm image of the given [`Kind`].
+pub fn encode(bitmap: &Bitmap, kind: Kind, writer: impl Write) -> io::Result<()> {
+    let mut encoder = Encoder::new(writer, bitmap.width(), bitmap.height(), kind)?;
+
+    for row in bitmap.buffer().chunks_exact(bitmap.width() as usize) {
+        encoder.write_row(row)?;
+    }
+
+    encoder.finish().map(drop)
+}
+
+/// Decode a PPM, PGM or PAM image into a [`Bitmap`].
+///
+/// Grayscale images are expanded to `RGBA`, and images without an alpha channel are opaque.
+/// Reading stops right after the last sample of the image.
+pub fn decode(reader: impl BufRead) -> Result<Bitmap, Error> {
+    decode_with(reader, &mut |_, _, _| {})
+}
+
+/// Decode a Netpbm image, calling `rows` with the pixels decoded so far after every row.
+pub(super) fn decode_with(
+    mut reader: impl BufRead,
+    rows: &mut dyn FnMut(&[u32], u32, u32),
+) -> Result<Bitmap, Error> {
+    let mut magic = [0; 2];
+    reader.read_exact(&mut magic)?;
+
+    let header = match magic {
+        [b'P', b'2'] => read_header(&mut reader, 1, true)?,
+        [b'P', b'3'] => read_header(&mut reader, 3, true)?,
+        [b'P', b'5'] => read_header(&mut reader, 1, false)?,
+        [b'P', b'6'] => read_header(&mut reader, 3, false)?,
+        [b'P', b'7'] => read_pam_header(&mut reader)?,
+        [b'P', b'1' | b'4'] => return Err(Error::Unsupported),
+        _ => return Err(Error::UnknownFormat),
+    };
+
+    let mut bitmap = allocate(header.width, header.height)?;
+
+    let width = header.width as usize;
+    let samples = width * header.depth;
+    let bytes = if header.maxval > 255 { 2 } else { 1 };
+
+    let mut row = vec![0; samples * bytes];
+    let mut values = vec![0; samples];
+
+    let buffer = bitmap.buffer_mut();
+
+    for end in (width..=buffer.len()).step_by(width) {
+        let output = &mut buffer[end - width..end];
+
+        if header.plain {
+            for value in values.iter_mut() {
+                *value = read_number(&mut reader)?.ok_or(Error::InvalidData)?;
+            }
+        } else {
+            reader.read_exact(&mut row)?;
+
+            for (value, sample) in values.iter_mut().zip(row.chunks_exact(bytes)) {
+                *value = match sample {
+                    [byte] => *byte as u32,
+                    [high, low] => u16::from_be_bytes([*high, *low]) as u32,
+                    _ => unreachable!(),
+                };
+            }
+        }
+
+        for (output, tuple) in output.iter_mut().zip(values.chunks_exact(header.depth)) {
+            let channel = |index: usize| {
+                let value = tuple[index].min(header.maxval);
+                ((value * 255 + header.maxval / 2) / header.maxval) as u8
+            };
+
+            let pixel = match header.depth {
+                1 => [channel(0), channel(0), channel(0), 255],
+                2 => [channel(0), channel(0), channel(0), channel(1)],
+                3 => [channel(0), channel(1), channel(2), 255],
+                _ => [channel(0), channel(1), channel(2), channel(3)],
+            };
+
+            *output = u32::from_ne_bytes(pixel);
+        }
+
+        rows(&buffer[..end], header.width, header.height);
+    }
+
+    Ok(bitmap)
+}
+
+/// Writes a Netpbm image row by row.
+///
+/// Rows are converted and written as soon as they're given to the [`Encoder`],
+/// so the whole image never has to be held in memory.
+pub struct Encoder<W: Write> {
+    writer: W,
+    kind: Kind,
+    width: u32,
+    height: u32,
+    rows: u32,
+    row: Vec<u8>,
+}
+
+impl<W: Write> Encoder<W> {
+    /// Create an [`Encoder`] for an image of the given size and [`Kind`], writing its header.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either the `width` or `height` is zero.
+    pub fn new(mut writer: W, width: u32, height: u32, kind: Kind) -> io::Result<Self> {
+        assert!(width > 0, "width must be greater than 0");
+        assert!(height > 0, "height must be greater than 0");
+
+        match kind {
+            Kind::Ppm => write!(writer, "P6\n{width} {height}\n255\n")?,
+            Kind::Pgm => write!(writer, "P5\n{width} {height}\n255\n")?,
+            Kind::Pam => write!(
+                writer,
+                "P7\nWIDTH {width}\nHEIGHT {height}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n"
+            )?,
+        }
+
+        Ok(Self {
+            writer,
+            kind,
+            width,
+            height,
+            rows: 0,
+            row: Vec::with_capacity(width as usize * kind.channels()),
+        })
+    }
+
+    /// Write the next row of `RGBA` pixels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of the row doesn't match the width of the image,
+    /// or if every row has already been written.
+    pub fn write_row(&mut self, row: &[u32]) -> io::Result<()> {
+        assert_eq!(row.len(), self.width as usize, "Size mismatch!");
+        assert!(
+            self.rows < self.height,
+            "every row has already been written"
+        );
+
+        self.row.clear();
+
+        for [r, g, b, a] in row.iter().map(|pixel| pixel.to_ne_bytes()) {
+            match self.kind {
+                Kind::Ppm => self.row.extend_from_slice(&[r, g, b]),
+                Kind::Pgm => self.row.push(luma(r, g, b)),
+                Kind::Pam => self.row.extend_from_slice(&[r, g, b, a]),
+            }
+        }
+
+        self.rows += 1;
+        self.writer.write_all(&self.row)
+    }
+
+    /// Flush the image and return the inner writer.
+    ///
+    /// Returns an error if not every row was written.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.rows != self.height {
+            return Err(incomplete());
+        }
+
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+struct Header {
+    width: u32,
+    height: u32,
+    depth: usize,
+    maxval: u32,
+    plain: bool,
+}
+
+fn read_header(reader: &mut impl BufRead, depth: usize, plain: bool) -> Result<Header, Error> {
+    let mut next = || read_number(reader)?.ok_or(Error::InvalidHeader);
+
+    let header = Header {
+        width: next()?,
+        height: next()?,
+        depth,
+        maxval: next()?,
+        plain,
+    };
+
+    // A single whitespace character separates the header from the samples.
+    if !read_u8(reader)?.is_ascii_whitespace() {
+        return Err(Error::InvalidHeader);
+    }
+
+    validate_maxval(header)
+}
+
+fn read_pam_header(reader: &mut impl BufRead) -> Result<Header, Error> {
+    let (mut width, mut height, mut depth, mut maxval) = (None, None, None, None);
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Err(Error::InvalidHeader);
+        }
+
+        let line = std::str::from_utf8(&line)
+            .map_err(|_| Error::InvalidHeader)?
+            .trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once(char::is_whitespace)
+            .map(|(key, value)| (key, value.trim()))
+            .unwrap_or((line, ""));
+
+        let number = || value.parse::<u32>().map_err(|_| Error::InvalidHeader);
+
+        match key {
+            "WIDTH" => width = Some(number()?),
+            "HEIGHT" => height = Some(number()?),
+            "DEPTH" => depth = Some(number()?),
+            "MAXVAL" => maxval = Some(number()?),
+            "TUPLTYPE" => {}
+            "ENDHDR" => break,
+            _ => return Err(Error::InvalidHeader),
+        }
+    }
+
+    let (Some(width), Some(height), Some(depth), Some(maxval)) = (width, height, depth, maxval)
+    else {
+        return Err(Error::InvalidHeader);
+    };
+
+    if !(1..=4).contains(&depth) {
+        return Err(Error::Unsupported);
+    }
+
+    validate_maxval(Header {
+        width,
+        height,
+        depth: depth as usize,
+        maxval,
+        plain: false,
+    })
+}
+
+fn validate_maxval(header: Header) -> Result<Header, Error> {
+    if (1..=u16::MAX as u32).contains(&header.maxval) {
+        Ok(header)
+    } else {
+        Err(Error::InvalidHeader)
+    }
+}
+
+/// Read an ASCII number, skipping any whitespace and comments before it.
+///
+/// Returns [`None`] if something other than a number was found.
+fn read_number(reader: &mut impl BufRead) -> io::Result<Option<u32>> {
+    loop {
+        match peek(reader)? {
+            Some(b'#') => {
+                reader.skip_until(b'\n')?;
+            }
+            Some(byte) if byte.is_ascii_whitespace() => reader.consume(1),
+            Some(byte) if byte.is_ascii_digit() => break,
+            Some(_) => return Ok(None),
+            None => return Err(io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+
+    let mut number: u32 = 0;
+
+    while let Some(byte) = peek(reader)?.filter(u8::is_ascii_digit) {
+        reader.consume(1);
+
+        let Some(next) = number
+            .checked_mul(10)
+            .and_then(|number| number.checked_add((byte - b'0') as u32))
+        else {
+            return Ok(None);
+        };
+
+        number = next;
+    }
+
+    Ok(Some(number))
+}
+
+fn peek(reader: &mut impl BufRead) -> io::Result<Option<u8>> {
+    Ok(reader.fill_buf()?.first().copied())
+}
+
+/// The luma of a color with the Rec. 601 coefficients.
+///
+/// Gray colors map to themselves, so gray images round-trip exactly.
+fn luma(r: u8, g: u8, b: u8) -> u8 {
+    ((77 * r as u32 + 150 * g as u32 + 29 * b as u32 + 128) >> 8) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixels(bitmap: &Bitmap) -> Vec<[u8; 4]> {
+        bitmap
+            .buffer()
+            .iter()
+            .map(|pixel| pixel.to_ne_bytes())
+            .collect()
+    }
+
+    fn decode_pixels(bytes: &[u8]) -> (u32, u32, Vec<[u8; 4]>) {
+        let bitmap = decode(bytes).unwrap();
+        (bitmap.width(), bitmap.height(), pixels(&bitmap))
+    }
+
+    #[test]
+    fn roundtrip() {
+        let colors = [[10, 20, 30, 40], [128, 128, 128, 255], [255, 0, 7, 0]];
+        let mut bitmap = Bitmap::new(3, 1);
+
+        for (output, color) in bitmap.buffer_mut().iter_mut().zip(colors) {
+            *output = u32::from_ne_bytes(color);
+        }
+
+        let encoded = |kind| {
+            let mut encoded = Vec::new();
+            encode(&bitmap, kind, &mut encoded).unwrap();
+            encoded
+        };
+
+        assert_eq!(decode_pixels(&encoded(Kind::Pam)), (3, 1, colors.to_vec()));
+        assert_eq!(
+            decode_pixels(&encoded(Kind::Ppm)),
+            (
+                3,
+                1,
+                vec![[10, 20, 30, 255], [128, 128, 128, 255], [255, 0, 7, 255]]
+            )
+        );
+
+        let gray = decode_pixels(&encoded(Kind::Pgm)).2;
+        assert_eq!(gray[1], [128, 128, 128, 255]);
+        assert!(
+            gray.iter()
+                .all(|[r, g, b, a]| r == g && g == b && *a == 255)
+        );
+    }
+
+    #[test]
+    fn plain_headers_with_comments() {
+        assert_eq!(
+            decode_pixels(b"P2\n# a comment\n2 # width\n1\n# maxval\n1\n0 # dark\n1\n"),
+            (2, 1, vec![[0, 0, 0, 255], [255, 255, 255, 255]])
+        );
+        assert_eq!(
+            decode_pixels(b"P3 # comment\n1 1 255\n# samples\n1 2\n3"),
+            (1, 1, vec![[1, 2, 3, 255]])
+        );
+    }
+
+    #[test]
+    fn binary_headers_with_comments() {
+        assert_eq!(
+            decode_pixels(b"P5\n# comment\n2 1\n# 16-bit\n65535\n\xff\xff\x80\x00"),
+            (2, 1, vec![[255, 255, 255, 255], [128, 128, 128, 255]])
+        );
+        assert_eq!(
+            decode_pixels(b"P6 # comment\n1 # width\n1 255\n\x01\x02\x03"),
+            (1, 1, vec![[1, 2, 3, 255]])
+        );
+        assert_eq!(
+            decode_pixels(
+                b"P7\n# comment\nWIDTH 1\nHEIGHT 1\n\nDEPTH 2\n# grayscale with alpha\nMAXVAL 255\nTUPLTYPE GRAYSCALE_ALPHA\nENDHDR\n\x40\x80"
+            ),
+            (1, 1, vec![[64, 64, 64, 128]])
+        );
+    }
+
+    #[test]
+    fn bitmaps_are_unsupported() {
+        assert!(matches!(
+            decode(&b"P1\n# comment\n1 1\n1"[..]),
+            Err(Error::Unsupported)
+        ));
+        assert!(matches!(
+            decode(&b"P4\n1 1\n\x80"[..]),
+            Err(Error::Unsupported)
+        ));
+    }
+
+    #[test]
+    fn invalid_headers() {
+        for header in [
+            &b"P6\n1 1\n0\n\x00\x00\x00"[..],
+            b"P6\n1 1\n65536\n\x00\x00\x00",
+            b"P6\n0 1\n255\n",
+            b"P6\nx 1\n255\n",
+            b"P7\nWIDTH 1\nHEIGHT 1\nMAXVAL 255\nENDHDR\n",
+            b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 4\nMAXVAL 255\nBOGUS\nENDHDR\n",
+        ] {
+            assert!(matches!(decode(header), Err(Error::InvalidHeader)));
+        }
+
+        assert!(matches!(
+            decode(&b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 5\nMAXVAL 255\nENDHDR\n"[..]),
+            Err(Error::Unsupported)
+        ));
+    }
+
+    #[test]
+    fn back_to_back() {
+        let mut reader = &b"P5 1 1 255\n\x10P2 1 1 255 32 rest"[..];
+
+        assert_eq!(pixels(&decode(&mut reader).unwrap()), [[16, 16, 16, 255]]);
+        assert_eq!(pixels(&decode(&mut reader).unwrap()), [[32, 32, 32, 255]]);
+        assert_eq!(reader, b" rest");
+    }
+}
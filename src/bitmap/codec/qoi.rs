@@ -0,0 +1,409 @@
+//! The [Quite OK Image](https://qoiformat.org) format.
+use super::{Bitmap, Error, allocate, incomplete, read_u8};
+
+use std::io::{self, BufRead, Write};
+
+const MAGIC: [u8; 4] = *b"qoif";
+const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+
+const OP_INDEX: u8 = 0b0000_0000;
+const OP_DIFF: u8 = 0b0100_0000;
+const OP_LUMA: u8 = 0b1000_0000;
+const OP_RUN: u8 = 0b1100_0000;
+const OP_RGB: u8 = 0b1111_1110;
+const OP_RGBA: u8 = 0b1111_1111;
+
+const MAX_RUN: u8 = 62;
+
+/// Encode a [`Bitmap`] as a QOI image.
+pub fn encode(bitmap: &Bitmap, writer: impl Write) -> io::Result<()> {
+    let mut encoder = Encoder::new(writer, bitmap.width(), bitmap.height())?;
+
+    for row in bitmap.buffer().chunks_exact(bitmap.width() as usize) {
+        encoder.write_row(row)?;
+    }
+
+    encoder.finish().map(drop)
+}
+
+/// Decode a QOI image into a [`Bitmap`].
+///
+/// Reading stops right after the end marker of the image.
+pub fn decode(reader: impl BufRead) -> Result<Bitmap, Error> {
+    decode_with(reader, &mut |_, _, _| {})
+}
+
+/// Decode a QOI image, calling `rows` with the pixels decoded so far after every row.
+pub(super) fn decode_with(
+    mut reader: impl BufRead,
+    rows: &mut dyn FnMut(&[u32], u32, u32),
+) -> Result<Bitmap, Error> {
+    let mut header = [0; 14];
+    reader.read_exact(&mut header)?;
+
+    if header[..4] != MAGIC {
+        return Err(Error::UnknownFormat);
+    }
+
+    let width = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+    let height = u32::from_be_bytes([header[8], header[9], header[10], header[11]]);
+    let (channels, colorspace) = (header[12], header[13]);
+
+    if !matches!(channels, 3 | 4) || colorspace > 1 {
+        return Err(Error::InvalidHeader);
+    }
+
+    let mut bitmap = allocate(width, height)?;
+
+    let mut index = [[0; 4]; 64];
+    let mut pixel = [0, 0, 0, 255];
+    let mut run = 0;
+
+    let buffer = bitmap.buffer_mut();
+    let row = width as usize;
+
+    for end in (row..=buffer.len()).step_by(row) {
+        for output in &mut buffer[end - row..end] {
+            if run > 0 {
+                run -= 1;
+            } else {
+                let tag = read_u8(&mut reader)?;
+
+                match tag {
+                    OP_RGB => reader.read_exact(&mut pixel[..3])?,
+                    OP_RGBA => reader.read_exact(&mut pixel)?,
+                    _ => match tag & 0b1100_0000 {
+                        OP_INDEX => pixel = index[tag as usize],
+                        OP_DIFF => {
+                            pixel[0] = pixel[0].wrapping_add((tag >> 4 & 0b11).wrapping_sub(2));
+                            pixel[1] = pixel[1].wrapping_add((tag >> 2 & 0b11).wrapping_sub(2));
+                            pixel[2] = pixel[2].wrapping_add((tag & 0b11).wrapping_sub(2));
+                        }
+                        OP_LUMA => {
+                            let next = read_u8(&mut reader)?;
+                            let green = (tag & 0b11_1111).wrapping_sub(32);
+
+                            pixel[0] = pixel[0]
+                                .wrapping_add(green)
+                                .wrapping_add((next >> 4).wrapping_sub(8));
+                            pixel[1] = pixel[1].wrapping_add(green);
+                            pixel[2] = pixel[2]
+                                .wrapping_add(green)
+                                .wrapping_add((next & 0b1111).wrapping_sub(8));
+                        }
+                        _ => run = tag & 0b11_1111,
+                    },
+                }
+
+                index[hash(pixel)] = pixel;
+            }
+
+            *output = u32::from_ne_bytes(pixel);
+        }
+
+        rows(&buffer[..end], width, height);
+    }
+
+    let mut marker = [0; 8];
+    reader.read_exact(&mut marker)?;
+
+    if marker != END_MARKER {
+        return Err(Error::InvalidData);
+    }
+
+    Ok(bitmap)
+}
+
+/// Writes a QOI image row by row.
+///
+/// Rows are encoded and written as soon as they're given to the [`Encoder`],
+/// so the whole image never has to be held in memory.
+pub struct Encoder<W: Write> {
+    writer: W,
+    width: u32,
+    height: u32,
+    rows: u32,
+    index: [[u8; 4]; 64],
+    previous: [u8; 4],
+    run: u8,
+    chunk: Vec<u8>,
+}
+
+impl<W: Write> Encoder<W> {
+    /// Create an [`Encoder`] for an image of the given size, writing its header.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either the `width` or `height` is zero.
+    pub fn new(mut writer: W, width: u32, height: u32) -> io::Result<Self> {
+        assert!(width > 0, "width must be greater than 0");
+        assert!(height > 0, "height must be greater than 0");
+
+        let mut header = [0; 14];
+        header[..4].copy_from_slice(&MAGIC);
+        header[4..8].copy_from_slice(&width.to_be_bytes());
+        header[8..12].copy_from_slice(&height.to_be_bytes());
+        header[12] = 4;
+        header[13] = 0;
+
+        writer.write_all(&header)?;
+
+        Ok(Self {
+            writer,
+            width,
+            height,
+            rows: 0,
+            index: [[0; 4]; 64],
+            previous: [0, 0, 0, 255],
+            run: 0,
+            chunk: Vec::with_capacity(width as usize * 5),
+        })
+    }
+
+    /// Encode the next row of `RGBA` pixels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of the row doesn't match the width of the image,
+    /// or if every row has already been written.
+    pub fn write_row(&mut self, row: &[u32]) -> io::Result<()> {
+        assert_eq!(row.len(), self.width as usize, "Size mismatch!");
+        assert!(
+            self.rows < self.height,
+            "every row has already been written"
+        );
+
+        self.chunk.clear();
+
+        for pixel in row.iter().map(|pixel| pixel.to_ne_bytes()) {
+            if pixel == self.previous {
+                self.run += 1;
+
+                if self.run == MAX_RUN {
+                    self.flush_run();
+                }
+
+                continue;
+            }
+
+            self.flush_run();
+
+            let hash = hash(pixel);
+
+            if self.index[hash] == pixel {
+                self.chunk.push(OP_INDEX | hash as u8);
+            } else {
+                self.index[hash] = pixel;
+                self.push_pixel(pixel);
+            }
+
+            self.previous = pixel;
+        }
+
+        self.rows += 1;
+
+        if self.rows == self.height {
+            self.flush_run();
+        }
+
+        self.writer.write_all(&self.chunk)
+    }
+
+    /// Write the end marker of the image and return the inner writer.
+    ///
+    /// Returns an error if not every row was written.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.rows != self.height {
+            return Err(incomplete());
+        }
+
+        self.writer.write_all(&END_MARKER)?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+
+    fn flush_run(&mut self) {
+        if self.run > 0 {
+            self.chunk.push(OP_RUN | (self.run - 1));
+            self.run = 0;
+        }
+    }
+
+    fn push_pixel(&mut self, pixel: [u8; 4]) {
+        let [r, g, b, a] = pixel;
+        let [pr, pg, pb, pa] = self.previous;
+
+        if a != pa {
+            self.chunk.extend_from_slice(&[OP_RGBA, r, g, b, a]);
+            return;
+        }
+
+        let red = r.wrapping_sub(pr) as i8;
+        let green = g.wrapping_sub(pg) as i8;
+        let blue = b.wrapping_sub(pb) as i8;
+
+        let green_red = red.wrapping_sub(green);
+        let green_blue = blue.wrapping_sub(green);
+
+        if (-2..2).contains(&red) && (-2..2).contains(&green) && (-2..2).contains(&blue) {
+            self.chunk.push(
+                OP_DIFF | ((red + 2) as u8) << 4 | ((green + 2) as u8) << 2 | (blue + 2) as u8,
+            );
+        } else if (-32..32).contains(&green)
+            && (-8..8).contains(&green_red)
+            && (-8..8).contains(&green_blue)
+        {
+            self.chunk.extend_from_slice(&[
+                OP_LUMA | (green + 32) as u8,
+                ((green_red + 8) as u8) << 4 | (green_blue + 8) as u8,
+            ]);
+        } else {
+            self.chunk.extend_from_slice(&[OP_RGB, r, g, b]);
+        }
+    }
+}
+
+fn hash([r, g, b, a]: [u8; 4]) -> usize {
+    (r as usize * 3 + g as usize * 5 + b as usize * 7 + a as usize * 11) % 64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bitmap(width: u32, height: u32, pixels: &[[u8; 4]]) -> Bitmap {
+        let mut bitmap = Bitmap::new(width, height);
+
+        for (output, pixel) in bitmap.buffer_mut().iter_mut().zip(pixels) {
+            *output = u32::from_ne_bytes(*pixel);
+        }
+
+        bitmap
+    }
+
+    /// Encode the [`Bitmap`], check that it decodes back exactly, and return the encoded chunks.
+    fn roundtrip(bitmap: &Bitmap) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        encode(bitmap, &mut encoded).unwrap();
+
+        let decoded = decode(&encoded[..]).unwrap();
+        assert_eq!(
+            (decoded.width(), decoded.height()),
+            (bitmap.width(), bitmap.height())
+        );
+        assert_eq!(decoded.buffer(), bitmap.buffer());
+
+        assert_eq!(encoded[..4], MAGIC);
+        assert_eq!(encoded[encoded.len() - 8..], END_MARKER);
+        encoded[14..encoded.len() - 8].to_vec()
+    }
+
+    #[test]
+    fn operations() {
+        let luma = [12, 10, 9, 255];
+        let pixels = [
+            [1, 0, 0, 255],
+            [1, 0, 0, 128],
+            [1, 0, 0, 255],
+            luma,
+            luma,
+            [200, 100, 50, 255],
+        ];
+
+        assert_eq!(
+            roundtrip(&bitmap(3, 2, &pixels)),
+            [
+                OP_DIFF | 3 << 4 | 2 << 2 | 2,
+                OP_RGBA,
+                1,
+                0,
+                0,
+                128,
+                OP_INDEX | hash([1, 0, 0, 255]) as u8,
+                OP_LUMA | 42,
+                9 << 4 | 7,
+                OP_RUN,
+                OP_RGB,
+                200,
+                100,
+                50,
+            ]
+        );
+    }
+
+    #[test]
+    fn runs() {
+        let black = [[0, 0, 0, 255]; 130];
+        let chunks = [OP_RUN | 61, OP_RUN | 61, OP_RUN | 5];
+
+        assert_eq!(roundtrip(&bitmap(130, 1, &black)), chunks);
+        assert_eq!(roundtrip(&bitmap(2, 65, &black)), chunks);
+        assert_eq!(roundtrip(&bitmap(62, 1, &black)), [OP_RUN | 61]);
+        assert_eq!(roundtrip(&bitmap(63, 1, &black)), [OP_RUN | 61, OP_RUN]);
+    }
+
+    #[test]
+    fn alpha() {
+        let pixels: Vec<_> = (0..=255)
+            .map(|alpha| [alpha, 255 - alpha, alpha / 2, alpha])
+            .collect();
+
+        roundtrip(&bitmap(16, 16, &pixels));
+        roundtrip(&bitmap(1, 1, &[[0, 0, 0, 0]]));
+    }
+
+    #[test]
+    fn noise() {
+        let mut state = 0x2545_f491_u32;
+        let pixels: Vec<_> = (0..64 * 64)
+            .map(|i| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+
+                match i % 7 {
+                    0..=2 => state.to_ne_bytes(),
+                    3 => [(i % 4) as u8, 1, 2, 255],
+                    _ => [(state % 3) as u8 * 100, 0, 0, 255],
+                }
+            })
+            .collect();
+
+        roundtrip(&bitmap(64, 64, &pixels));
+    }
+
+    #[test]
+    fn back_to_back() {
+        let first = bitmap(2, 1, &[[1, 2, 3, 4], [5, 6, 7, 8]]);
+        let second = bitmap(1, 2, &[[9, 9, 9, 9], [0, 0, 0, 255]]);
+
+        let mut encoded = Vec::new();
+        encode(&first, &mut encoded).unwrap();
+        encode(&second, &mut encoded).unwrap();
+
+        let mut reader = &encoded[..];
+        assert_eq!(decode(&mut reader).unwrap().buffer(), first.buffer());
+        assert_eq!(decode(&mut reader).unwrap().buffer(), second.buffer());
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn invalid() {
+        let mut encoded = Vec::new();
+        encode(&bitmap(1, 1, &[[1, 2, 3, 4]]), &mut encoded).unwrap();
+
+        let mut corrupted = encoded.clone();
+        *corrupted.last_mut().unwrap() = 0;
+        assert!(matches!(decode(&corrupted[..]), Err(Error::InvalidData)));
+
+        let mut header = encoded.clone();
+        header[12] = 2;
+        assert!(matches!(decode(&header[..]), Err(Error::InvalidHeader)));
+
+        assert!(matches!(
+            decode(&encoded[..encoded.len() - 1]),
+            Err(Error::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,456 @@
+//! Copying and compositing one [`Bitmap`] onto another.
+//!
+//! [`Bitmap::blit`] copies pixels as they are, while [`Bitmap::composite`] combines them with
+//! one of the Porter-Duff [`Operator`]s and a [`BlendMode`], following the
+//! [W3C compositing model](https://www.w3.org/TR/compositing-1/).
+//!
+//! Both take a rectangle of the source and the point of the destination it's copied to,
+//! and clip them to the bounds of both bitmaps. The loops run row by row over contiguous
+//! slices, so the common cases can be vectorized by the compiler.
+use super::Bitmap;
+
+use iced_core::{Point, Rectangle};
+
+/// How the color of the source and destination are combined with their alpha.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Operator {
+    /// Neither the source nor the destination is kept.
+    Clear,
+    /// Only the source is kept.
+    Source,
+    /// Only the destination is kept.
+    Destination,
+    /// The source is placed over the destination.
+    #[default]
+    SourceOver,
+    /// The destination is placed over the source.
+    DestinationOver,
+    /// The part of the source inside of the destination replaces the destination.
+    SourceIn,
+    /// The part of the destination inside of the source replaces the destination.
+    DestinationIn,
+    /// The part of the source outside of the destination replaces the destination.
+    SourceOut,
+    /// The part of the destination outside of the source is kept.
+    DestinationOut,
+    /// The part of the source inside of the destination is placed over the destination.
+    SourceAtop,
+    /// The part of the destination inside of the source is placed over the source.
+    DestinationAtop,
+    /// The parts of the source and destination that don't overlap are kept.
+    Xor,
+    /// The source and destination are added together.
+    Plus,
+}
+
+/// How the colors of the source and destination are mixed where they overlap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// The source color is used as is.
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+}
+
+/// How the color channels of a [`Bitmap`] relate to its alpha channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Alpha {
+    /// The color channels are independent of the alpha channel.
+    #[default]
+    Straight,
+    /// The color channels have already been multiplied by the alpha channel.
+    Premultiplied,
+}
+
+/// The settings of [`Bitmap::composite`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Composite {
+    pub operator: Operator,
+    pub blend: BlendMode,
+    /// The opacity of the source, between `0.0` and `1.0`.
+    pub opacity: f32,
+    /// How the pixels of both the source and destination are stored.
+    pub alpha: Alpha,
+}
+
+impl Default for Composite {
+    fn default() -> Self {
+        Self {
+            operator: Operator::SourceOver,
+            blend: BlendMode::Normal,
+            opacity: 1.0,
+            alpha: Alpha::Straight,
+        }
+    }
+}
+
+impl Composite {
+    /// Composite with the given [`Operator`] and the default settings.
+    pub fn new(operator: Operator) -> Self {
+        Self {
+            operator,
+            ..Self::default()
+        }
+    }
+
+    /// Set the [`BlendMode`].
+    pub fn blend(mut self, blend: BlendMode) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    /// Set the opacity of the source.
+    pub fn opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    /// Set how the pixels are stored.
+    pub fn alpha(mut self, alpha: Alpha) -> Self {
+        self.alpha = alpha;
+        self
+    }
+}
+
+impl Bitmap {
+    /// Copy a rectangle of the `source` to the `destination` point, replacing the pixels underneath.
+    ///
+    /// Returns the region of the [`Bitmap`] that was written to, which is also marked as modified.
+    pub fn blit(
+        &mut self,
+        source: &Bitmap,
+        rectangle: Rectangle<u32>,
+        destination: Point<i32>,
+    ) -> Option<Rectangle<u32>> {
+        let area = Area::clip(source, rectangle, self, destination)?;
+
+        let (source_width, width) = (source.width() as usize, self.width() as usize);
+        let buffer = self.buffer_mut_untracked();
+
+        for (source_row, row) in area.rows(source_width, width) {
+            buffer[row].copy_from_slice(&source.buffer()[source_row]);
+        }
+
+        self.mark_dirty(area.region);
+        Some(area.region)
+    }
+
+    /// Composite a rectangle of the `source` onto the `destination` point.
+    ///
+    /// Returns the region of the [`Bitmap`] that was written to, which is also marked as modified.
+    pub fn composite(
+        &mut self,
+        source: &Bitmap,
+        rectangle: Rectangle<u32>,
+        destination: Point<i32>,
+        composite: Composite,
+    ) -> Option<Rectangle<u32>> {
+        let area = Area::clip(source, rectangle, self, destination)?;
+
+        let (source_width, width) = (source.width() as usize, self.width() as usize);
+        let opacity = composite.opacity.clamp(0.0, 1.0);
+        let integer_opacity = (opacity * 255.0).round() as u32;
+        let buffer = self.buffer_mut_untracked();
+
+        for (source_row, row) in area.rows(source_width, width) {
+            let (source_row, row) = (&source.buffer()[source_row], &mut buffer[row]);
+
+            match composite {
+                Composite {
+                    operator: Operator::SourceOver,
+                    blend: BlendMode::Normal,
+                    alpha: Alpha::Premultiplied,
+                    ..
+                } => source_over_premultiplied(row, source_row, integer_opacity),
+                Composite {
+                    operator: Operator::SourceOver,
+                    blend: BlendMode::Normal,
+                    alpha: Alpha::Straight,
+                    ..
+                } => source_over_straight(row, source_row, integer_opacity),
+                Composite {
+                    operator: Operator::Source,
+                    blend: BlendMode::Normal,
+                    ..
+                } if opacity == 1.0 => row.copy_from_slice(source_row),
+                _ => {
+                    for (pixel, source) in row.iter_mut().zip(source_row) {
+                        *pixel = composite_pixel(*source, *pixel, composite, opacity);
+                    }
+                }
+            }
+        }
+
+        self.mark_dirty(area.region);
+        Some(area.region)
+    }
+}
+
+/// The overlapping area of a source and destination [`Bitmap`].
+struct Area {
+    source: Point<u32>,
+    region: Rectangle<u32>,
+}
+
+impl Area {
+    fn clip(
+        source: &Bitmap,
+        rectangle: Rectangle<u32>,
+        destination: &Bitmap,
+        point: Point<i32>,
+    ) -> Option<Self> {
+        let left = rectangle.x.min(source.width()) as i64;
+        let top = rectangle.y.min(source.height()) as i64;
+        let right = rectangle
+            .x
+            .saturating_add(rectangle.width)
+            .min(source.width()) as i64;
+        let bottom = rectangle
+            .y
+            .saturating_add(rectangle.height)
+            .min(source.height()) as i64;
+
+        let (mut source_x, mut source_y) = (left, top);
+        let (mut x, mut y) = (point.x as i64, point.y as i64);
+        let (mut width, mut height) = (right - left, bottom - top);
+
+        if x < 0 {
+            source_x -= x;
+            width += x;
+            x = 0;
+        }
+
+        if y < 0 {
+            source_y -= y;
+            height += y;
+            y = 0;
+        }
+
+        width = width.min(destination.width() as i64 - x);
+        height = height.min(destination.height() as i64 - y);
+
+        (width > 0 && height > 0).then(|| Self {
+            source: Point::new(source_x as u32, source_y as u32),
+            region: Rectangle {
+                x: x as u32,
+                y: y as u32,
+                width: width as u32,
+                height: height as u32,
+            },
+        })
+    }
+
+    /// The index ranges of each row of the source and destination buffers.
+    fn rows(
+        &self,
+        source_width: usize,
+        width: usize,
+    ) -> impl Iterator<Item = (std::ops::Range<usize>, std::ops::Range<usize>)> + '_ {
+        let length = self.region.width as usize;
+
+        (0..self.region.height as usize).map(move |y| {
+            let source = (self.source.y as usize + y) * source_width + self.source.x as usize;
+            let destination = (self.region.y as usize + y) * width + self.region.x as usize;
+
+            (source..source + length, destination..destination + length)
+        })
+    }
+}
+
+/// Source-over of premultiplied pixels in integer arithmetic, with an `opacity` out of 255.
+fn source_over_premultiplied(row: &mut [u32], source: &[u32], opacity: u32) {
+    for (pixel, source) in row.iter_mut().zip(source) {
+        let source = source
+            .to_ne_bytes()
+            .map(|channel| div_255(channel as u32 * opacity));
+        let destination = pixel.to_ne_bytes();
+        let inverse = 255 - source[3];
+
+        *pixel = u32::from_ne_bytes(std::array::from_fn(|i| {
+            (source[i] + div_255(destination[i] as u32 * inverse)).min(255) as u8
+        }));
+    }
+}
+
+/// Source-over of straight pixels in integer arithmetic, with an `opacity` out of 255.
+///
+/// Only pixels where both the source and destination are translucent need a division.
+fn source_over_straight(row: &mut [u32], source: &[u32], opacity: u32) {
+    for (pixel, source) in row.iter_mut().zip(source) {
+        let source = source.to_ne_bytes();
+        let destination = pixel.to_ne_bytes();
+
+        let source_alpha = div_255(source[3] as u32 * opacity);
+        let inverse = 255 - source_alpha;
+        let destination_alpha = destination[3] as u32;
+
+        *pixel = match (source_alpha, destination_alpha) {
+            (0, _) => *pixel,
+            (255, _) => u32::from_ne_bytes([source[0], source[1], source[2], 255]),
+            (_, 255) => u32::from_ne_bytes(std::array::from_fn(|i| match i {
+                3 => 255,
+                _ => {
+                    div_255(source[i] as u32 * source_alpha + destination[i] as u32 * inverse) as u8
+                }
+            })),
+            _ => {
+                // The alpha of the result, scaled by 255.
+                let weight = source_alpha * 255 + destination_alpha * inverse;
+
+                u32::from_ne_bytes(std::array::from_fn(|i| match i {
+                    3 => div_255(weight) as u8,
+                    _ => {
+                        ((source[i] as u32 * source_alpha * 255
+                            + destination[i] as u32 * destination_alpha * inverse
+                            + weight / 2)
+                            / weight) as u8
+                    }
+                }))
+            }
+        };
+    }
+}
+
+/// Divide by 255 with rounding, exact for products of two bytes.
+fn div_255(value: u32) -> u32 {
+    let value = value + 128;
+    (value + (value >> 8)) >> 8
+}
+
+/// Composite a single pixel in floating point.
+fn composite_pixel(source: u32, destination: u32, composite: Composite, opacity: f32) -> u32 {
+    let premultiplied = composite.alpha == Alpha::Premultiplied;
+
+    let source = to_premultiplied(source, premultiplied).map(|channel| channel * opacity);
+    let backdrop = to_premultiplied(destination, premultiplied);
+
+    let (source_alpha, backdrop_alpha) = (source[3], backdrop[3]);
+
+    // Mix the blended color into the source where it overlaps the backdrop.
+    let source = if composite.blend == BlendMode::Normal || source_alpha <= 0.0 {
+        source
+    } else {
+        let mut mixed = source;
+
+        for i in 0..3 {
+            let color = source[i] / source_alpha;
+            let base = if backdrop_alpha > 0.0 {
+                backdrop[i] / backdrop_alpha
+            } else {
+                0.0
+            };
+
+            let blended = blend(composite.blend, base, color);
+            mixed[i] = source_alpha * ((1.0 - backdrop_alpha) * color + backdrop_alpha * blended);
+        }
+
+        mixed
+    };
+
+    let (fa, fb) = match composite.operator {
+        Operator::Clear => (0.0, 0.0),
+        Operator::Source => (1.0, 0.0),
+        Operator::Destination => (0.0, 1.0),
+        Operator::SourceOver => (1.0, 1.0 - source_alpha),
+        Operator::DestinationOver => (1.0 - backdrop_alpha, 1.0),
+        Operator::SourceIn => (backdrop_alpha, 0.0),
+        Operator::DestinationIn => (0.0, source_alpha),
+        Operator::SourceOut => (1.0 - backdrop_alpha, 0.0),
+        Operator::DestinationOut => (0.0, 1.0 - source_alpha),
+        Operator::SourceAtop => (backdrop_alpha, 1.0 - source_alpha),
+        Operator::DestinationAtop => (1.0 - backdrop_alpha, source_alpha),
+        Operator::Xor => (1.0 - backdrop_alpha, 1.0 - source_alpha),
+        Operator::Plus => (1.0, 1.0),
+    };
+
+    let output: [f32; 4] =
+        std::array::from_fn(|i| (fa * source[i] + fb * backdrop[i]).clamp(0.0, 1.0));
+
+    from_premultiplied(output, premultiplied)
+}
+
+/// A separable blend mode, given the backdrop and source colors.
+fn blend(mode: BlendMode, backdrop: f32, source: f32) -> f32 {
+    let multiply = |b: f32, s: f32| b * s;
+    let screen = |b: f32, s: f32| b + s - b * s;
+    let hard_light = |b: f32, s: f32| {
+        if s <= 0.5 {
+            multiply(b, 2.0 * s)
+        } else {
+            screen(b, 2.0 * s - 1.0)
+        }
+    };
+
+    match mode {
+        BlendMode::Normal => source,
+        BlendMode::Multiply => multiply(backdrop, source),
+        BlendMode::Screen => screen(backdrop, source),
+        BlendMode::Overlay => hard_light(source, backdrop),
+        BlendMode::Darken => backdrop.min(source),
+        BlendMode::Lighten => backdrop.max(source),
+        BlendMode::ColorDodge => {
+            if backdrop <= 0.0 {
+                0.0
+            } else if source >= 1.0 {
+                1.0
+            } else {
+                (backdrop / (1.0 - source)).min(1.0)
+            }
+        }
+        BlendMode::ColorBurn => {
+            if backdrop >= 1.0 {
+                1.0
+            } else if source <= 0.0 {
+                0.0
+            } else {
+                1.0 - ((1.0 - backdrop) / source).min(1.0)
+            }
+        }
+        BlendMode::HardLight => hard_light(backdrop, source),
+        BlendMode::SoftLight => {
+            if source <= 0.5 {
+                backdrop - (1.0 - 2.0 * source) * backdrop * (1.0 - backdrop)
+            } else {
+                let d = if backdrop <= 0.25 {
+                    ((16.0 * backdrop - 12.0) * backdrop + 4.0) * backdrop
+                } else {
+                    backdrop.sqrt()
+                };
+
+                backdrop + (2.0 * source - 1.0) * (d - backdrop)
+            }
+        }
+        BlendMode::Difference => (backdrop - source).abs(),
+        BlendMode::Exclusion => backdrop + source - 2.0 * backdrop * source,
+    }
+}
+
+fn to_premultiplied(pixel: u32, premultiplied: bool) -> [f32; 4] {
+    let [r, g, b, a] = pixel.to_ne_bytes().map(|channel| channel as f32 / 255.0);
+
+    if premultiplied {
+        [r, g, b, a]
+    } else {
+        [r * a, g * a, b * a, a]
+    }
+}
+
+fn from_premultiplied([r, g, b, a]: [f32; 4], premultiplied: bool) -> u32 {
+    let color = if premultiplied || a <= 0.0 {
+        [r, g, b, a]
+    } else {
+        [r / a, g / a, b / a, a]
+    };
+
+    u32::from_ne_bytes(color.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8))
+}
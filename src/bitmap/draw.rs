@@ -0,0 +1,746 @@
+//! Drawing shapes onto a [`Bitmap`].
+//!
+//! Shapes are drawn with a [`Painter`], in image coordinates where the pixel at `(x, y)`
+//! covers the area from `(x, y)` to `(x + 1, y + 1)`. Everything is clipped to the bounds of
+//! the [`Bitmap`] and blended over the existing pixels, optionally anti-aliased.
+//!
+//! Every primitive returns the exact region of pixels it touched,
+//! and only that region is marked as modified.
+use super::selection::{Mask, Mode};
+use super::{Bitmap, Rgba};
+
+use iced_core::{Point, Rectangle, Vector};
+
+use std::f32::consts::TAU;
+
+/// The number of sub-scanlines sampled per row when anti-aliasing.
+const SAMPLES: usize = 16;
+
+/// Draws shapes onto a [`Bitmap`].
+///
+/// Colors are anything that converts into an [`Rgba`] pixel.
+pub struct Painter<'a> {
+    bitmap: &'a mut Bitmap,
+    anti_alias: bool,
+}
+
+impl<'a> Painter<'a> {
+    /// Create a [`Painter`] that draws onto the [`Bitmap`].
+    pub fn new(bitmap: &'a mut Bitmap) -> Self {
+        Self {
+            bitmap,
+            anti_alias: false,
+        }
+    }
+
+    /// Smooth the edges of the shapes drawn by the [`Painter`].
+    ///
+    /// Without anti-aliasing, a pixel is covered when its center lies inside of the shape.
+    pub fn anti_alias(mut self, anti_alias: bool) -> Self {
+        self.anti_alias = anti_alias;
+        self
+    }
+
+    /// Set a single pixel, replacing its previous color.
+    pub fn put_pixel(&mut self, x: u32, y: u32, color: impl Into<Rgba>) -> Option<Rectangle<u32>> {
+        let color = color.into().to_u32();
+
+        if x >= self.bitmap.width() || y >= self.bitmap.height() {
+            return None;
+        }
+
+        let width = self.bitmap.width() as usize;
+        self.bitmap.buffer_mut_untracked()[y as usize * width + x as usize] = color;
+
+        self.finish(Region::pixel(x, y))
+    }
+
+    /// Draw a line with square caps.
+    ///
+    /// Lines no wider than a pixel are drawn with Bresenham's algorithm when anti-aliasing is off.
+    pub fn line(
+        &mut self,
+        from: Point,
+        to: Point,
+        width: f32,
+        color: impl Into<Rgba>,
+    ) -> Option<Rectangle<u32>> {
+        let color = color.into().to_u32();
+
+        if !self.anti_alias && width <= 1.0 {
+            let mut region = Region::default();
+            self.thin_line(from, to, color, &mut region);
+
+            return self.finish(region);
+        }
+
+        let half = width / 2.0;
+        let direction = to - from;
+        let length = direction.x.hypot(direction.y);
+
+        let direction = if length > 0.0 {
+            direction * (half / length)
+        } else {
+            Vector::new(half, 0.0)
+        };
+        let normal = Vector::new(-direction.y, direction.x);
+
+        self.fill(
+            &[vec![
+                from - direction + normal,
+                to + direction + normal,
+                to + direction - normal,
+                from - direction - normal,
+            ]],
+            Rule::NonZero,
+            color,
+        )
+    }
+
+    /// Draw connected lines through the `points`, with round joins and caps.
+    pub fn polyline(
+        &mut self,
+        points: &[Point],
+        width: f32,
+        color: impl Into<Rgba>,
+    ) -> Option<Rectangle<u32>> {
+        self.stroke_path(points, false, width, color.into().to_u32())
+    }
+
+    /// Fill a rectangle.
+    pub fn fill_rectangle(
+        &mut self,
+        rectangle: Rectangle,
+        color: impl Into<Rgba>,
+    ) -> Option<Rectangle<u32>> {
+        self.fill(&[corners(rectangle)], Rule::NonZero, color.into().to_u32())
+    }
+
+    /// Draw the outline of a rectangle, centered on its edges.
+    pub fn stroke_rectangle(
+        &mut self,
+        rectangle: Rectangle,
+        width: f32,
+        color: impl Into<Rgba>,
+    ) -> Option<Rectangle<u32>> {
+        let color = color.into().to_u32();
+
+        let half = width / 2.0;
+        let outer = Rectangle {
+            x: rectangle.x - half,
+            y: rectangle.y - half,
+            width: rectangle.width + width,
+            height: rectangle.height + width,
+        };
+
+        if rectangle.width <= width || rectangle.height <= width {
+            return self.fill_rectangle(outer, color);
+        }
+
+        let inner = Rectangle {
+            x: rectangle.x + half,
+            y: rectangle.y + half,
+            width: rectangle.width - width,
+            height: rectangle.height - width,
+        };
+
+        self.fill(&[corners(outer), corners(inner)], Rule::EvenOdd, color)
+    }
+
+    /// Fill a circle.
+    pub fn fill_circle(
+        &mut self,
+        center: Point,
+        radius: f32,
+        color: impl Into<Rgba>,
+    ) -> Option<Rectangle<u32>> {
+        self.fill_ellipse(center, Vector::new(radius, radius), color)
+    }
+
+    /// Draw the outline of a circle, centered on its edge.
+    pub fn stroke_circle(
+        &mut self,
+        center: Point,
+        radius: f32,
+        width: f32,
+        color: impl Into<Rgba>,
+    ) -> Option<Rectangle<u32>> {
+        self.stroke_ellipse(center, Vector::new(radius, radius), width, color)
+    }
+
+    /// Fill an axis aligned ellipse.
+    pub fn fill_ellipse(
+        &mut self,
+        center: Point,
+        radii: Vector,
+        color: impl Into<Rgba>,
+    ) -> Option<Rectangle<u32>> {
+        self.fill(
+            &[ellipse(center, radii)],
+            Rule::NonZero,
+            color.into().to_u32(),
+        )
+    }
+
+    /// Draw the outline of an axis aligned ellipse, centered on its edge.
+    pub fn stroke_ellipse(
+        &mut self,
+        center: Point,
+        radii: Vector,
+        width: f32,
+        color: impl Into<Rgba>,
+    ) -> Option<Rectangle<u32>> {
+        let color = color.into().to_u32();
+
+        let half = width / 2.0;
+        let outer = ellipse(center, Vector::new(radii.x + half, radii.y + half));
+
+        if radii.x <= half || radii.y <= half {
+            return self.fill(&[outer], Rule::NonZero, color);
+        }
+
+        let inner = ellipse(center, Vector::new(radii.x - half, radii.y - half));
+
+        self.fill(&[outer, inner], Rule::EvenOdd, color)
+    }
+
+    /// Fill a polygon.
+    ///
+    /// Self-intersecting polygons are filled with the even-odd rule, like [`Mask::polygon`].
+    pub fn fill_polygon(
+        &mut self,
+        points: &[Point],
+        color: impl Into<Rgba>,
+    ) -> Option<Rectangle<u32>> {
+        self.fill(&[points.to_vec()], Rule::EvenOdd, color.into().to_u32())
+    }
+
+    /// Draw the outline of a polygon with round joins, centered on its edges.
+    pub fn stroke_polygon(
+        &mut self,
+        points: &[Point],
+        width: f32,
+        color: impl Into<Rgba>,
+    ) -> Option<Rectangle<u32>> {
+        self.stroke_path(points, true, width, color.into().to_u32())
+    }
+
+    /// Fill the pixels connected to the `seed` with a similar color.
+    ///
+    /// A pixel is similar if none of its `RGBA` channels differ by more than the `tolerance`,
+    /// like [`Mask::magic_wand`].
+    pub fn flood_fill(
+        &mut self,
+        seed: Point<u32>,
+        tolerance: u8,
+        color: impl Into<Rgba>,
+    ) -> Option<Rectangle<u32>> {
+        let color = color.into().to_u32();
+
+        let mut mask = Mask::for_bitmap(self.bitmap);
+        mask.magic_wand(self.bitmap, seed, tolerance, true, Mode::Replace);
+
+        let bounds = mask.bounds()?;
+        let width = self.bitmap.width() as usize;
+        let buffer = self.bitmap.buffer_mut_untracked();
+
+        for y in bounds.y..bounds.y + bounds.height {
+            for x in bounds.x..bounds.x + bounds.width {
+                if mask.contains(x, y) {
+                    blend(&mut buffer[y as usize * width + x as usize], color, 1.0);
+                }
+            }
+        }
+
+        self.bitmap.mark_dirty(bounds);
+        Some(bounds)
+    }
+
+    /// Mark the touched region as modified.
+    fn finish(&mut self, region: Region) -> Option<Rectangle<u32>> {
+        let region = region.into_rectangle()?;
+
+        self.bitmap.mark_dirty(region);
+        Some(region)
+    }
+
+    /// Stroke each segment of a path, with a disc on every vertex for the joins and caps.
+    fn stroke_path(
+        &mut self,
+        points: &[Point],
+        closed: bool,
+        width: f32,
+        color: u32,
+    ) -> Option<Rectangle<u32>> {
+        let segments = points
+            .windows(2)
+            .map(|segment| (segment[0], segment[1]))
+            .chain(
+                closed
+                    .then(|| points.first().zip(points.last()))
+                    .flatten()
+                    .map(|(first, last)| (*last, *first)),
+            );
+
+        if !self.anti_alias && width <= 1.0 {
+            let mut region = Region::default();
+
+            for (from, to) in segments {
+                self.thin_line(from, to, color, &mut region);
+            }
+
+            return self.finish(region);
+        }
+
+        let half = width / 2.0;
+        let mut contours: Vec<Vec<Point>> = Vec::new();
+
+        for (from, to) in segments {
+            let direction = to - from;
+            let length = direction.x.hypot(direction.y);
+
+            if length > 0.0 {
+                let normal = Vector::new(-direction.y, direction.x) * (half / length);
+
+                contours.push(vec![from + normal, to + normal, to - normal, from - normal]);
+            }
+        }
+
+        contours.extend(
+            points
+                .iter()
+                .map(|point| ellipse(*point, Vector::new(half, half))),
+        );
+
+        // The union of the contours is only filled if they all wind the same way.
+        for contour in &mut contours {
+            if signed_area(contour) < 0.0 {
+                contour.reverse();
+            }
+        }
+
+        self.fill(&contours, Rule::NonZero, color)
+    }
+
+    /// Draw a line one pixel wide with Bresenham's algorithm.
+    fn thin_line(&mut self, from: Point, to: Point, color: u32, region: &mut Region) {
+        let (width, height) = (self.bitmap.width(), self.bitmap.height());
+
+        let Some((from, to)) = clip_segment(from, to, width as f32, height as f32) else {
+            return;
+        };
+
+        let (mut x, mut y) = (from.x.floor() as i64, from.y.floor() as i64);
+        let (x1, y1) = (to.x.floor() as i64, to.y.floor() as i64);
+
+        let dx = (x1 - x).abs();
+        let dy = -(y1 - y).abs();
+        let (sx, sy) = ((x1 - x).signum(), (y1 - y).signum());
+        let mut error = dx + dy;
+
+        let buffer = self.bitmap.buffer_mut_untracked();
+
+        loop {
+            if (0..width as i64).contains(&x) && (0..height as i64).contains(&y) {
+                blend(
+                    &mut buffer[y as usize * width as usize + x as usize],
+                    color,
+                    1.0,
+                );
+                region.add(x as u32, y as u32, x as u32 + 1);
+            }
+
+            if x == x1 && y == y1 {
+                break;
+            }
+
+            let doubled = error * 2;
+
+            if doubled >= dy {
+                error += dy;
+                x += sx;
+            }
+
+            if doubled <= dx {
+                error += dx;
+                y += sy;
+            }
+        }
+    }
+
+    /// Fill the area enclosed by the contours with the given [`Rule`].
+    fn fill(&mut self, contours: &[Vec<Point>], rule: Rule, color: u32) -> Option<Rectangle<u32>> {
+        let (width, height) = (self.bitmap.width(), self.bitmap.height());
+
+        let mut edges: Vec<Edge> = contours
+            .iter()
+            .flat_map(|contour| {
+                let next = contour.iter().cycle().skip(1);
+                contour
+                    .iter()
+                    .zip(next)
+                    .filter_map(|(a, b)| Edge::new(*a, *b))
+            })
+            .collect();
+
+        if edges.is_empty() {
+            return None;
+        }
+
+        edges.sort_by(|a, b| a.top.total_cmp(&b.top));
+
+        let (mut left, mut right) = (f32::INFINITY, f32::NEG_INFINITY);
+        let (mut top, mut bottom) = (f32::INFINITY, f32::NEG_INFINITY);
+
+        for edge in &edges {
+            left = left.min(edge.top_x).min(edge.bottom_x);
+            right = right.max(edge.top_x).max(edge.bottom_x);
+            top = top.min(edge.top);
+            bottom = bottom.max(edge.bottom);
+        }
+
+        let start = left.floor().clamp(0.0, width as f32) as u32;
+        let end = right.ceil().clamp(0.0, width as f32) as u32;
+        let first_row = top.floor().clamp(0.0, height as f32) as u32;
+        let last_row = bottom.ceil().clamp(0.0, height as f32) as u32;
+
+        if start >= end || first_row >= last_row {
+            return None;
+        }
+
+        let samples = if self.anti_alias { SAMPLES } else { 1 };
+        let weight = 1.0 / samples as f32;
+
+        let mut coverage = Coverage::new(start, end);
+        let mut region = Region::default();
+        let mut active: Vec<usize> = Vec::new();
+        let mut crossings: Vec<(f32, i32)> = Vec::new();
+        let mut next = 0;
+
+        let row_width = width as usize;
+        let buffer = self.bitmap.buffer_mut_untracked();
+
+        for y in first_row..last_row {
+            coverage.clear();
+
+            for sample in 0..samples {
+                let scanline = y as f32 + (sample as f32 + 0.5) * weight;
+
+                while next < edges.len() && edges[next].top <= scanline {
+                    active.push(next);
+                    next += 1;
+                }
+
+                active.retain(|index| edges[*index].bottom > scanline);
+
+                crossings.clear();
+                crossings.extend(
+                    active
+                        .iter()
+                        .map(|index| &edges[*index])
+                        .map(|edge| (edge.x_at(scanline), edge.winding)),
+                );
+                crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+                let mut winding = 0;
+
+                for pair in crossings.windows(2) {
+                    winding += pair[0].1;
+
+                    let inside = match rule {
+                        Rule::NonZero => winding != 0,
+                        Rule::EvenOdd => winding % 2 != 0,
+                    };
+
+                    if inside {
+                        if self.anti_alias {
+                            coverage.add_span(pair[0].0, pair[1].0, weight);
+                        } else {
+                            coverage.add_centers(pair[0].0, pair[1].0);
+                        }
+                    }
+                }
+            }
+
+            let row = &mut buffer[y as usize * row_width..][..row_width];
+
+            for (x, amount) in coverage.resolve() {
+                blend(&mut row[x as usize], color, amount);
+                region.add(x, y, x + 1);
+            }
+        }
+
+        self.finish(region)
+    }
+}
+
+impl Bitmap {
+    /// Create a [`Painter`] to draw shapes onto the [`Bitmap`].
+    pub fn painter(&mut self) -> Painter<'_> {
+        Painter::new(self)
+    }
+}
+
+/// How the inside of overlapping contours is determined.
+#[derive(Debug, Clone, Copy)]
+enum Rule {
+    NonZero,
+    EvenOdd,
+}
+
+/// An edge of a contour, going downwards.
+#[derive(Debug, Clone, Copy)]
+struct Edge {
+    top: f32,
+    bottom: f32,
+    top_x: f32,
+    bottom_x: f32,
+    winding: i32,
+}
+
+impl Edge {
+    fn new(a: Point, b: Point) -> Option<Self> {
+        if a.y == b.y || !(a.y.is_finite() && b.y.is_finite() && a.x.is_finite() && b.x.is_finite())
+        {
+            return None;
+        }
+
+        let (top, bottom, winding) = if a.y < b.y { (a, b, 1) } else { (b, a, -1) };
+
+        Some(Self {
+            top: top.y,
+            bottom: bottom.y,
+            top_x: top.x,
+            bottom_x: bottom.x,
+            winding,
+        })
+    }
+
+    fn x_at(&self, y: f32) -> f32 {
+        let t = (y - self.top) / (self.bottom - self.top);
+        self.top_x + (self.bottom_x - self.top_x) * t
+    }
+}
+
+/// The coverage of each pixel of a row, accumulated from horizontal spans.
+struct Coverage {
+    start: u32,
+    /// Coverage of the pixels partially covered at the ends of spans.
+    partial: Vec<f32>,
+    /// Differences in coverage of fully covered pixels, summed from left to right.
+    runs: Vec<f32>,
+}
+
+impl Coverage {
+    fn new(start: u32, end: u32) -> Self {
+        let len = (end - start) as usize;
+
+        Self {
+            start,
+            partial: vec![0.0; len],
+            runs: vec![0.0; len + 1],
+        }
+    }
+
+    fn clear(&mut self) {
+        self.partial.fill(0.0);
+        self.runs.fill(0.0);
+    }
+
+    /// Add the exact horizontal coverage of the span between `a` and `b`.
+    fn add_span(&mut self, a: f32, b: f32, weight: f32) {
+        let len = self.partial.len() as f32;
+        let a = (a - self.start as f32).clamp(0.0, len);
+        let b = (b - self.start as f32).clamp(0.0, len);
+
+        if b <= a {
+            return;
+        }
+
+        let (first, last) = (a.floor() as usize, b.floor() as usize);
+
+        if first == last {
+            self.partial[first] += (b - a) * weight;
+            return;
+        }
+
+        self.partial[first] += (first as f32 + 1.0 - a) * weight;
+        self.runs[first + 1] += weight;
+        self.runs[last] -= weight;
+
+        if last < self.partial.len() {
+            self.partial[last] += (b - last as f32) * weight;
+        }
+    }
+
+    /// Fully cover the pixels whose centers lie between `a` and `b`.
+    fn add_centers(&mut self, a: f32, b: f32) {
+        let len = self.partial.len() as f32;
+        let first = (a - self.start as f32 - 0.5).ceil().clamp(0.0, len) as usize;
+        let last = (b - self.start as f32 - 0.5).ceil().clamp(0.0, len) as usize;
+
+        if first < last {
+            self.runs[first] += 1.0;
+            self.runs[last] -= 1.0;
+        }
+    }
+
+    /// The covered pixels of the row and their coverage.
+    fn resolve(&self) -> impl Iterator<Item = (u32, f32)> + '_ {
+        self.partial
+            .iter()
+            .zip(self.runs.iter().scan(0.0, |sum, run| {
+                *sum += run;
+                Some(*sum)
+            }))
+            .zip(self.start..)
+            .map(|((partial, run), x)| (x, (partial + run).clamp(0.0, 1.0)))
+            .filter(|(_, amount)| *amount > 0.5 / 255.0)
+    }
+}
+
+/// The bounding box of the touched pixels.
+#[derive(Debug, Clone, Copy)]
+struct Region {
+    left: u32,
+    top: u32,
+    right: u32,
+    bottom: u32,
+}
+
+impl Default for Region {
+    fn default() -> Self {
+        Self {
+            left: u32::MAX,
+            top: u32::MAX,
+            right: 0,
+            bottom: 0,
+        }
+    }
+}
+
+impl Region {
+    fn pixel(x: u32, y: u32) -> Self {
+        let mut region = Self::default();
+        region.add(x, y, x + 1);
+        region
+    }
+
+    /// Add the pixels from `x` up to `right` on row `y`.
+    fn add(&mut self, x: u32, y: u32, right: u32) {
+        self.left = self.left.min(x);
+        self.top = self.top.min(y);
+        self.right = self.right.max(right);
+        self.bottom = self.bottom.max(y + 1);
+    }
+
+    fn into_rectangle(self) -> Option<Rectangle<u32>> {
+        (self.left < self.right && self.top < self.bottom).then(|| Rectangle {
+            x: self.left,
+            y: self.top,
+            width: self.right - self.left,
+            height: self.bottom - self.top,
+        })
+    }
+}
+
+/// Blend the `color` over the pixel, scaling its alpha by the `coverage`.
+pub(super) fn blend(pixel: &mut u32, color: u32, coverage: f32) {
+    let source = color.to_ne_bytes();
+    let alpha = source[3] as f32 / 255.0 * coverage;
+
+    if alpha >= 254.5 / 255.0 {
+        *pixel = color;
+        return;
+    }
+
+    let destination = pixel.to_ne_bytes();
+    let destination_alpha = destination[3] as f32 / 255.0 * (1.0 - alpha);
+    let output_alpha = alpha + destination_alpha;
+
+    if output_alpha <= 0.0 {
+        return;
+    }
+
+    let channel = |i: usize| {
+        let value =
+            (source[i] as f32 * alpha + destination[i] as f32 * destination_alpha) / output_alpha;
+        value.round() as u8
+    };
+
+    *pixel = u32::from_ne_bytes([
+        channel(0),
+        channel(1),
+        channel(2),
+        (output_alpha * 255.0).round() as u8,
+    ]);
+}
+
+/// Clip a line segment to the area between the origin and `width` by `height`.
+fn clip_segment(from: Point, to: Point, width: f32, height: f32) -> Option<(Point, Point)> {
+    let delta = to - from;
+    let (mut enter, mut exit) = (0.0f32, 1.0f32);
+
+    for (p, q) in [
+        (-delta.x, from.x),
+        (delta.x, width - from.x),
+        (-delta.y, from.y),
+        (delta.y, height - from.y),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else if p < 0.0 {
+            enter = enter.max(q / p);
+        } else {
+            exit = exit.min(q / p);
+        }
+    }
+
+    (enter <= exit).then(|| (from + delta * enter, from + delta * exit))
+}
+
+fn corners(rectangle: Rectangle) -> Vec<Point> {
+    let Rectangle {
+        x,
+        y,
+        width,
+        height,
+    } = rectangle;
+
+    vec![
+        Point::new(x, y),
+        Point::new(x + width, y),
+        Point::new(x + width, y + height),
+        Point::new(x, y + height),
+    ]
+}
+
+/// Approximate an ellipse with segments about a pixel long.
+fn ellipse(center: Point, radii: Vector) -> Vec<Point> {
+    let segments = (TAU * radii.x.abs().max(radii.y.abs()))
+        .ceil()
+        .clamp(8.0, 4096.0) as usize;
+
+    (0..segments)
+        .map(|i| {
+            let angle = i as f32 / segments as f32 * TAU;
+            Point::new(
+                center.x + radii.x * angle.cos(),
+                center.y + radii.y * angle.sin(),
+            )
+        })
+        .collect()
+}
+
+fn signed_area(contour: &[Point]) -> f32 {
+    let next = contour.iter().cycle().skip(1);
+
+    contour
+        .iter()
+        .zip(next)
+        .map(|(a, b)| a.x * b.y - b.x * a.y)
+        .sum::<f32>()
+        / 2.0
+}
@@ -0,0 +1,685 @@
+//! Image filters and convolutions for a [`Bitmap`].
+//!
+//! Filters are applied through [`Filters`], which can be restricted to a [`region`](Filters::region)
+//! and a [`mask`](Filters::mask). Only the pixels inside of both are reprocessed and marked as
+//! modified, while pixels around them are still read by the kernels. Pixels outside of the
+//! [`Bitmap`] are sampled according to the [`Edge`] mode.
+//!
+//! Convolutions are computed with premultiplied alpha, so transparent pixels don't bleed
+//! their color into their neighbours.
+use super::Bitmap;
+use super::selection::Mask;
+
+use iced_core::Rectangle;
+
+/// How pixels outside of the [`Bitmap`] are sampled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Edge {
+    /// Repeat the closest pixel on the edge.
+    #[default]
+    Clamp,
+    /// Wrap around to the opposite edge.
+    Wrap,
+    /// Reflect the pixels along the edge.
+    Mirror,
+    /// Treat every pixel outside as transparent.
+    Transparent,
+}
+
+/// A convolution kernel of arbitrary size.
+///
+/// The kernel is centered on the pixel being computed, rounding up and to the left for even sizes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kernel {
+    width: u32,
+    height: u32,
+    weights: Vec<f32>,
+}
+
+impl Kernel {
+    /// Create a [`Kernel`] from its weights, row by row.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of weights doesn't match `width * height`, or if either is zero.
+    pub fn new(width: u32, height: u32, weights: impl Into<Vec<f32>>) -> Self {
+        let weights = weights.into();
+
+        assert!(width > 0 && height > 0, "kernel must not be empty");
+        assert_eq!(
+            width as usize * height as usize,
+            weights.len(),
+            "Size mismatch!"
+        );
+
+        Self {
+            width,
+            height,
+            weights,
+        }
+    }
+
+    /// Scale the weights so they add up to one, keeping the brightness of the image.
+    ///
+    /// Kernels whose weights add up to zero are left as they are.
+    pub fn normalized(mut self) -> Self {
+        let total: f32 = self.weights.iter().sum();
+
+        if total != 0.0 {
+            self.weights.iter_mut().for_each(|weight| *weight /= total);
+        }
+
+        self
+    }
+
+    /// Get the width of the [`Kernel`].
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Get the height of the [`Kernel`].
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Get the weights of the [`Kernel`], row by row.
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+}
+
+/// Applies filters to a [`Bitmap`].
+///
+/// Every filter returns the region of pixels it reprocessed, and only that region is marked as modified.
+pub struct Filters<'a> {
+    bitmap: &'a mut Bitmap,
+    region: Option<Rectangle<u32>>,
+    mask: Option<&'a Mask>,
+    edge: Edge,
+}
+
+impl<'a> Filters<'a> {
+    /// Create [`Filters`] that apply to the whole [`Bitmap`].
+    pub fn new(bitmap: &'a mut Bitmap) -> Self {
+        Self {
+            bitmap,
+            region: None,
+            mask: None,
+            edge: Edge::default(),
+        }
+    }
+
+    /// Only apply the filters inside of the rectangle.
+    pub fn region(mut self, region: Rectangle<u32>) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    /// Only apply the filters to the pixels selected by the [`Mask`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the [`Mask`] doesn't match the [`Bitmap`].
+    pub fn mask(mut self, mask: &'a Mask) -> Self {
+        assert!(
+            mask.width() == self.bitmap.width() && mask.height() == self.bitmap.height(),
+            "Size mismatch!"
+        );
+
+        self.mask = Some(mask);
+        self
+    }
+
+    /// Set how pixels outside of the [`Bitmap`] are sampled.
+    pub fn edge(mut self, edge: Edge) -> Self {
+        self.edge = edge;
+        self
+    }
+
+    /// Blur with a Gaussian of the given standard deviation, in pixels.
+    pub fn gaussian_blur(&mut self, sigma: f32) -> Option<Rectangle<u32>> {
+        let weights = gaussian(sigma);
+        self.separable(&weights, &weights)
+    }
+
+    /// Blur by averaging the square of pixels within the `radius`.
+    pub fn box_blur(&mut self, radius: u32) -> Option<Rectangle<u32>> {
+        let weights = vec![1.0 / (radius * 2 + 1) as f32; radius as usize * 2 + 1];
+        self.separable(&weights, &weights)
+    }
+
+    /// Sharpen by adding the difference from a Gaussian blur, scaled by the `amount`.
+    ///
+    /// Colors that differ by less than the `threshold`, between `0.0` and `1.0`, are left as they are.
+    pub fn unsharp_mask(
+        &mut self,
+        sigma: f32,
+        amount: f32,
+        threshold: f32,
+    ) -> Option<Rectangle<u32>> {
+        let target = self.target()?;
+        let weights = gaussian(sigma);
+        let blurred = self.blur(target, &weights, &weights);
+
+        self.apply(target, |x, y, pixel| {
+            let original = to_premultiplied(*pixel);
+            let blurred = blurred.get(x as i64, y as i64);
+            let (original_alpha, blurred_alpha) = (original[3], blurred[3]);
+
+            if original_alpha <= 0.0 {
+                return;
+            }
+
+            let mut sharpened = original;
+
+            for i in 0..3 {
+                let color = original[i] / original_alpha;
+                let base = if blurred_alpha > 0.0 {
+                    blurred[i] / blurred_alpha
+                } else {
+                    color
+                };
+
+                if (color - base).abs() >= threshold {
+                    sharpened[i] =
+                        (color + amount * (color - base)).clamp(0.0, 1.0) * original_alpha;
+                }
+            }
+
+            *pixel = from_premultiplied(sharpened);
+        })
+    }
+
+    /// Replace each pixel with the gradient magnitude of the luma, using the Sobel operator.
+    ///
+    /// The alpha channel is kept as is.
+    pub fn sobel(&mut self) -> Option<Rectangle<u32>> {
+        const X: [f32; 9] = [-1.0, 0.0, 1.0, -2.0, 0.0, 2.0, -1.0, 0.0, 1.0];
+        const Y: [f32; 9] = [-1.0, -2.0, -1.0, 0.0, 0.0, 0.0, 1.0, 2.0, 1.0];
+
+        self.edges(|luma| {
+            let gx: f32 = luma.iter().zip(X).map(|(luma, weight)| luma * weight).sum();
+            let gy: f32 = luma.iter().zip(Y).map(|(luma, weight)| luma * weight).sum();
+
+            (gx * gx + gy * gy).sqrt()
+        })
+    }
+
+    /// Replace each pixel with the magnitude of the Laplacian of the luma.
+    ///
+    /// The alpha channel is kept as is.
+    pub fn laplacian(&mut self) -> Option<Rectangle<u32>> {
+        const WEIGHTS: [f32; 9] = [0.0, 1.0, 0.0, 1.0, -4.0, 1.0, 0.0, 1.0, 0.0];
+
+        self.edges(|luma| {
+            luma.iter()
+                .zip(WEIGHTS)
+                .map(|(luma, weight)| luma * weight)
+                .sum::<f32>()
+                .abs()
+        })
+    }
+
+    /// Replace each channel with its median within the square of the given `radius`, removing noise.
+    pub fn median(&mut self, radius: u32) -> Option<Rectangle<u32>> {
+        let target = self.target()?;
+        let window = Window::new(self.bitmap, target, radius, radius, self.edge, false);
+        let radius = radius as i64;
+        let size = (radius as usize * 2 + 1).pow(2);
+        let mut channels: [Vec<f32>; 4] = std::array::from_fn(|_| Vec::with_capacity(size));
+
+        self.apply(target, |x, y, pixel| {
+            let (x, y) = (x as i64, y as i64);
+
+            for values in &mut channels {
+                values.clear();
+            }
+
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    let sample = window.get(x + dx, y + dy);
+
+                    for (values, value) in channels.iter_mut().zip(sample) {
+                        values.push(value);
+                    }
+                }
+            }
+
+            let median = channels
+                .each_mut()
+                .map(|values| *values.select_nth_unstable_by(size / 2, f32::total_cmp).1);
+
+            *pixel = from_straight(median);
+        })
+    }
+
+    /// Convolve with an arbitrary [`Kernel`].
+    pub fn convolve(&mut self, kernel: &Kernel) -> Option<Rectangle<u32>> {
+        let target = self.target()?;
+        let (left, top) = (kernel.width / 2, kernel.height / 2);
+        let (right, bottom) = (kernel.width - 1 - left, kernel.height - 1 - top);
+
+        let window = Window::new(
+            self.bitmap,
+            target,
+            left.max(right),
+            top.max(bottom),
+            self.edge,
+            true,
+        );
+
+        self.apply(target, |x, y, pixel| {
+            let mut sum = [0.0; 4];
+
+            for (i, weight) in kernel.weights.iter().enumerate() {
+                let dx = (i as u32 % kernel.width) as i64 - left as i64;
+                let dy = (i as u32 / kernel.width) as i64 - top as i64;
+                let sample = window.get(x as i64 + dx, y as i64 + dy);
+
+                for channel in 0..4 {
+                    sum[channel] += sample[channel] * weight;
+                }
+            }
+
+            *pixel = from_premultiplied(sum);
+        })
+    }
+
+    /// Set each pixel to white if its luma is at least the `level`, or to black otherwise.
+    ///
+    /// The alpha channel is kept as is.
+    pub fn threshold(&mut self, level: u8) -> Option<Rectangle<u32>> {
+        let target = self.target()?;
+        let level = level as f32 / 255.0;
+
+        self.apply(target, |_, _, pixel| {
+            let [r, g, b, a] = to_straight(*pixel);
+            let value = if luma(r, g, b) >= level { 1.0 } else { 0.0 };
+
+            *pixel = from_straight([value, value, value, a]);
+        })
+    }
+
+    /// Invert the color of each pixel, keeping the alpha channel as is.
+    pub fn invert(&mut self) -> Option<Rectangle<u32>> {
+        let target = self.target()?;
+
+        self.apply(target, |_, _, pixel| {
+            let [r, g, b, a] = pixel.to_ne_bytes();
+            *pixel = u32::from_ne_bytes([255 - r, 255 - g, 255 - b, a]);
+        })
+    }
+
+    /// Replace the color of each pixel with its luma, keeping the alpha channel as is.
+    pub fn grayscale(&mut self) -> Option<Rectangle<u32>> {
+        let target = self.target()?;
+
+        self.apply(target, |_, _, pixel| {
+            let [r, g, b, a] = to_straight(*pixel);
+            let luma = luma(r, g, b);
+
+            *pixel = from_straight([luma, luma, luma, a]);
+        })
+    }
+
+    /// The pixels that are reprocessed, or `None` if there are none.
+    fn target(&self) -> Option<Rectangle<u32>> {
+        let mut target = Rectangle {
+            x: 0,
+            y: 0,
+            width: self.bitmap.width(),
+            height: self.bitmap.height(),
+        };
+
+        if let Some(region) = self.region {
+            target = intersection(target, region)?;
+        }
+
+        if let Some(mask) = self.mask {
+            target = intersection(target, mask.bounds()?)?;
+        }
+
+        Some(target)
+    }
+
+    /// Run `filter` on every pixel of the target that is selected, then mark the target as modified.
+    fn apply(
+        &mut self,
+        target: Rectangle<u32>,
+        mut filter: impl FnMut(u32, u32, &mut u32),
+    ) -> Option<Rectangle<u32>> {
+        let width = self.bitmap.width() as usize;
+        let buffer = self.bitmap.buffer_mut_untracked();
+
+        for y in target.y..target.y + target.height {
+            let row = &mut buffer[y as usize * width..][..width];
+
+            for x in target.x..target.x + target.width {
+                if self.mask.is_none_or(|mask| mask.contains(x, y)) {
+                    filter(x, y, &mut row[x as usize]);
+                }
+            }
+        }
+
+        self.bitmap.mark_dirty(target);
+        Some(target)
+    }
+
+    /// Convolve with a separable kernel, given as its horizontal and vertical weights.
+    fn separable(&mut self, horizontal: &[f32], vertical: &[f32]) -> Option<Rectangle<u32>> {
+        let target = self.target()?;
+        let blurred = self.blur(target, horizontal, vertical);
+
+        self.apply(target, |x, y, pixel| {
+            *pixel = from_premultiplied(blurred.get(x as i64, y as i64));
+        })
+    }
+
+    /// Convolve the target horizontally and then vertically, in premultiplied alpha.
+    fn blur(&self, target: Rectangle<u32>, horizontal: &[f32], vertical: &[f32]) -> Window {
+        let (radius_x, radius_y) = (horizontal.len() / 2, vertical.len() / 2);
+        let window = Window::new(
+            self.bitmap,
+            target,
+            radius_x as u32,
+            radius_y as u32,
+            self.edge,
+            true,
+        );
+
+        // Only the columns of the target are needed after the horizontal pass.
+        let mut rows = Window {
+            left: target.x as i64,
+            width: target.width as usize,
+            pixels: Vec::with_capacity(target.width as usize * window.height),
+            ..window
+        };
+
+        for y in 0..window.height as i64 {
+            for x in 0..target.width as i64 {
+                let (x, y) = (rows.left + x, window.top + y);
+                rows.pixels.push(weighted(horizontal, |i| {
+                    window.get(x + i as i64 - radius_x as i64, y)
+                }));
+            }
+        }
+
+        let mut columns = Window {
+            top: target.y as i64,
+            height: target.height as usize,
+            pixels: Vec::with_capacity(target.width as usize * target.height as usize),
+            ..rows
+        };
+
+        for y in 0..target.height as i64 {
+            for x in 0..target.width as i64 {
+                let (x, y) = (columns.left + x, columns.top + y);
+                columns.pixels.push(weighted(vertical, |i| {
+                    rows.get(x, y + i as i64 - radius_y as i64)
+                }));
+            }
+        }
+
+        columns
+    }
+
+    /// Replace each pixel with a gray level computed from the luma of its 3x3 neighbourhood.
+    fn edges(&mut self, magnitude: impl Fn(&[f32; 9]) -> f32) -> Option<Rectangle<u32>> {
+        let target = self.target()?;
+        let window = Window::new(self.bitmap, target, 1, 1, self.edge, false);
+
+        self.apply(target, |x, y, pixel| {
+            let (x, y) = (x as i64, y as i64);
+            let neighbourhood: [f32; 9] = std::array::from_fn(|i| {
+                let [r, g, b, _] = window.get(x + i as i64 % 3 - 1, y + i as i64 / 3 - 1);
+                luma(r, g, b)
+            });
+
+            let value = magnitude(&neighbourhood).clamp(0.0, 1.0);
+            let alpha = to_straight(*pixel)[3];
+
+            *pixel = from_straight([value, value, value, alpha]);
+        })
+    }
+}
+
+impl Bitmap {
+    /// Create [`Filters`] to process the [`Bitmap`].
+    pub fn filters(&mut self) -> Filters<'_> {
+        Filters::new(self)
+    }
+}
+
+/// A copy of the pixels around a target, in floating point.
+struct Window {
+    left: i64,
+    top: i64,
+    width: usize,
+    height: usize,
+    pixels: Vec<[f32; 4]>,
+    /// The size of the [`Bitmap`].
+    bounds: (u32, u32),
+    edge: Edge,
+}
+
+impl Window {
+    /// Copy the target and the pixels within the radius around it.
+    fn new(
+        bitmap: &Bitmap,
+        target: Rectangle<u32>,
+        radius_x: u32,
+        radius_y: u32,
+        edge: Edge,
+        premultiplied: bool,
+    ) -> Self {
+        let bounds = (bitmap.width(), bitmap.height());
+
+        // Wrapping around can sample from anywhere in the bitmap.
+        let (left, top, right, bottom) = if edge == Edge::Wrap {
+            (0, 0, bounds.0, bounds.1)
+        } else {
+            (
+                target.x.saturating_sub(radius_x),
+                target.y.saturating_sub(radius_y),
+                (target.x + target.width)
+                    .saturating_add(radius_x)
+                    .min(bounds.0),
+                (target.y + target.height)
+                    .saturating_add(radius_y)
+                    .min(bounds.1),
+            )
+        };
+
+        let convert = if premultiplied {
+            to_premultiplied
+        } else {
+            to_straight
+        };
+
+        let pixels = (top..bottom)
+            .flat_map(|y| {
+                let row = &bitmap.buffer()[y as usize * bounds.0 as usize..];
+                row[left as usize..right as usize]
+                    .iter()
+                    .map(|pixel| convert(*pixel))
+            })
+            .collect();
+
+        Self {
+            left: left as i64,
+            top: top as i64,
+            width: (right - left) as usize,
+            height: (bottom - top) as usize,
+            pixels,
+            bounds,
+            edge,
+        }
+    }
+
+    /// Sample the pixel at the given coordinates of the [`Bitmap`].
+    fn get(&self, x: i64, y: i64) -> [f32; 4] {
+        let (Some(x), Some(y)) = (
+            resolve(x, self.bounds.0, self.edge),
+            resolve(y, self.bounds.1, self.edge),
+        ) else {
+            return [0.0; 4];
+        };
+
+        self.pixels[(y - self.top) as usize * self.width + (x - self.left) as usize]
+    }
+}
+
+/// Map a coordinate outside of the [`Bitmap`] to the one it samples, if any.
+fn resolve(coordinate: i64, size: u32, edge: Edge) -> Option<i64> {
+    let size = size as i64;
+
+    if (0..size).contains(&coordinate) {
+        return Some(coordinate);
+    }
+
+    match edge {
+        Edge::Clamp => Some(coordinate.clamp(0, size - 1)),
+        Edge::Wrap => Some(coordinate.rem_euclid(size)),
+        Edge::Mirror => {
+            let coordinate = coordinate.rem_euclid(size * 2);
+            Some(coordinate.min(size * 2 - 1 - coordinate))
+        }
+        Edge::Transparent => None,
+    }
+}
+
+fn weighted(weights: &[f32], sample: impl Fn(usize) -> [f32; 4]) -> [f32; 4] {
+    let mut sum = [0.0; 4];
+
+    for (i, weight) in weights.iter().enumerate() {
+        let pixel = sample(i);
+
+        for channel in 0..4 {
+            sum[channel] += pixel[channel] * weight;
+        }
+    }
+
+    sum
+}
+
+/// The normalized weights of a Gaussian, covering three standard deviations on each side.
+fn gaussian(sigma: f32) -> Vec<f32> {
+    if sigma <= 0.0 {
+        return vec![1.0];
+    }
+
+    let radius = (sigma * 3.0).ceil() as i32;
+    let weights: Vec<f32> = (-radius..=radius)
+        .map(|x| (-(x * x) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect();
+
+    let total: f32 = weights.iter().sum();
+    weights.into_iter().map(|weight| weight / total).collect()
+}
+
+fn intersection(a: Rectangle<u32>, b: Rectangle<u32>) -> Option<Rectangle<u32>> {
+    let x = a.x.max(b.x);
+    let y = a.y.max(b.y);
+    let right = (a.x + a.width).min(b.x.saturating_add(b.width));
+    let bottom = (a.y + a.height).min(b.y.saturating_add(b.height));
+
+    (right > x && bottom > y).then(|| Rectangle {
+        x,
+        y,
+        width: right - x,
+        height: bottom - y,
+    })
+}
+
+/// The luma of a color with the Rec. 601 coefficients.
+fn luma(r: f32, g: f32, b: f32) -> f32 {
+    0.299 * r + 0.587 * g + 0.114 * b
+}
+
+fn to_straight(pixel: u32) -> [f32; 4] {
+    pixel.to_ne_bytes().map(|channel| channel as f32 / 255.0)
+}
+
+fn from_straight(pixel: [f32; 4]) -> u32 {
+    u32::from_ne_bytes(pixel.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8))
+}
+
+fn to_premultiplied(pixel: u32) -> [f32; 4] {
+    let [r, g, b, a] = to_straight(pixel);
+    [r * a, g * a, b * a, a]
+}
+
+fn from_premultiplied([r, g, b, a]: [f32; 4]) -> u32 {
+    let a = a.clamp(0.0, 1.0);
+
+    if a <= 0.0 {
+        return 0;
+    }
+
+    from_straight([r / a, g / a, b / a, a])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_inside() {
+        for edge in [Edge::Clamp, Edge::Wrap, Edge::Mirror, Edge::Transparent] {
+            assert_eq!(resolve(0, 4, edge), Some(0));
+            assert_eq!(resolve(3, 4, edge), Some(3));
+        }
+    }
+
+    #[test]
+    fn resolve_clamp() {
+        assert_eq!(resolve(-1, 4, Edge::Clamp), Some(0));
+        assert_eq!(resolve(-100, 4, Edge::Clamp), Some(0));
+        assert_eq!(resolve(4, 4, Edge::Clamp), Some(3));
+        assert_eq!(resolve(100, 4, Edge::Clamp), Some(3));
+    }
+
+    #[test]
+    fn resolve_wrap() {
+        assert_eq!(resolve(-1, 4, Edge::Wrap), Some(3));
+        assert_eq!(resolve(-4, 4, Edge::Wrap), Some(0));
+        assert_eq!(resolve(4, 4, Edge::Wrap), Some(0));
+        assert_eq!(resolve(9, 4, Edge::Wrap), Some(1));
+    }
+
+    #[test]
+    fn resolve_mirror() {
+        let resolved: Vec<_> = (-6..10)
+            .map(|x| resolve(x, 4, Edge::Mirror).unwrap())
+            .collect();
+
+        assert_eq!(resolved, [2, 3, 3, 2, 1, 0, 0, 1, 2, 3, 3, 2, 1, 0, 0, 1]);
+        assert_eq!(resolve(-1, 1, Edge::Mirror), Some(0));
+        assert_eq!(resolve(5, 1, Edge::Mirror), Some(0));
+    }
+
+    #[test]
+    fn resolve_transparent() {
+        assert_eq!(resolve(-1, 4, Edge::Transparent), None);
+        assert_eq!(resolve(4, 4, Edge::Transparent), None);
+    }
+
+    #[test]
+    fn median_removes_spike() {
+        let gray = from_straight([0.5, 0.5, 0.5, 1.0]);
+        let mut bitmap = Bitmap::new(5, 5);
+        bitmap.buffer_mut().fill(gray);
+        bitmap.buffer_mut()[12] = from_straight([1.0; 4]);
+
+        assert_eq!(
+            bitmap.filters().median(1),
+            Some(Rectangle {
+                x: 0,
+                y: 0,
+                width: 5,
+                height: 5
+            })
+        );
+        assert!(bitmap.buffer().iter().all(|&pixel| pixel == gray));
+    }
+}
@@ -0,0 +1,574 @@
+//! Undo and redo for a [`Bitmap`], storing only the tiles touched by each edit.
+//!
+//! A [`History`] keeps the [`Bitmap`] as it was after the last commit, split into tiles that are
+//! shared with the undo stack instead of copied. Committing an edit only copies the tiles inside
+//! of the regions it [touched](History::touch), and skips the ones that didn't actually change.
+//!
+//! ```no_run
+//! # use iced_texture_canvas::{Bitmap, Rgba};
+//! # use iced_texture_canvas::bitmap::history::History;
+//! # use iced_core::{Point, Rectangle};
+//! # let mut bitmap = Bitmap::new(100, 100);
+//! let mut history = History::new(&bitmap);
+//!
+//! // A single edit, committed right away.
+//! history.edit(&mut bitmap, |bitmap| {
+//!     bitmap.painter().fill_circle(Point::new(50.0, 50.0), 10.0, Rgba::BLACK)
+//! });
+//!
+//! // Several edits, undone and redone as one.
+//! history.begin();
+//! # let stroke_region = Rectangle::default();
+//! // ... paint a stroke
+//! history.touch(stroke_region);
+//! history.end(&bitmap);
+//!
+//! history.undo(&mut bitmap);
+//! history.redo(&mut bitmap);
+//! ```
+//!
+//! Edits made without touching their region can't be undone correctly.
+use super::Bitmap;
+
+use iced_core::Rectangle;
+
+use std::collections::{BTreeSet, VecDeque};
+use std::sync::Arc;
+
+/// The width and height of each tile, in pixels.
+const TILE_SIZE: u32 = 64;
+
+/// The default memory budget of a [`History`], in bytes.
+const BUDGET: usize = 256 * 1024 * 1024;
+
+/// The undo and redo stacks of a [`Bitmap`].
+#[derive(Debug)]
+pub struct History {
+    /// The [`Bitmap`] as of the last commit.
+    base: Tiles,
+    /// The tiles touched since the last commit.
+    pending: BTreeSet<usize>,
+    /// Whether a transaction is open, and how many times it was nested.
+    transaction: usize,
+    undo: VecDeque<Entry>,
+    redo: Vec<Entry>,
+    budget: usize,
+}
+
+impl History {
+    /// Start recording the history of the [`Bitmap`] from its current state.
+    pub fn new(bitmap: &Bitmap) -> Self {
+        Self {
+            base: Tiles::new(bitmap),
+            pending: BTreeSet::new(),
+            transaction: 0,
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            budget: BUDGET,
+        }
+    }
+
+    /// Set the memory budget of the undo and redo stacks, in bytes.
+    ///
+    /// The oldest edits are forgotten once the budget is exceeded, but the latest edit is always kept.
+    pub fn budget(mut self, bytes: usize) -> Self {
+        self.budget = bytes;
+        self.evict();
+        self
+    }
+
+    /// Record that a region of the [`Bitmap`] was modified since the last commit.
+    pub fn touch(&mut self, region: Rectangle<u32>) {
+        self.pending.extend(self.base.covering(region));
+    }
+
+    /// Apply an edit returning the region it modified, and commit it unless a transaction is open.
+    ///
+    /// This fits every method of [`Painter`](super::draw::Painter) and [`Filters`](super::filter::Filters).
+    pub fn edit(
+        &mut self,
+        bitmap: &mut Bitmap,
+        edit: impl FnOnce(&mut Bitmap) -> Option<Rectangle<u32>>,
+    ) -> Option<Rectangle<u32>> {
+        let region = edit(bitmap);
+
+        if let Some(region) = region {
+            self.touch(region);
+        }
+
+        if self.transaction == 0 {
+            self.commit(bitmap);
+        }
+
+        region
+    }
+
+    /// Open a transaction, grouping every following edit into a single entry until [`end`](Self::end).
+    ///
+    /// Transactions can be nested, in which case they are committed when the outermost one ends.
+    pub fn begin(&mut self) {
+        self.transaction += 1;
+    }
+
+    /// Close the current transaction, committing it if it's the outermost one.
+    pub fn end(&mut self, bitmap: &Bitmap) {
+        self.transaction = self.transaction.saturating_sub(1);
+
+        if self.transaction == 0 {
+            self.commit(bitmap);
+        }
+    }
+
+    /// Whether a transaction is open.
+    pub fn in_transaction(&self) -> bool {
+        self.transaction > 0
+    }
+
+    /// Commit the edits touched since the last commit as a single entry.
+    ///
+    /// Returns `false` if no pixel was changed.
+    pub fn commit(&mut self, bitmap: &Bitmap) -> bool {
+        let pending = std::mem::take(&mut self.pending);
+
+        let entry = if bitmap.width() != self.base.width || bitmap.height() != self.base.height {
+            let after = Tiles::new(bitmap);
+            let before = std::mem::replace(&mut self.base, after.clone());
+
+            Entry {
+                before: Patch::Whole(before),
+                after: Patch::Whole(after),
+            }
+        } else {
+            let (mut before, mut after) = (Vec::new(), Vec::new());
+
+            for index in pending {
+                let tile = self.base.extract(bitmap, index);
+
+                if tile[..] != self.base.tiles[index][..] {
+                    let tile = Arc::from(tile);
+
+                    before.push((
+                        index,
+                        std::mem::replace(&mut self.base.tiles[index], Arc::clone(&tile)),
+                    ));
+                    after.push((index, tile));
+                }
+            }
+
+            if before.is_empty() {
+                return false;
+            }
+
+            Entry {
+                before: Patch::Tiles(before),
+                after: Patch::Tiles(after),
+            }
+        };
+
+        self.undo.push_back(entry);
+        self.redo.clear();
+        self.evict();
+
+        true
+    }
+
+    /// Restore the [`Bitmap`] to how it was before the last commit.
+    ///
+    /// Uncommitted edits are committed first, and any open transaction is closed.
+    ///
+    /// Returns the region of the [`Bitmap`] that was restored, which is also marked as modified.
+    pub fn undo(&mut self, bitmap: &mut Bitmap) -> Option<Rectangle<u32>> {
+        self.transaction = 0;
+        self.commit(bitmap);
+
+        let entry = self.undo.pop_back()?;
+        let region = self.base.apply(bitmap, &entry.before);
+
+        self.redo.push(entry);
+        Some(region)
+    }
+
+    /// Restore the [`Bitmap`] to how it was before the last [`undo`](Self::undo).
+    ///
+    /// Uncommitted edits are committed first, which leaves nothing to redo.
+    ///
+    /// Returns the region of the [`Bitmap`] that was restored, which is also marked as modified.
+    pub fn redo(&mut self, bitmap: &mut Bitmap) -> Option<Rectangle<u32>> {
+        self.transaction = 0;
+        self.commit(bitmap);
+
+        let entry = self.redo.pop()?;
+        let region = self.base.apply(bitmap, &entry.after);
+
+        self.undo.push_back(entry);
+        Some(region)
+    }
+
+    /// Discard the edits touched since the last commit, restoring their tiles.
+    ///
+    /// Any open transaction is closed.
+    pub fn rollback(&mut self, bitmap: &mut Bitmap) -> Option<Rectangle<u32>> {
+        self.transaction = 0;
+        let pending = std::mem::take(&mut self.pending);
+
+        if bitmap.width() != self.base.width || bitmap.height() != self.base.height {
+            return Some(self.base.apply(bitmap, &Patch::Whole(self.base.clone())));
+        }
+
+        let tiles = pending
+            .into_iter()
+            .map(|index| (index, Arc::clone(&self.base.tiles[index])))
+            .collect::<Vec<_>>();
+
+        (!tiles.is_empty()).then(|| self.base.apply(bitmap, &Patch::Tiles(tiles)))
+    }
+
+    /// Whether there is an edit to undo.
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    /// Whether there is an edit to redo.
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Forget every edit, keeping the current state of the [`Bitmap`].
+    pub fn clear(&mut self, bitmap: &Bitmap) {
+        *self = Self::new(bitmap).budget(self.budget);
+    }
+
+    /// The memory used by the undo and redo stacks, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.undo.iter().chain(&self.redo).map(Entry::size).sum()
+    }
+
+    /// Forget the oldest edits until the budget is met, always keeping the latest one.
+    fn evict(&mut self) {
+        let mut usage = self.memory_usage();
+
+        while usage > self.budget && self.undo.len() > 1 {
+            if let Some(entry) = self.undo.pop_front() {
+                usage -= entry.size();
+            }
+        }
+    }
+}
+
+/// A committed edit.
+#[derive(Debug)]
+struct Entry {
+    before: Patch,
+    after: Patch,
+}
+
+impl Entry {
+    fn size(&self) -> usize {
+        self.before.size() + self.after.size()
+    }
+}
+
+/// The tiles to restore for one side of an [`Entry`].
+#[derive(Debug)]
+enum Patch {
+    Tiles(Vec<(usize, Arc<[u32]>)>),
+    /// The [`Bitmap`] was resized, so all of it is restored.
+    Whole(Tiles),
+}
+
+impl Patch {
+    fn size(&self) -> usize {
+        let pixels: usize = match self {
+            Self::Tiles(tiles) => tiles.iter().map(|(_, tile)| tile.len()).sum(),
+            Self::Whole(tiles) => tiles.tiles.iter().map(|tile| tile.len()).sum(),
+        };
+
+        pixels * size_of::<u32>()
+    }
+}
+
+/// A [`Bitmap`] split into shared tiles, row by row.
+#[derive(Debug, Clone)]
+struct Tiles {
+    width: u32,
+    height: u32,
+    tiles: Vec<Arc<[u32]>>,
+}
+
+impl Tiles {
+    fn new(bitmap: &Bitmap) -> Self {
+        let mut this = Self {
+            width: bitmap.width(),
+            height: bitmap.height(),
+            tiles: Vec::new(),
+        };
+
+        let count = this.columns() * this.rows();
+        this.tiles = (0..count)
+            .map(|index| Arc::from(this.extract(bitmap, index)))
+            .collect();
+
+        this
+    }
+
+    fn columns(&self) -> usize {
+        self.width.div_ceil(TILE_SIZE) as usize
+    }
+
+    fn rows(&self) -> usize {
+        self.height.div_ceil(TILE_SIZE) as usize
+    }
+
+    /// The region of the [`Bitmap`] covered by a tile.
+    fn bounds(&self, index: usize) -> Rectangle<u32> {
+        let x = (index % self.columns()) as u32 * TILE_SIZE;
+        let y = (index / self.columns()) as u32 * TILE_SIZE;
+
+        Rectangle {
+            x,
+            y,
+            width: TILE_SIZE.min(self.width - x),
+            height: TILE_SIZE.min(self.height - y),
+        }
+    }
+
+    /// The indices of the tiles overlapping the region.
+    fn covering(&self, region: Rectangle<u32>) -> impl Iterator<Item = usize> + use<> {
+        let right = region.x.saturating_add(region.width).min(self.width);
+        let bottom = region.y.saturating_add(region.height).min(self.height);
+        let columns = self.columns();
+
+        let horizontal = region.x / TILE_SIZE..right.div_ceil(TILE_SIZE);
+        let vertical = region.y / TILE_SIZE..bottom.div_ceil(TILE_SIZE);
+
+        vertical.flat_map(move |y| {
+            horizontal
+                .clone()
+                .map(move |x| y as usize * columns + x as usize)
+        })
+    }
+
+    /// Copy the pixels of a tile out of the [`Bitmap`].
+    fn extract(&self, bitmap: &Bitmap, index: usize) -> Vec<u32> {
+        let bounds = self.bounds(index);
+        let width = self.width as usize;
+
+        (bounds.y..bounds.y + bounds.height)
+            .flat_map(|y| {
+                let start = y as usize * width + bounds.x as usize;
+                &bitmap.buffer()[start..start + bounds.width as usize]
+            })
+            .copied()
+            .collect()
+    }
+
+    /// Restore the [`Patch`] onto the [`Bitmap`], returning the region marked as modified.
+    fn apply(&mut self, bitmap: &mut Bitmap, patch: &Patch) -> Rectangle<u32> {
+        match patch {
+            Patch::Tiles(tiles) => {
+                let width = self.width as usize;
+                let mut region: Option<Rectangle<u32>> = None;
+
+                for (index, tile) in tiles {
+                    let bounds = self.bounds(*index);
+                    let buffer = bitmap.buffer_mut_untracked();
+
+                    for (y, row) in tile.chunks_exact(bounds.width as usize).enumerate() {
+                        let start = (bounds.y as usize + y) * width + bounds.x as usize;
+                        buffer[start..start + row.len()].copy_from_slice(row);
+                    }
+
+                    self.tiles[*index] = Arc::clone(tile);
+                    bitmap.mark_dirty(bounds);
+                    region = Some(region.map_or(bounds, |region| union(region, bounds)));
+                }
+
+                region.unwrap_or_default()
+            }
+            Patch::Whole(tiles) => {
+                *self = tiles.clone();
+
+                let mut buffer = vec![0; self.width as usize * self.height as usize];
+                let width = self.width as usize;
+
+                for (index, tile) in self.tiles.iter().enumerate() {
+                    let bounds = self.bounds(index);
+
+                    for (y, row) in tile.chunks_exact(bounds.width as usize).enumerate() {
+                        let start = (bounds.y as usize + y) * width + bounds.x as usize;
+                        buffer[start..start + row.len()].copy_from_slice(row);
+                    }
+                }
+
+                bitmap.replace(self.width, self.height, buffer);
+
+                Rectangle {
+                    x: 0,
+                    y: 0,
+                    width: self.width,
+                    height: self.height,
+                }
+            }
+        }
+    }
+}
+
+fn union(a: Rectangle<u32>, b: Rectangle<u32>) -> Rectangle<u32> {
+    let x = a.x.min(b.x);
+    let y = a.y.min(b.y);
+
+    Rectangle {
+        x,
+        y,
+        width: (a.x + a.width).max(b.x + b.width) - x,
+        height: (a.y + a.height).max(b.y + b.height) - y,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitmap::Rgba;
+
+    fn region(x: u32, y: u32, width: u32, height: u32) -> Rectangle<u32> {
+        Rectangle {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut bitmap = Bitmap::new(100, 70);
+        let original = bitmap.buffer().to_vec();
+        let mut history = History::new(&bitmap);
+
+        history.edit(&mut bitmap, |bitmap| {
+            bitmap.painter().put_pixel(70, 10, Rgba::WHITE)
+        });
+        let first = bitmap.buffer().to_vec();
+
+        history.begin();
+        history.edit(&mut bitmap, |bitmap| {
+            bitmap.painter().put_pixel(0, 0, Rgba::BLACK)
+        });
+        history.edit(&mut bitmap, |bitmap| {
+            bitmap.painter().put_pixel(99, 69, Rgba::WHITE)
+        });
+        history.end(&bitmap);
+        let second = bitmap.buffer().to_vec();
+
+        assert_eq!(history.undo(&mut bitmap), Some(region(0, 0, 100, 70)));
+        assert_eq!(bitmap.buffer(), first);
+        assert_eq!(history.undo(&mut bitmap), Some(region(64, 0, 36, 64)));
+        assert_eq!(bitmap.buffer(), original);
+        assert_eq!(history.undo(&mut bitmap), None);
+
+        history.redo(&mut bitmap);
+        history.redo(&mut bitmap);
+        assert_eq!(bitmap.buffer(), second);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn unchanged_edits_are_skipped() {
+        let bitmap = Bitmap::new(10, 10);
+        let mut history = History::new(&bitmap);
+
+        history.touch(region(0, 0, 10, 10));
+
+        assert!(!history.commit(&bitmap));
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn new_edits_clear_redo() {
+        let mut bitmap = Bitmap::new(10, 10);
+        let mut history = History::new(&bitmap);
+
+        history.edit(&mut bitmap, |bitmap| {
+            bitmap.painter().put_pixel(1, 1, Rgba::WHITE)
+        });
+        history.undo(&mut bitmap);
+        history.edit(&mut bitmap, |bitmap| {
+            bitmap.painter().put_pixel(2, 2, Rgba::WHITE)
+        });
+
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn undo_resize() {
+        let mut bitmap = Bitmap::new(100, 70);
+        bitmap.set_pixel(80, 60, Rgba::WHITE);
+        let original = bitmap.buffer().to_vec();
+        let mut history = History::new(&bitmap);
+
+        history.edit(&mut bitmap, |bitmap| {
+            bitmap.resize(30, 20);
+            None
+        });
+        assert_eq!((bitmap.width(), bitmap.height()), (30, 20));
+
+        assert_eq!(history.undo(&mut bitmap), Some(region(0, 0, 100, 70)));
+        assert_eq!((bitmap.width(), bitmap.height()), (100, 70));
+        assert_eq!(bitmap.buffer(), original);
+
+        history.redo(&mut bitmap);
+        assert_eq!((bitmap.width(), bitmap.height()), (30, 20));
+    }
+
+    #[test]
+    fn rollback() {
+        let mut bitmap = Bitmap::new(100, 70);
+        let original = bitmap.buffer().to_vec();
+        let mut history = History::new(&bitmap);
+
+        history.begin();
+        bitmap.set_pixel(70, 10, Rgba::WHITE);
+        history.touch(region(70, 10, 1, 1));
+
+        assert_eq!(history.rollback(&mut bitmap), Some(region(64, 0, 36, 64)));
+        assert_eq!(bitmap.buffer(), original);
+        assert!(!history.in_transaction());
+        assert!(!history.can_undo());
+        assert_eq!(history.rollback(&mut bitmap), None);
+    }
+
+    #[test]
+    fn rollback_after_resize() {
+        let mut bitmap = Bitmap::new(100, 70);
+        bitmap.set_pixel(80, 60, Rgba::WHITE);
+        let original = bitmap.buffer().to_vec();
+        let mut history = History::new(&bitmap);
+
+        bitmap.resize(30, 20);
+
+        assert_eq!(history.rollback(&mut bitmap), Some(region(0, 0, 100, 70)));
+        assert_eq!((bitmap.width(), bitmap.height()), (100, 70));
+        assert_eq!(bitmap.buffer(), original);
+
+        history.edit(&mut bitmap, |bitmap| {
+            bitmap.painter().put_pixel(1, 1, Rgba::WHITE)
+        });
+        assert_eq!(history.undo(&mut bitmap), Some(region(0, 0, 64, 64)));
+        assert_eq!(bitmap.buffer(), original);
+    }
+
+    #[test]
+    fn budget() {
+        let mut bitmap = Bitmap::new(200, 10);
+        let mut history = History::new(&bitmap).budget(2 * 2 * 64 * 10 * 4);
+
+        for x in [0, 70, 140] {
+            history.edit(&mut bitmap, |bitmap| {
+                bitmap.painter().put_pixel(x, 0, Rgba::WHITE)
+            });
+        }
+
+        assert_eq!(history.undo.len(), 2);
+        assert!(history.memory_usage() <= 2 * 2 * 64 * 10 * 4);
+    }
+}
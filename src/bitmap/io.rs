@@ -0,0 +1,155 @@
+//! Loading and saving a [`Bitmap`] with the [`image`] crate.
+//!
+//! Requires the `image` feature.
+use super::Bitmap;
+
+use image::{DynamicImage, ImageDecoder, ImageReader, RgbaImage};
+
+use std::io::Cursor;
+use std::path::Path;
+
+pub use image::ImageFormat;
+
+/// An error that can occur when loading or saving a [`Bitmap`].
+#[derive(Debug)]
+pub enum Error {
+    /// The file could not be opened or created.
+    Io(std::io::Error),
+    /// The image could not be decoded or encoded.
+    Image(image::ImageError),
+    /// The image has a width or height of zero, which a [`Bitmap`] cannot represent.
+    Empty,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "io error: {error}"),
+            Error::Image(error) => write!(f, "image error: {error}"),
+            Error::Empty => write!(f, "the image has a width or height of zero"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            Error::Image(error) => Some(error),
+            Error::Empty => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(error: image::ImageError) -> Self {
+        Error::Image(error)
+    }
+}
+
+impl Bitmap {
+    /// Load a [`Bitmap`] from an image file.
+    ///
+    /// The format is guessed from the contents of the file,
+    /// and the EXIF orientation of the image is applied.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        decode(ImageReader::open(path)?.with_guessed_format()?)
+    }
+
+    /// Load a [`Bitmap`] from an encoded image in memory.
+    ///
+    /// The format is guessed from the contents of the data,
+    /// and the EXIF orientation of the image is applied.
+    pub fn from_memory(bytes: &[u8]) -> Result<Self, Error> {
+        decode(ImageReader::new(Cursor::new(bytes)).with_guessed_format()?)
+    }
+
+    /// Save the [`Bitmap`] to a file with the given [`ImageFormat`].
+    ///
+    /// The alpha channel is discarded for JPEG, which doesn't support it. Formats whose encoder
+    /// isn't enabled in this crate fail with an [`Error::Image`].
+    ///
+    /// The image is encoded in memory first, so the file is left untouched if encoding fails.
+    pub fn save(&self, path: impl AsRef<Path>, format: ImageFormat) -> Result<(), Error> {
+        let image = DynamicImage::from(self);
+
+        let image = match format {
+            ImageFormat::Jpeg => DynamicImage::ImageRgb8(image.into_rgb8()),
+            _ => image,
+        };
+
+        let mut encoded = Cursor::new(Vec::new());
+        image.write_to(&mut encoded, format)?;
+
+        std::fs::write(path, encoded.into_inner())?;
+        Ok(())
+    }
+}
+
+pub(super) fn decode<R>(reader: ImageReader<R>) -> Result<Bitmap, Error>
+where
+    R: std::io::BufRead + std::io::Seek,
+{
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+
+    Bitmap::try_from(image)
+}
+
+impl TryFrom<RgbaImage> for Bitmap {
+    type Error = Error;
+
+    fn try_from(image: RgbaImage) -> Result<Self, Self::Error> {
+        if image.width() == 0 || image.height() == 0 {
+            return Err(Error::Empty);
+        }
+
+        Ok(Bitmap::new_init(
+            image.width(),
+            image.height(),
+            image.as_raw(),
+        ))
+    }
+}
+
+impl TryFrom<DynamicImage> for Bitmap {
+    type Error = Error;
+
+    fn try_from(image: DynamicImage) -> Result<Self, Self::Error> {
+        Bitmap::try_from(image.into_rgba8())
+    }
+}
+
+impl From<&Bitmap> for RgbaImage {
+    fn from(bitmap: &Bitmap) -> Self {
+        RgbaImage::from_raw(bitmap.width(), bitmap.height(), bitmap.raw().to_vec())
+            .expect("bitmap size matches its buffer")
+    }
+}
+
+impl From<Bitmap> for RgbaImage {
+    fn from(bitmap: Bitmap) -> Self {
+        RgbaImage::from(&bitmap)
+    }
+}
+
+impl From<&Bitmap> for DynamicImage {
+    fn from(bitmap: &Bitmap) -> Self {
+        DynamicImage::ImageRgba8(RgbaImage::from(bitmap))
+    }
+}
+
+impl From<Bitmap> for DynamicImage {
+    fn from(bitmap: Bitmap) -> Self {
+        DynamicImage::from(&bitmap)
+    }
+}
@@ -0,0 +1,299 @@
+//! Decode images into a [`Bitmap`] on a background thread.
+//!
+//! Decoding a large image can take several seconds, which would freeze the interface
+//! if it were done in `update`. The [`Task`]s in this module decode on their own thread instead,
+//! reporting their [`Loading`] progress as they go.
+//!
+//! QOI and Netpbm images are always supported. Every other format requires the `image` feature.
+//!
+//! Loading can be cancelled by aborting the [`Task`] with [`Task::abortable`],
+//! which stops the background thread the next time it reads from the image.
+//!
+//! ```no_run
+//! # use iced_texture_canvas::Bitmap;
+//! # use iced_texture_canvas::bitmap::load::{self, Loading};
+//! # use iced_widget::runtime::task::Task;
+//! #[derive(Debug)]
+//! enum Message {
+//!     Loading(Loading),
+//! }
+//!
+//! fn open() -> Task<Message> {
+//!     load::open("image.tiff").map(Message::Loading)
+//! }
+//! ```
+use super::{Bitmap, codec};
+
+use iced_widget::runtime::futures::futures::channel::mpsc;
+use iced_widget::runtime::task::Task;
+
+use std::cell::Cell;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// The largest side of the previews sent while decoding.
+const PREVIEW_SIZE: u32 = 512;
+
+/// How many previews are sent while decoding an image.
+const PREVIEWS: u32 = 8;
+
+/// The progress of a [`Bitmap`] being loaded in the background.
+#[derive(Debug)]
+pub enum Loading {
+    /// The fraction of the image that has been decoded, between `0.0` and `1.0`.
+    Progress(f32),
+    /// A low resolution preview of the image decoded so far.
+    ///
+    /// The preview is scaled down so its largest side is at most 512 pixels,
+    /// and pixels that haven't been decoded yet are transparent.
+    /// It's only sent if the decoder reads the image row by row.
+    Preview(Bitmap),
+    /// The image was decoded, or failed to decode.
+    Finished(Result<Bitmap, Error>),
+}
+
+/// An error that can occur when loading a [`Bitmap`] in the background.
+#[derive(Debug)]
+pub enum Error {
+    /// The file could not be opened.
+    Io(io::Error),
+    /// The image could not be decoded by the [`codec`] module.
+    Codec(codec::Error),
+    /// The image could not be decoded by the [`image`] crate.
+    #[cfg(feature = "image")]
+    Image(super::io::Error),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "io error: {error}"),
+            Error::Codec(error) => error.fmt(f),
+            #[cfg(feature = "image")]
+            Error::Image(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            Error::Codec(error) => Some(error),
+            #[cfg(feature = "image")]
+            Error::Image(error) => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<codec::Error> for Error {
+    fn from(error: codec::Error) -> Self {
+        Error::Codec(error)
+    }
+}
+
+#[cfg(feature = "image")]
+impl From<super::io::Error> for Error {
+    fn from(error: super::io::Error) -> Self {
+        Error::Image(error)
+    }
+}
+
+/// A [`Task`] that loads a [`Bitmap`] from an image file on a background thread.
+///
+/// The format is guessed from the contents of the file.
+pub fn open(path: impl AsRef<Path> + Send + 'static) -> Task<Loading> {
+    spawn(move |reporter| {
+        let file = File::open(path)?;
+        let length = file.metadata()?.len();
+
+        decode(file, length, reporter)
+    })
+}
+
+/// A [`Task`] that loads a [`Bitmap`] from an encoded image in memory on a background thread.
+///
+/// The format is guessed from the contents of the data.
+pub fn from_memory(bytes: impl AsRef<[u8]> + Send + 'static) -> Task<Loading> {
+    spawn(move |reporter| {
+        let length = bytes.as_ref().len() as u64;
+
+        decode(io::Cursor::new(bytes), length, reporter)
+    })
+}
+
+fn spawn<F>(load: F) -> Task<Loading>
+where
+    F: FnOnce(&Reporter) -> Result<Bitmap, Error> + Send + 'static,
+{
+    let (sender, receiver) = mpsc::unbounded();
+
+    // The thread owns the sender, so keep one to report a failure to start it.
+    let failed = sender.clone();
+
+    let spawned = std::thread::Builder::new()
+        .name(String::from("bitmap loader"))
+        .spawn(move || {
+            let reporter = Reporter {
+                sender,
+                percent: Cell::new(0),
+            };
+
+            let result = load(&reporter);
+
+            // Nobody is listening if the task was aborted.
+            if !reporter.is_cancelled() {
+                reporter.send(Loading::Finished(result));
+            }
+        });
+
+    if let Err(error) = spawned {
+        let _ = failed.unbounded_send(Loading::Finished(Err(Error::Io(error))));
+    }
+
+    Task::stream(receiver)
+}
+
+fn decode<R: Read + Seek>(reader: R, length: u64, reporter: &Reporter) -> Result<Bitmap, Error> {
+    let mut reader = BufReader::new(Tracked {
+        reader,
+        position: 0,
+        length,
+        reporter,
+        report: false,
+    });
+
+    if codec::Format::guess(reader.fill_buf()?).is_some() {
+        let mut previews = 0;
+
+        return Ok(codec::decode_with(
+            reader,
+            &mut |decoded, width, height| {
+                let rows = (decoded.len() / width as usize) as u32;
+
+                reporter.progress(rows as f32 / height as f32);
+
+                // Only send previews of images that are being scaled down.
+                if width.max(height) > PREVIEW_SIZE && rows * PREVIEWS >= height * (previews + 1) {
+                    previews += 1;
+                    reporter.send(Loading::Preview(preview(decoded, width, height)));
+                }
+            },
+        )?);
+    }
+
+    reader.get_mut().report = true;
+
+    decode_image(reader)
+}
+
+#[cfg(feature = "image")]
+fn decode_image(reader: impl BufRead + Seek) -> Result<Bitmap, Error> {
+    let reader = image::ImageReader::new(reader)
+        .with_guessed_format()
+        .map_err(super::io::Error::from)?;
+
+    Ok(super::io::decode(reader)?)
+}
+
+#[cfg(not(feature = "image"))]
+fn decode_image(_reader: impl BufRead + Seek) -> Result<Bitmap, Error> {
+    Err(Error::Codec(codec::Error::UnknownFormat))
+}
+
+/// Sample the decoded rows of an image into a preview no larger than [`PREVIEW_SIZE`].
+fn preview(decoded: &[u32], width: u32, height: u32) -> Bitmap {
+    let factor = width.max(height).div_ceil(PREVIEW_SIZE) as usize;
+    let width = width as usize;
+
+    let mut preview = Bitmap::new(
+        width.div_ceil(factor) as u32,
+        (height as usize).div_ceil(factor) as u32,
+    );
+    let preview_width = preview.width() as usize;
+
+    for (y, row) in preview
+        .buffer_mut()
+        .chunks_exact_mut(preview_width)
+        .enumerate()
+    {
+        let Some(source) = decoded.get(y * factor * width..(y * factor + 1) * width) else {
+            break;
+        };
+
+        for (pixel, source) in row.iter_mut().zip(source.iter().step_by(factor)) {
+            *pixel = *source;
+        }
+    }
+
+    preview
+}
+
+/// Sends [`Loading`] updates from the background thread.
+struct Reporter {
+    sender: mpsc::UnboundedSender<Loading>,
+    percent: Cell<u32>,
+}
+
+impl Reporter {
+    fn send(&self, loading: Loading) {
+        let _ = self.sender.unbounded_send(loading);
+    }
+
+    /// Send the progress, at most once for every percent.
+    fn progress(&self, fraction: f32) {
+        let percent = (fraction.clamp(0.0, 1.0) * 100.0) as u32;
+
+        if percent > self.percent.get() {
+            self.percent.set(percent);
+            self.send(Loading::Progress(fraction.clamp(0.0, 1.0)));
+        }
+    }
+
+    /// Whether the [`Task`] was aborted or dropped.
+    fn is_cancelled(&self) -> bool {
+        self.sender.is_closed()
+    }
+}
+
+/// A reader that stops once the [`Task`] is cancelled,
+/// optionally reporting how much of the image has been read.
+struct Tracked<'a, R> {
+    reader: R,
+    position: u64,
+    length: u64,
+    reporter: &'a Reporter,
+    report: bool,
+}
+
+impl<R: Read> Read for Tracked<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.reporter.is_cancelled() {
+            return Err(io::Error::other("loading was cancelled"));
+        }
+
+        let read = self.reader.read(buf)?;
+        self.position += read as u64;
+
+        if self.report && self.length > 0 {
+            self.reporter
+                .progress(self.position as f32 / self.length as f32);
+        }
+
+        Ok(read)
+    }
+}
+
+impl<R: Seek> Seek for Tracked<'_, R> {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        self.position = self.reader.seek(position)?;
+        Ok(self.position)
+    }
+}
@@ -0,0 +1,88 @@
+//! The [`Rgba`] pixel type.
+use iced_core::Color;
+
+/// A single pixel of a [`Bitmap`](super::Bitmap), stored as straight `RGBA` bytes.
+///
+/// It has the same memory layout as the pixels of the buffer, regardless of the endianness of the
+/// platform, unlike the `u32` returned by [`Bitmap::buffer`](super::Bitmap::buffer).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Rgba {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+// SAFETY: `Rgba` is `repr(C)` and made of four `u8`s, so it has no padding and any bit pattern is valid.
+unsafe impl bytemuck::Zeroable for Rgba {}
+unsafe impl bytemuck::Pod for Rgba {}
+
+impl Rgba {
+    pub const TRANSPARENT: Self = Self::new(0, 0, 0, 0);
+    pub const BLACK: Self = Self::rgb(0, 0, 0);
+    pub const WHITE: Self = Self::rgb(255, 255, 255);
+
+    /// Create a pixel from its channels.
+    pub const fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// Create an opaque pixel.
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self::new(r, g, b, 255)
+    }
+
+    /// Create a pixel from a value with the layout of the [`Bitmap`](super::Bitmap) buffer.
+    pub const fn from_u32(pixel: u32) -> Self {
+        let [r, g, b, a] = pixel.to_ne_bytes();
+        Self::new(r, g, b, a)
+    }
+
+    /// Convert the pixel to a value with the layout of the [`Bitmap`](super::Bitmap) buffer.
+    pub const fn to_u32(self) -> u32 {
+        u32::from_ne_bytes([self.r, self.g, self.b, self.a])
+    }
+
+    /// Set the alpha channel.
+    pub const fn with_alpha(self, a: u8) -> Self {
+        Self { a, ..self }
+    }
+}
+
+impl From<[u8; 4]> for Rgba {
+    fn from([r, g, b, a]: [u8; 4]) -> Self {
+        Self::new(r, g, b, a)
+    }
+}
+
+impl From<Rgba> for [u8; 4] {
+    fn from(pixel: Rgba) -> Self {
+        [pixel.r, pixel.g, pixel.b, pixel.a]
+    }
+}
+
+/// Interprets the value with the layout of the [`Bitmap`](super::Bitmap) buffer.
+impl From<u32> for Rgba {
+    fn from(pixel: u32) -> Self {
+        Self::from_u32(pixel)
+    }
+}
+
+impl From<Rgba> for u32 {
+    fn from(pixel: Rgba) -> Self {
+        pixel.to_u32()
+    }
+}
+
+impl From<Color> for Rgba {
+    fn from(color: Color) -> Self {
+        color.into_rgba8().into()
+    }
+}
+
+impl From<Rgba> for Color {
+    fn from(pixel: Rgba) -> Self {
+        Color::from_rgba8(pixel.r, pixel.g, pixel.b, pixel.a as f32 / 255.0)
+    }
+}
@@ -0,0 +1,271 @@
+//! Scaling a [`Bitmap`] with quality filters.
+//!
+//! Apart from [`Filter::Nearest`], pixels are averaged in linear light with premultiplied alpha,
+//! so scaled images don't darken and transparent pixels don't bleed their color into their
+//! neighbours. The image is scaled horizontally and then vertically, and each pass is spread
+//! across threads when the `rayon` feature is enabled.
+use super::Bitmap;
+
+use std::f32::consts::PI;
+use std::sync::LazyLock;
+
+/// The filter used to [`resample`](Bitmap::resample) a [`Bitmap`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Filter {
+    /// Picks the closest pixel, keeping hard edges.
+    Nearest,
+    /// Linear interpolation between neighbouring pixels.
+    Bilinear,
+    /// Catmull-Rom cubic interpolation, sharper than [`Filter::Bilinear`].
+    #[default]
+    Bicubic,
+    /// A windowed sinc with a radius of 3 pixels, the sharpest but also the slowest.
+    Lanczos3,
+}
+
+impl Filter {
+    /// The radius of the filter, in source pixels when upscaling.
+    fn support(self) -> f32 {
+        match self {
+            Self::Nearest => 0.5,
+            Self::Bilinear => 1.0,
+            Self::Bicubic => 2.0,
+            Self::Lanczos3 => 3.0,
+        }
+    }
+
+    fn weight(self, x: f32) -> f32 {
+        let x = x.abs();
+
+        match self {
+            Self::Nearest => (x <= 0.5) as u8 as f32,
+            Self::Bilinear => (1.0 - x).max(0.0),
+            Self::Bicubic => {
+                if x < 1.0 {
+                    (1.5 * x - 2.5) * x * x + 1.0
+                } else if x < 2.0 {
+                    ((-0.5 * x + 2.5) * x - 4.0) * x + 2.0
+                } else {
+                    0.0
+                }
+            }
+            Self::Lanczos3 => {
+                if x < 3.0 {
+                    sinc(x) * sinc(x / 3.0)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+impl Bitmap {
+    /// Create a copy of the [`Bitmap`] scaled to the given size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either the `width` or `height` is zero.
+    pub fn resample(&self, width: u32, height: u32, filter: Filter) -> Bitmap {
+        let mut bitmap = Bitmap::new(width, height);
+        bitmap.resample_into(self, filter);
+        bitmap
+    }
+
+    /// Scale the [`Bitmap`] to the given size in place, marking all of it as modified.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either the `width` or `height` is zero.
+    pub fn resample_in_place(&mut self, width: u32, height: u32, filter: Filter) {
+        let resampled = self.resample(width, height, filter);
+        self.replace(width, height, resampled.buffer().to_vec());
+    }
+
+    /// Fill the [`Bitmap`] with the `source` scaled to its size.
+    fn resample_into(&mut self, source: &Bitmap, filter: Filter) {
+        let (width, height) = (self.width() as usize, self.height() as usize);
+        let (source_width, source_height) = (source.width() as usize, source.height() as usize);
+        let buffer = self.buffer_mut_untracked();
+
+        if filter == Filter::Nearest {
+            let columns: Vec<usize> = (0..width)
+                .map(|x| nearest(x, width, source_width))
+                .collect();
+
+            for_each_row(buffer, width, |y, row| {
+                let y = nearest(y, height, source_height);
+                let source = &source.buffer()[y * source_width..][..source_width];
+
+                for (pixel, x) in row.iter_mut().zip(&columns) {
+                    *pixel = source[*x];
+                }
+            });
+
+            return;
+        }
+
+        let columns = contributions(source_width, width, filter);
+        let rows = contributions(source_height, height, filter);
+
+        // Scale the rows horizontally into linear light, then the columns vertically.
+        let mut scaled = vec![[0.0; 4]; width * source_height];
+
+        for_each_row(&mut scaled, width, |y, row| {
+            let source = &source.buffer()[y * source_width..][..source_width];
+
+            for (pixel, contribution) in row.iter_mut().zip(&columns) {
+                let pixels = &source[contribution.start..];
+
+                for (source, weight) in pixels.iter().zip(&contribution.weights) {
+                    let source = to_linear(*source);
+
+                    for i in 0..4 {
+                        pixel[i] += source[i] * weight;
+                    }
+                }
+            }
+        });
+
+        for_each_row(buffer, width, |y, row| {
+            let contribution = &rows[y];
+            let mut sum = vec![[0.0; 4]; width];
+
+            for (offset, weight) in contribution.weights.iter().enumerate() {
+                let source = &scaled[(contribution.start + offset) * width..][..width];
+
+                for (sum, source) in sum.iter_mut().zip(source) {
+                    for i in 0..4 {
+                        sum[i] += source[i] * weight;
+                    }
+                }
+            }
+
+            for (pixel, sum) in row.iter_mut().zip(sum) {
+                *pixel = from_linear(sum);
+            }
+        });
+    }
+}
+
+/// The weights of the source pixels that make up a single output pixel.
+struct Contribution {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+/// Compute the [`Contribution`] of each output pixel along one axis.
+fn contributions(source: usize, output: usize, filter: Filter) -> Vec<Contribution> {
+    let scale = source as f32 / output as f32;
+    // Widen the filter when downscaling, so every source pixel is accounted for.
+    let stretch = scale.max(1.0);
+    let support = filter.support() * stretch;
+
+    (0..output)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * scale;
+            let start = (center - support).floor().max(0.0) as usize;
+            let end = ((center + support).ceil() as usize).min(source);
+
+            let mut weights: Vec<f32> = (start..end)
+                .map(|j| filter.weight((j as f32 + 0.5 - center) / stretch))
+                .collect();
+
+            let total: f32 = weights.iter().sum();
+
+            if total != 0.0 {
+                weights.iter_mut().for_each(|weight| *weight /= total);
+            }
+
+            Contribution { start, weights }
+        })
+        .collect()
+}
+
+/// The source coordinate closest to the center of the output pixel.
+fn nearest(i: usize, output: usize, source: usize) -> usize {
+    ((i * 2 + 1) * source / (output * 2)).min(source - 1)
+}
+
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        let x = x * PI;
+        x.sin() / x
+    }
+}
+
+/// The linear value of every sRGB byte.
+static LINEAR: LazyLock<[f32; 256]> = LazyLock::new(|| {
+    std::array::from_fn(|i| {
+        let c = i as f32 / 255.0;
+
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    })
+});
+
+/// Convert a pixel to premultiplied linear light.
+fn to_linear(pixel: u32) -> [f32; 4] {
+    let [r, g, b, a] = pixel.to_ne_bytes();
+    let alpha = a as f32 / 255.0;
+
+    [
+        LINEAR[r as usize] * alpha,
+        LINEAR[g as usize] * alpha,
+        LINEAR[b as usize] * alpha,
+        alpha,
+    ]
+}
+
+/// Convert a premultiplied linear pixel back to straight sRGB.
+fn from_linear([r, g, b, a]: [f32; 4]) -> u32 {
+    let alpha = a.clamp(0.0, 1.0);
+
+    let encode = |c: f32| {
+        let c = if alpha > 0.0 {
+            (c / alpha).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        let c = if c <= 0.0031308 {
+            c * 12.92
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        };
+
+        (c * 255.0).round() as u8
+    };
+
+    u32::from_ne_bytes([
+        encode(r),
+        encode(g),
+        encode(b),
+        (alpha * 255.0).round() as u8,
+    ])
+}
+
+/// Run `f` on each row of the buffer along with its index, in parallel with `rayon`.
+#[cfg(feature = "rayon")]
+fn for_each_row<T: Send>(buffer: &mut [T], width: usize, f: impl Fn(usize, &mut [T]) + Sync) {
+    use rayon::prelude::*;
+
+    buffer
+        .par_chunks_exact_mut(width)
+        .enumerate()
+        .for_each(|(y, row)| f(y, row));
+}
+
+/// Run `f` on each row of the buffer along with its index.
+#[cfg(not(feature = "rayon"))]
+fn for_each_row<T: Send>(buffer: &mut [T], width: usize, f: impl Fn(usize, &mut [T]) + Sync) {
+    buffer
+        .chunks_exact_mut(width)
+        .enumerate()
+        .for_each(|(y, row)| f(y, row));
+}
@@ -0,0 +1,491 @@
+//! Pixel selections in the form of a 1-bit [`Mask`].
+//!
+//! A [`Mask`] can be built up from rectangles, ellipses, lassos and the magic wand,
+//! displayed by a [`TextureCanvas`](crate::TextureCanvas) with
+//! [`selection`](crate::TextureCanvas::selection), and used to restrict edits
+//! with [`Bitmap::edit_masked`].
+use super::Bitmap;
+
+use iced_core::{Point, Rectangle};
+
+use std::sync::OnceLock;
+
+/// How a new selection is combined with the existing [`Mask`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// Replace the existing selection.
+    #[default]
+    Replace,
+    /// Add to the existing selection.
+    Add,
+    /// Remove from the existing selection.
+    Subtract,
+    /// Keep only the overlap with the existing selection.
+    Intersect,
+}
+
+/// A 1-bit selection mask.
+///
+/// Pixels are selected when their center lies inside of a selected shape.
+#[derive(Clone, Default)]
+pub struct Mask {
+    width: u32,
+    height: u32,
+    /// Bits are stored row by row, with each row starting on a new word.
+    bits: Vec<u64>,
+    /// The boundary of the selection, computed lazily.
+    outline: OnceLock<Vec<[Point<u32>; 2]>>,
+}
+
+impl Mask {
+    /// Create an empty [`Mask`].
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            bits: vec![0; words_per_row(width) * height as usize],
+            outline: OnceLock::new(),
+        }
+    }
+
+    /// Create an empty [`Mask`] with the same size as the [`Bitmap`].
+    pub fn for_bitmap(bitmap: &Bitmap) -> Self {
+        Self::new(bitmap.width(), bitmap.height())
+    }
+
+    /// Get the width of the [`Mask`]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Get the height of the [`Mask`]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Check if the pixel at the given coordinates is selected.
+    ///
+    /// Pixels outside of the [`Mask`] are never selected.
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        if x >= self.width || y >= self.height {
+            return false;
+        }
+
+        let (word, bit) = self.index(x, y);
+        self.bits[word] & (1 << bit) != 0
+    }
+
+    /// Select or deselect a single pixel.
+    pub fn set(&mut self, x: u32, y: u32, selected: bool) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+
+        let (word, bit) = self.index(x, y);
+
+        if selected {
+            self.bits[word] |= 1 << bit;
+        } else {
+            self.bits[word] &= !(1 << bit);
+        }
+
+        self.outline.take();
+    }
+
+    /// Check if nothing is selected.
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|word| *word == 0)
+    }
+
+    /// The number of selected pixels.
+    pub fn count(&self) -> usize {
+        self.bits
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// The smallest [`Rectangle`] containing every selected pixel.
+    pub fn bounds(&self) -> Option<Rectangle<u32>> {
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+
+        for y in 0..self.height {
+            for x in self.row_spans(y).flat_map(|(start, end)| [start, end - 1]) {
+                min_x = min_x.min(x);
+                max_x = max_x.max(x);
+                min_y = min_y.min(y);
+                max_y = max_y.max(y);
+            }
+        }
+
+        (min_x <= max_x).then(|| Rectangle {
+            x: min_x,
+            y: min_y,
+            width: max_x - min_x + 1,
+            height: max_y - min_y + 1,
+        })
+    }
+
+    /// Deselect every pixel.
+    pub fn clear(&mut self) {
+        self.bits.fill(0);
+        self.outline.take();
+    }
+
+    /// Select every pixel.
+    pub fn select_all(&mut self) {
+        self.bits.fill(u64::MAX);
+        self.clear_padding();
+        self.outline.take();
+    }
+
+    /// Invert the selection.
+    pub fn invert(&mut self) {
+        self.bits.iter_mut().for_each(|word| *word = !*word);
+        self.clear_padding();
+        self.outline.take();
+    }
+
+    /// Combine another [`Mask`] of the same size with this one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sizes of the masks don't match.
+    pub fn combine(&mut self, other: &Mask, mode: Mode) {
+        assert!(
+            self.width == other.width && self.height == other.height,
+            "Size mismatch!"
+        );
+
+        let op: fn(u64, u64) -> u64 = match mode {
+            Mode::Replace => |_, b| b,
+            Mode::Add => |a, b| a | b,
+            Mode::Subtract => |a, b| a & !b,
+            Mode::Intersect => |a, b| a & b,
+        };
+
+        for (a, b) in self.bits.iter_mut().zip(&other.bits) {
+            *a = op(*a, *b);
+        }
+
+        self.outline.take();
+    }
+
+    /// Select the pixels inside of a [`Rectangle`] in image coordinates.
+    pub fn rectangle(&mut self, rectangle: Rectangle, mode: Mode) {
+        self.select_with(mode, |mask| {
+            let x0 = pixel_start(rectangle.x, mask.width);
+            let x1 = pixel_start(rectangle.x + rectangle.width, mask.width);
+            let y0 = pixel_start(rectangle.y, mask.height);
+            let y1 = pixel_start(rectangle.y + rectangle.height, mask.height);
+
+            for y in y0..y1 {
+                mask.fill_span(y, x0, x1);
+            }
+        });
+    }
+
+    /// Select the pixels inside of the ellipse bounded by a [`Rectangle`] in image coordinates.
+    pub fn ellipse(&mut self, bounds: Rectangle, mode: Mode) {
+        self.select_with(mode, |mask| {
+            let rx = bounds.width / 2.0;
+            let ry = bounds.height / 2.0;
+            let cx = bounds.x + rx;
+            let cy = bounds.y + ry;
+
+            if rx <= 0.0 || ry <= 0.0 {
+                return;
+            }
+
+            let y0 = pixel_start(bounds.y, mask.height);
+            let y1 = pixel_start(bounds.y + bounds.height, mask.height);
+
+            for y in y0..y1 {
+                let dy = (y as f32 + 0.5 - cy) / ry;
+                let half = rx * (1.0 - dy * dy).max(0.0).sqrt();
+
+                let x0 = pixel_start(cx - half, mask.width);
+                let x1 = pixel_start(cx + half, mask.width);
+
+                mask.fill_span(y, x0, x1);
+            }
+        });
+    }
+
+    /// Select the pixels inside of a freehand lasso.
+    ///
+    /// The `points` are usually the cursor positions collected while dragging,
+    /// the path is closed automatically.
+    pub fn lasso(&mut self, points: &[Point], mode: Mode) {
+        self.polygon(points, mode);
+    }
+
+    /// Select the pixels inside of a polygon.
+    ///
+    /// Self intersecting polygons use the even-odd rule.
+    pub fn polygon(&mut self, points: &[Point], mode: Mode) {
+        self.select_with(mode, |mask| {
+            if points.len() < 3 {
+                return;
+            }
+
+            let mut crossings = Vec::new();
+
+            for y in 0..mask.height {
+                let center = y as f32 + 0.5;
+
+                crossings.clear();
+
+                let mut previous = points[points.len() - 1];
+
+                for &point in points {
+                    let (a, b) = (previous, point);
+                    previous = point;
+
+                    if (a.y > center) != (b.y > center) {
+                        crossings.push(a.x + (center - a.y) * (b.x - a.x) / (b.y - a.y));
+                    }
+                }
+
+                crossings.sort_by(f32::total_cmp);
+
+                for pair in crossings.chunks_exact(2) {
+                    let x0 = pixel_start(pair[0], mask.width);
+                    let x1 = pixel_start(pair[1], mask.width);
+
+                    mask.fill_span(y, x0, x1);
+                }
+            }
+        });
+    }
+
+    /// Select pixels with a color similar to the pixel at the `seed`.
+    ///
+    /// A pixel is similar if none of its `RGBA` channels differ by more than the `tolerance`.
+    /// When `contiguous` is set, only similar pixels connected to the `seed` are selected.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the [`Bitmap`] doesn't match the [`Mask`].
+    pub fn magic_wand(
+        &mut self,
+        bitmap: &Bitmap,
+        seed: Point<u32>,
+        tolerance: u8,
+        contiguous: bool,
+        mode: Mode,
+    ) {
+        assert!(
+            bitmap.width() == self.width && bitmap.height() == self.height,
+            "Size mismatch!"
+        );
+
+        self.select_with(mode, |mask| {
+            if seed.x >= mask.width || seed.y >= mask.height {
+                return;
+            }
+
+            let width = mask.width as usize;
+            let pixels = bitmap.raw();
+
+            let pixel = |x: u32, y: u32| {
+                let i = (y as usize * width + x as usize) * 4;
+                &pixels[i..i + 4]
+            };
+
+            let target: [u8; 4] = pixel(seed.x, seed.y).try_into().unwrap();
+
+            let similar = |x: u32, y: u32| {
+                pixel(x, y)
+                    .iter()
+                    .zip(target)
+                    .all(|(a, b)| a.abs_diff(b) <= tolerance)
+            };
+
+            if !contiguous {
+                for y in 0..mask.height {
+                    for x in 0..mask.width {
+                        if similar(x, y) {
+                            mask.set(x, y, true);
+                        }
+                    }
+                }
+
+                return;
+            }
+
+            // Scanline flood fill.
+            let mut stack = vec![seed];
+
+            while let Some(Point { x, y }) = stack.pop() {
+                if mask.contains(x, y) {
+                    continue;
+                }
+
+                let mut start = x;
+                while start > 0 && !mask.contains(start - 1, y) && similar(start - 1, y) {
+                    start -= 1;
+                }
+
+                let mut end = x + 1;
+                while end < mask.width && !mask.contains(end, y) && similar(end, y) {
+                    end += 1;
+                }
+
+                mask.fill_span(y, start, end);
+
+                for ny in [y.checked_sub(1), Some(y + 1).filter(|ny| *ny < mask.height)]
+                    .into_iter()
+                    .flatten()
+                {
+                    let mut inside = false;
+
+                    for nx in start..end {
+                        let candidate = !mask.contains(nx, ny) && similar(nx, ny);
+
+                        if candidate && !inside {
+                            stack.push(Point::new(nx, ny));
+                        }
+
+                        inside = candidate;
+                    }
+                }
+            }
+        });
+    }
+
+    /// The boundary between selected and unselected pixels as a list of
+    /// horizontal and vertical line segments in image coordinates.
+    ///
+    /// The result is cached until the [`Mask`] is modified.
+    pub fn outline(&self) -> &[[Point<u32>; 2]] {
+        self.outline.get_or_init(|| {
+            let mut segments = Vec::new();
+
+            // Horizontal edges between each pair of rows.
+            for y in 0..=self.height {
+                let mut run: Option<u32> = None;
+
+                for x in 0..=self.width {
+                    let edge =
+                        x < self.width && (y > 0 && self.contains(x, y - 1)) != self.contains(x, y);
+
+                    match (edge, run) {
+                        (true, None) => run = Some(x),
+                        (false, Some(start)) => {
+                            segments.push([Point::new(start, y), Point::new(x, y)]);
+                            run = None;
+                        }
+                        _ => (),
+                    }
+                }
+            }
+
+            // Vertical edges between each pair of columns.
+            for x in 0..=self.width {
+                let mut run: Option<u32> = None;
+
+                for y in 0..=self.height {
+                    let edge = y < self.height
+                        && (x > 0 && self.contains(x - 1, y)) != self.contains(x, y);
+
+                    match (edge, run) {
+                        (true, None) => run = Some(y),
+                        (false, Some(start)) => {
+                            segments.push([Point::new(x, start), Point::new(x, y)]);
+                            run = None;
+                        }
+                        _ => (),
+                    }
+                }
+            }
+
+            segments
+        })
+    }
+
+    /// Iterate over the selected spans of a row as `start..end` pairs.
+    fn row_spans(&self, y: u32) -> impl Iterator<Item = (u32, u32)> + '_ {
+        let mut x = 0;
+
+        std::iter::from_fn(move || {
+            while x < self.width && !self.contains(x, y) {
+                x += 1;
+            }
+
+            if x >= self.width {
+                return None;
+            }
+
+            let start = x;
+
+            while x < self.width && self.contains(x, y) {
+                x += 1;
+            }
+
+            Some((start, x))
+        })
+    }
+
+    fn select_with(&mut self, mode: Mode, select: impl FnOnce(&mut Mask)) {
+        let mut selection = Mask::new(self.width, self.height);
+        select(&mut selection);
+        self.combine(&selection, mode);
+    }
+
+    /// Select the pixels of a row in `x0..x1`.
+    fn fill_span(&mut self, y: u32, x0: u32, x1: u32) {
+        for x in x0..x1.min(self.width) {
+            let (word, bit) = self.index(x, y);
+            self.bits[word] |= 1 << bit;
+        }
+
+        self.outline.take();
+    }
+
+    fn index(&self, x: u32, y: u32) -> (usize, u32) {
+        let word = y as usize * words_per_row(self.width) + x as usize / 64;
+        (word, x % 64)
+    }
+
+    /// Clear the unused bits at the end of each row.
+    fn clear_padding(&mut self) {
+        let words = words_per_row(self.width);
+        let used = self.width % 64;
+
+        if used == 0 || words == 0 {
+            return;
+        }
+
+        for row in self.bits.chunks_mut(words) {
+            row[words - 1] &= (1 << used) - 1;
+        }
+    }
+}
+
+impl PartialEq for Mask {
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width && self.height == other.height && self.bits == other.bits
+    }
+}
+
+impl Eq for Mask {}
+
+impl std::fmt::Debug for Mask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Mask")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("selected", &self.count())
+            .finish()
+    }
+}
+
+fn words_per_row(width: u32) -> usize {
+    (width as usize).div_ceil(64)
+}
+
+/// The first pixel whose center lies at or after the coordinate.
+fn pixel_start(coordinate: f32, limit: u32) -> u32 {
+    (coordinate - 0.5).ceil().clamp(0.0, limit as f32) as u32
+}
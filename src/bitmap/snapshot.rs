@@ -0,0 +1,107 @@
+//! Immutable snapshots of a [`Bitmap`].
+use super::{Bitmap, Damage, Rgba, SurfaceInner};
+use crate::widget::surface::SurfaceId;
+
+use iced_core::Size;
+
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+/// An immutable view of a [`Bitmap`] at the time it was taken.
+///
+/// Taking a snapshot only clones an [`Arc`]. The pixels are shared with the [`Bitmap`] until it's
+/// next written to, at which point the [`Bitmap`] copies them, leaving the snapshot untouched.
+///
+/// Snapshots can be sent to other threads, so frames can be handed to encoders or file savers
+/// without blocking painting.
+#[derive(Debug, Clone)]
+pub struct BitmapSnapshot {
+    buffer: Arc<Vec<u32>>,
+    width: NonZeroU32,
+    height: NonZeroU32,
+}
+
+impl Bitmap {
+    /// Take a [`BitmapSnapshot`] of the current pixels.
+    pub fn snapshot(&self) -> BitmapSnapshot {
+        BitmapSnapshot {
+            buffer: Arc::clone(&self.0.buffer),
+            width: self.0.width,
+            height: self.0.height,
+        }
+    }
+}
+
+impl BitmapSnapshot {
+    /// Get the width of the [`BitmapSnapshot`]
+    pub fn width(&self) -> u32 {
+        self.width.get()
+    }
+
+    /// Get the height of the [`BitmapSnapshot`]
+    pub fn height(&self) -> u32 {
+        self.height.get()
+    }
+
+    /// Get the [`Size`] of the [`BitmapSnapshot`]
+    pub fn size(&self) -> Size {
+        (self.width() as f32, self.height() as f32).into()
+    }
+
+    /// Get an immutable u8 slice of the raw `RGBA` image data.
+    pub fn raw(&self) -> &[u8] {
+        bytemuck::cast_slice(self.buffer())
+    }
+
+    /// Get an immutable u32 slice of the raw `RGBA` image data.
+    pub fn buffer(&self) -> &[u32] {
+        &self.buffer
+    }
+
+    /// Get an immutable slice of the pixels, row by row.
+    pub fn pixels(&self) -> &[Rgba] {
+        bytemuck::cast_slice(self.buffer())
+    }
+
+    /// Get the pixel at the given coordinates, if it's inside of the [`BitmapSnapshot`].
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<Rgba> {
+        self.row(y)?.get(x as usize).copied()
+    }
+
+    /// Get a row of pixels, if it's inside of the [`BitmapSnapshot`].
+    pub fn row(&self, y: u32) -> Option<&[Rgba]> {
+        let width = self.width() as usize;
+        (y < self.height()).then(|| &self.pixels()[y as usize * width..][..width])
+    }
+
+    /// Iterate over the rows of pixels from top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item = &[Rgba]> {
+        self.pixels().chunks_exact(self.width() as usize)
+    }
+
+    /// Create a [`Bitmap`] from the [`BitmapSnapshot`], sharing its pixels until it's written to.
+    ///
+    /// This is as cheap as taking the snapshot, and gives access to everything that takes a
+    /// [`Bitmap`], like [`Bitmap::encode`].
+    pub fn to_bitmap(&self) -> Bitmap {
+        Bitmap(Arc::new(SurfaceInner {
+            buffer: Arc::clone(&self.buffer),
+            width: self.width,
+            height: self.height,
+            id: SurfaceId::unique(),
+            dirty: Damage::default(),
+        }))
+    }
+}
+
+impl From<&BitmapSnapshot> for Bitmap {
+    fn from(snapshot: &BitmapSnapshot) -> Self {
+        snapshot.to_bitmap()
+    }
+}
+
+impl From<BitmapSnapshot> for Bitmap {
+    fn from(snapshot: BitmapSnapshot) -> Self {
+        snapshot.to_bitmap()
+    }
+}
@@ -0,0 +1,459 @@
+//! Frames produced on another thread and displayed by a [`TextureCanvas`](crate::TextureCanvas).
+//!
+//! A [`swapchain`] is split into a [`Producer`] that can be moved to another thread, and a
+//! [`Consumer`] that is kept in the application state and displayed like a [`Bitmap`].
+//!
+//! The [`Producer`] draws into its own [`Bitmap`] and [`publish`](Producer::publish)es it as a
+//! new frame. The [`Consumer`] picks up the latest frame the next time the widget is drawn,
+//! skipping any frames published in between, and [`Consumer::subscription`] notifies the
+//! application so it can redraw. Frames are recycled, so no more than three buffers are in use
+//! at a time, and neither side ever waits for the other.
+//!
+//! With the `rayon` feature, [`Producer::par_tiles_mut`] and [`Producer::par_rows_mut`] render a
+//! frame in parallel and publish every tile as soon as it's finished, so progressive renders show
+//! up tile by tile.
+//!
+//! ```no_run
+//! # use iced_texture_canvas::bitmap::swapchain;
+//! # use iced_texture_canvas::{Rgba, texture_canvas};
+//! # use iced_widget::runtime::futures::Subscription;
+//! # #[derive(Clone)] enum Message { Frame }
+//! let (mut producer, consumer) = swapchain::swapchain(320, 240);
+//!
+//! std::thread::spawn(move || loop {
+//!     producer.bitmap_mut().pixels_mut().fill(Rgba::BLACK);
+//!     // ... render the frame
+//!     producer.publish();
+//! });
+//!
+//! // In the application:
+//! let subscription: Subscription<Message> = consumer.subscription().map(|_| Message::Frame);
+//! let canvas = texture_canvas::<Message, iced_core::Theme, _>(&consumer);
+//! ```
+use super::{Bitmap, BitmapViewMut};
+use crate::widget::surface::{Surface, SurfaceHandler, SurfaceId};
+
+use iced_widget::runtime::futures::Subscription;
+use iced_widget::runtime::futures::futures::Stream;
+
+use std::hash::{Hash, Hasher};
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll, Waker};
+
+/// Create a connected [`Producer`] and [`Consumer`] of frames, starting with a transparent frame.
+///
+/// # Panics
+///
+/// Panics if either the `width` or `height` is zero.
+pub fn swapchain(width: u32, height: u32) -> (Producer, Consumer) {
+    let bitmap = Bitmap::new(width, height);
+
+    let id = SurfaceId::unique();
+
+    let shared = Arc::new(Shared {
+        slot: Mutex::new(Slot {
+            latest: None,
+            displayed: Arc::new(Frame::new(&bitmap, Vec::new(), id, 0)),
+            spare: Vec::new(),
+        }),
+        sequence: AtomicU64::new(0),
+        id,
+        closed: AtomicBool::new(false),
+        waker: Mutex::new(None),
+    });
+
+    let consumer = Consumer {
+        shared: Arc::clone(&shared),
+    };
+
+    (Producer { bitmap, shared }, consumer)
+}
+
+/// The writing half of a [`swapchain`], which can be moved to another thread.
+#[derive(Debug)]
+pub struct Producer {
+    bitmap: Bitmap,
+    shared: Arc<Shared>,
+}
+
+impl Producer {
+    /// The [`Bitmap`] the next frame is drawn into.
+    pub fn bitmap(&self) -> &Bitmap {
+        &self.bitmap
+    }
+
+    /// Mutably borrow the [`Bitmap`] the next frame is drawn into.
+    ///
+    /// It keeps its content after being published, so frames can be drawn incrementally.
+    pub fn bitmap_mut(&mut self) -> &mut Bitmap {
+        &mut self.bitmap
+    }
+
+    /// Publish the current content of the [`Bitmap`] as the latest frame.
+    ///
+    /// A previously published frame that the [`Consumer`] hasn't picked up yet is discarded.
+    pub fn publish(&mut self) {
+        {
+            let mut slot = self
+                .shared
+                .slot
+                .lock()
+                .unwrap_or_else(|error| error.into_inner());
+            let buffer = slot.spare.pop().unwrap_or_default();
+            let version = self.shared.sequence.load(Ordering::Acquire) + 1;
+            let frame = Frame::new(&self.bitmap, buffer, self.shared.id, version);
+
+            if let Some(skipped) = slot.latest.replace(frame) {
+                slot.recycle(skipped.buffer);
+            }
+        }
+
+        self.shared.sequence.fetch_add(1, Ordering::Release);
+        self.shared.wake();
+    }
+
+    /// Split the [`Bitmap`] into disjoint tiles of the given size, to be rendered in parallel.
+    ///
+    /// Every tile is published on its own as soon as it's dropped, on top of the latest frame, so
+    /// the [`Consumer`] shows the render progressing tile by tile.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either the `width` or `height` is zero.
+    #[cfg(feature = "rayon")]
+    pub fn par_tiles_mut(
+        &mut self,
+        width: u32,
+        height: u32,
+    ) -> impl rayon::iter::IndexedParallelIterator<Item = TileMut<'_>> {
+        use rayon::iter::ParallelIterator;
+
+        let size = (self.bitmap.width(), self.bitmap.height());
+        let shared = &self.shared;
+
+        self.bitmap
+            .par_tiles_mut(width, height)
+            .map(move |view| TileMut { view, shared, size })
+    }
+
+    /// Split the [`Bitmap`] into disjoint bands of rows with the given height, to be rendered in parallel.
+    ///
+    /// Every band is published on its own as soon as it's dropped, like the tiles of
+    /// [`par_tiles_mut`](Self::par_tiles_mut).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `height` is zero.
+    #[cfg(feature = "rayon")]
+    pub fn par_rows_mut(
+        &mut self,
+        height: u32,
+    ) -> impl rayon::iter::IndexedParallelIterator<Item = TileMut<'_>> {
+        let width = self.bitmap.width();
+        self.par_tiles_mut(width, height)
+    }
+}
+
+/// A tile of the [`Bitmap`] of a [`Producer`], published as soon as it's dropped.
+///
+/// It can be used like any other [`BitmapViewMut`].
+#[derive(Debug)]
+pub struct TileMut<'a> {
+    view: BitmapViewMut<'a>,
+    shared: &'a Shared,
+    /// The size of the [`Bitmap`] the tile belongs to.
+    size: (u32, u32),
+}
+
+impl<'a> Deref for TileMut<'a> {
+    type Target = BitmapViewMut<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.view
+    }
+}
+
+impl DerefMut for TileMut<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.view
+    }
+}
+
+impl Drop for TileMut<'_> {
+    fn drop(&mut self) {
+        {
+            let mut slot = self
+                .shared
+                .slot
+                .lock()
+                .unwrap_or_else(|error| error.into_inner());
+
+            // Bump the sequence while the frame is locked, so tiles finished at the same time
+            // never publish the same version twice.
+            let version = self.shared.sequence.fetch_add(1, Ordering::Release) + 1;
+            let frame = slot.pending(self.shared.id, self.size);
+
+            frame.copy_view(&self.view);
+            frame.version = version;
+        }
+
+        self.shared.wake();
+    }
+}
+
+impl Drop for Producer {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+        self.shared.wake();
+    }
+}
+
+/// The reading half of a [`swapchain`], displayed by a [`TextureCanvas`](crate::TextureCanvas).
+#[derive(Debug)]
+pub struct Consumer {
+    shared: Arc<Shared>,
+}
+
+impl Consumer {
+    /// A [`Subscription`] that produces a value every time a new frame is published,
+    /// so the application can redraw.
+    ///
+    /// It ends once the [`Producer`] is dropped.
+    pub fn subscription(&self) -> Subscription<()> {
+        Subscription::run_with(Frames(Arc::clone(&self.shared)), |frames| Published {
+            shared: Arc::clone(&frames.0),
+            seen: 0,
+        })
+    }
+
+    /// Whether a frame was published that hasn't been picked up yet.
+    pub fn has_new_frame(&self) -> bool {
+        self.shared
+            .slot
+            .lock()
+            .is_ok_and(|slot| slot.latest.is_some())
+    }
+
+    /// The latest frame, picking up a newly published one if there is any.
+    fn latest(&self) -> Arc<Frame> {
+        let mut slot = self
+            .shared
+            .slot
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+
+        if let Some(frame) = slot.latest.take() {
+            let previous = std::mem::replace(&mut slot.displayed, Arc::new(frame));
+
+            // The renderer may still hold on to the previous frame, in which case it's dropped later.
+            if let Ok(previous) = Arc::try_unwrap(previous) {
+                slot.recycle(previous.buffer);
+            }
+        }
+
+        Arc::clone(&slot.displayed)
+    }
+}
+
+impl SurfaceHandler for Consumer {
+    type Surface = Frame;
+
+    fn width(&self) -> u32 {
+        self.latest().width
+    }
+
+    fn height(&self) -> u32 {
+        self.latest().height
+    }
+
+    fn create_weak(&self) -> Weak<Self::Surface> {
+        Arc::downgrade(&self.latest())
+    }
+}
+
+/// A published frame.
+///
+/// Every frame of a [`swapchain`] shares the same [`SurfaceId`], with the number of frames
+/// published before it as its version.
+pub struct Frame {
+    buffer: Vec<u32>,
+    width: u32,
+    height: u32,
+    id: SurfaceId,
+    version: u64,
+}
+
+impl Frame {
+    /// Copy the [`Bitmap`] into a recycled buffer.
+    fn new(bitmap: &Bitmap, mut buffer: Vec<u32>, id: SurfaceId, version: u64) -> Self {
+        buffer.clear();
+        buffer.extend_from_slice(bitmap.buffer());
+
+        Self {
+            buffer,
+            width: bitmap.width(),
+            height: bitmap.height(),
+            id,
+            version,
+        }
+    }
+
+    /// Copy the pixels of a finished tile into the frame.
+    fn copy_view(&mut self, view: &BitmapViewMut<'_>) {
+        let region = view.region();
+
+        for (row, y) in view.rows().zip(region.y..) {
+            let start = y as usize * self.width as usize + region.x as usize;
+
+            self.buffer[start..start + row.len()].copy_from_slice(bytemuck::cast_slice(row));
+        }
+    }
+}
+
+impl Surface for Frame {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn data(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.buffer)
+    }
+
+    fn id(&self) -> SurfaceId {
+        self.id
+    }
+
+    fn version(&self) -> u64 {
+        self.version
+    }
+}
+
+impl std::fmt::Debug for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("frame")
+            .field("buffer", &"...")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("version", &self.version)
+            .finish()
+    }
+}
+
+/// The state shared by a [`Producer`] and [`Consumer`].
+#[derive(Debug)]
+struct Shared {
+    slot: Mutex<Slot>,
+    /// The number of frames published so far.
+    sequence: AtomicU64,
+    /// Shared by every [`Frame`].
+    id: SurfaceId,
+    /// Whether the [`Producer`] was dropped.
+    closed: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+impl Shared {
+    fn wake(&self) {
+        if let Some(waker) = self.waker.lock().ok().and_then(|mut waker| waker.take()) {
+            waker.wake();
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Slot {
+    /// The latest frame, until it's picked up by the [`Consumer`].
+    latest: Option<Frame>,
+    /// The frame picked up last by the [`Consumer`].
+    displayed: Arc<Frame>,
+    /// Buffers of frames that are no longer displayed.
+    spare: Vec<Vec<u32>>,
+}
+
+impl Slot {
+    /// The latest frame, to publish a tile on top of.
+    ///
+    /// Once the [`Consumer`] has picked it up, a new one is started from a copy of it.
+    fn pending(&mut self, id: SurfaceId, (width, height): (u32, u32)) -> &mut Frame {
+        if let Some(stale) = self
+            .latest
+            .take_if(|frame| frame.width != width || frame.height != height)
+        {
+            self.recycle(stale.buffer);
+        }
+
+        self.latest.get_or_insert_with(|| {
+            let mut buffer = self.spare.pop().unwrap_or_default();
+            let displayed = &self.displayed;
+
+            buffer.clear();
+
+            if displayed.width == width && displayed.height == height {
+                buffer.extend_from_slice(&displayed.buffer);
+            } else {
+                buffer.resize(width as usize * height as usize, 0);
+            }
+
+            Frame {
+                buffer,
+                width,
+                height,
+                id,
+                version: 0,
+            }
+        })
+    }
+
+    fn recycle(&mut self, buffer: Vec<u32>) {
+        // One buffer is drawn into, one is published and one is displayed.
+        if self.spare.is_empty() {
+            self.spare.push(buffer);
+        }
+    }
+}
+
+/// Identifies the [`Subscription`] of a [`swapchain`].
+struct Frames(Arc<Shared>);
+
+impl Hash for Frames {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).hash(state);
+    }
+}
+
+/// A [`Stream`] of published frames.
+struct Published {
+    shared: Arc<Shared>,
+    seen: u64,
+}
+
+impl Stream for Published {
+    type Item = ();
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        // Register before checking, so a frame published in between isn't missed.
+        if let Ok(mut waker) = this.shared.waker.lock() {
+            *waker = Some(cx.waker().clone());
+        }
+
+        let sequence = this.shared.sequence.load(Ordering::Acquire);
+
+        if sequence != this.seen {
+            this.seen = sequence;
+            Poll::Ready(Some(()))
+        } else if this.shared.closed.load(Ordering::Acquire) {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+}
@@ -0,0 +1,226 @@
+//! Changing the size and orientation of a [`Bitmap`] while keeping its content in place.
+//!
+//! Every operation marks the whole [`Bitmap`] as modified, so the texture is reallocated or
+//! uploaded again even if its size ends up unchanged.
+use super::{Bitmap, Rgba};
+
+use iced_core::{Padding, Rectangle};
+
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+/// Where the existing content of a [`Bitmap`] is placed when its canvas is resized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Anchor {
+    #[default]
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// The offset of the old content inside of the new canvas.
+    fn offset(self, old: (u32, u32), new: (u32, u32)) -> (i64, i64) {
+        let (horizontal, vertical) = match self {
+            Self::TopLeft => (0, 0),
+            Self::Top => (1, 0),
+            Self::TopRight => (2, 0),
+            Self::Left => (0, 1),
+            Self::Center => (1, 1),
+            Self::Right => (2, 1),
+            Self::BottomLeft => (0, 2),
+            Self::Bottom => (1, 2),
+            Self::BottomRight => (2, 2),
+        };
+
+        let align = |alignment: i64, old: u32, new: u32| (new as i64 - old as i64) * alignment / 2;
+
+        (
+            align(horizontal, old.0, new.0),
+            align(vertical, old.1, new.1),
+        )
+    }
+}
+
+impl Bitmap {
+    /// Change the size of the canvas, keeping the existing pixels at their position
+    /// relative to the `anchor`.
+    ///
+    /// Pixels outside of the new canvas are discarded, and new pixels are set to `fill`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either the `width` or `height` is zero.
+    pub fn resize_canvas(
+        &mut self,
+        width: u32,
+        height: u32,
+        anchor: Anchor,
+        fill: impl Into<Rgba>,
+    ) {
+        let (x, y) = anchor.offset((self.width(), self.height()), (width, height));
+        self.reframe(width, height, x, y, fill.into());
+    }
+
+    /// Crop the [`Bitmap`] to the rectangle, clipped to its bounds.
+    ///
+    /// Returns the clipped rectangle, or [`None`] without changing anything if it's empty.
+    pub fn crop(&mut self, rectangle: Rectangle<u32>) -> Option<Rectangle<u32>> {
+        let x = rectangle.x.min(self.width());
+        let y = rectangle.y.min(self.height());
+        let right = rectangle
+            .x
+            .saturating_add(rectangle.width)
+            .min(self.width());
+        let bottom = rectangle
+            .y
+            .saturating_add(rectangle.height)
+            .min(self.height());
+
+        if right <= x || bottom <= y {
+            return None;
+        }
+
+        self.reframe(
+            right - x,
+            bottom - y,
+            -(x as i64),
+            -(y as i64),
+            Rgba::TRANSPARENT,
+        );
+
+        Some(Rectangle {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        })
+    }
+
+    /// Add the `padding` around the [`Bitmap`], filled with a single color.
+    ///
+    /// Each side is rounded to the nearest pixel.
+    ///
+    /// Returns `false` without changing anything if the new size doesn't fit in a `u32`.
+    pub fn extend(&mut self, padding: impl Into<Padding>, fill: impl Into<Rgba>) -> bool {
+        let padding = padding.into();
+        let side = |side: f32| side.max(0.0).round() as u32;
+
+        let (left, top) = (side(padding.left), side(padding.top));
+
+        let size = self
+            .width()
+            .checked_add(left)
+            .and_then(|width| width.checked_add(side(padding.right)))
+            .zip(
+                self.height()
+                    .checked_add(top)
+                    .and_then(|height| height.checked_add(side(padding.bottom))),
+            );
+
+        let Some((width, height)) = size else {
+            return false;
+        };
+
+        self.reframe(width, height, left as i64, top as i64, fill.into());
+        true
+    }
+
+    /// Mirror the [`Bitmap`] from left to right.
+    pub fn flip_horizontal(&mut self) {
+        let width = self.width() as usize;
+
+        self.buffer_mut()
+            .chunks_exact_mut(width)
+            .for_each(<[u32]>::reverse);
+    }
+
+    /// Mirror the [`Bitmap`] from top to bottom.
+    pub fn flip_vertical(&mut self) {
+        let width = self.width() as usize;
+        let height = self.height() as usize;
+        let buffer = self.buffer_mut();
+
+        for y in 0..height / 2 {
+            let (top, bottom) = buffer.split_at_mut((height - 1 - y) * width);
+            top[y * width..(y + 1) * width].swap_with_slice(&mut bottom[..width]);
+        }
+    }
+
+    /// Rotate the [`Bitmap`] by 90 degrees clockwise, swapping its width and height.
+    pub fn rotate90(&mut self) {
+        self.rotate(|x, y, _, height| (height - 1 - y, x));
+    }
+
+    /// Rotate the [`Bitmap`] by 180 degrees.
+    pub fn rotate180(&mut self) {
+        self.buffer_mut().reverse();
+    }
+
+    /// Rotate the [`Bitmap`] by 270 degrees clockwise, swapping its width and height.
+    pub fn rotate270(&mut self) {
+        self.rotate(|x, y, width, _| (y, width - 1 - x));
+    }
+
+    /// Move every pixel to the position given by `map(x, y, width, height)` in the transposed [`Bitmap`].
+    fn rotate(&mut self, map: impl Fn(usize, usize, usize, usize) -> (usize, usize)) {
+        let (width, height) = (self.width() as usize, self.height() as usize);
+        let mut buffer = vec![0; width * height];
+
+        for (y, row) in self.buffer().chunks_exact(width).enumerate() {
+            for (x, pixel) in row.iter().enumerate() {
+                let (x, y) = map(x, y, width, height);
+                buffer[y * height + x] = *pixel;
+            }
+        }
+
+        self.replace(self.height(), self.width(), buffer);
+    }
+
+    /// Move the content by `(x, y)` into a new canvas of the given size.
+    fn reframe(&mut self, width: u32, height: u32, x: i64, y: i64, fill: Rgba) {
+        let mut buffer = vec![fill.to_u32(); width as usize * height as usize];
+
+        let (old_width, old_height) = (self.width() as i64, self.height() as i64);
+        let left = x.max(0);
+        let right = (x + old_width).min(width as i64);
+
+        if left < right {
+            let length = (right - left) as usize;
+
+            for row in 0..old_height {
+                let target = row + y;
+
+                if !(0..height as i64).contains(&target) {
+                    continue;
+                }
+
+                let source = (row * old_width + left - x) as usize;
+                let destination = (target * width as i64 + left) as usize;
+
+                buffer[destination..destination + length]
+                    .copy_from_slice(&self.buffer()[source..source + length]);
+            }
+        }
+
+        self.replace(width, height, buffer);
+    }
+
+    /// Replace the content of the [`Bitmap`], marking all of it as modified.
+    pub(super) fn replace(&mut self, width: u32, height: u32, buffer: Vec<u32>) {
+        let width = NonZeroU32::new(width).expect("width must be greater than 0");
+        let height = NonZeroU32::new(height).expect("height must be greater than 0");
+        let this = Arc::make_mut(&mut self.0);
+
+        this.buffer = Arc::new(buffer);
+        this.width = width;
+        this.height = height;
+        this.buffer_mut();
+    }
+}
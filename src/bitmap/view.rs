@@ -0,0 +1,404 @@
+//! Borrowed views into a rectangle of a [`Bitmap`].
+//!
+//! A [`Bitmap`] can also be split into disjoint [`BitmapViewMut`]s with
+//! [`tiles_mut`](Bitmap::tiles_mut), or processed in parallel with `par_tiles_mut`
+//! and `par_rows_mut` when the `rayon` feature is enabled.
+//!
+//! Mutable views borrow the [`Bitmap`] itself, so a [`TextureCanvas`](crate::TextureCanvas) only
+//! draws the new pixels once every view is gone. To show a render tile by tile while it's still
+//! in progress, render on another thread with the `par_tiles_mut` and `par_rows_mut` of a
+//! [`swapchain::Producer`](super::swapchain::Producer) instead.
+use super::{Bitmap, Damage, Rgba};
+
+use iced_core::Rectangle;
+
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+use std::sync::Arc;
+
+/// An immutable view into a rectangle of a [`Bitmap`].
+///
+/// Coordinates are relative to the top left corner of the view.
+#[derive(Debug, Clone, Copy)]
+pub struct BitmapView<'a> {
+    pixels: &'a [Rgba],
+    stride: usize,
+    region: Rectangle<u32>,
+}
+
+/// A mutable view into a rectangle of a [`Bitmap`].
+///
+/// The whole rectangle is marked as modified when the view is dropped.
+///
+/// Coordinates are relative to the top left corner of the view.
+#[derive(Debug)]
+pub struct BitmapViewMut<'a> {
+    pixels: Pixels<'a>,
+    region: Rectangle<u32>,
+    damage: &'a Damage,
+}
+
+impl Bitmap {
+    /// Borrow a rectangle of the [`Bitmap`], clipped to its bounds.
+    ///
+    /// Returns `None` if the clipped rectangle is empty.
+    pub fn view(&self, rectangle: Rectangle<u32>) -> Option<BitmapView<'_>> {
+        let region = clip(self, rectangle)?;
+
+        Some(BitmapView {
+            pixels: &self.pixels()[offset(self, region)..],
+            stride: self.width() as usize,
+            region,
+        })
+    }
+
+    /// Mutably borrow a rectangle of the [`Bitmap`], clipped to its bounds.
+    ///
+    /// Returns `None` if the clipped rectangle is empty.
+    pub fn view_mut(&mut self, rectangle: Rectangle<u32>) -> Option<BitmapViewMut<'_>> {
+        let region = clip(self, rectangle)?;
+        let (pixels, damage) = self.split();
+
+        // SAFETY: The region was clipped to the bounds of the bitmap, and it's the only view.
+        Some(unsafe { pixels.view(region, damage) })
+    }
+
+    /// Split the [`Bitmap`] into disjoint tiles of the given size, row by row.
+    ///
+    /// Tiles on the right and bottom edges are cut to fit inside of the [`Bitmap`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if either the `width` or `height` is zero.
+    pub fn tiles_mut(
+        &mut self,
+        width: u32,
+        height: u32,
+    ) -> impl ExactSizeIterator<Item = BitmapViewMut<'_>> {
+        let tiles = Tiles::new(self, width, height);
+        let (pixels, damage) = self.split();
+
+        (0..tiles.count()).map(move |index| {
+            // SAFETY: Every index is visited once, and tiles with different indices are disjoint.
+            unsafe { pixels.view(tiles.get(index), damage) }
+        })
+    }
+
+    /// Split the [`Bitmap`] into disjoint tiles of the given size, to be processed in parallel.
+    ///
+    /// Each tile marks its region as modified when it's dropped, and the new pixels are drawn
+    /// once the [`Bitmap`] is no longer borrowed. Use the
+    /// [`par_tiles_mut`](super::swapchain::Producer::par_tiles_mut) of a swapchain to draw every
+    /// tile as soon as it's finished.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either the `width` or `height` is zero.
+    #[cfg(feature = "rayon")]
+    pub fn par_tiles_mut(
+        &mut self,
+        width: u32,
+        height: u32,
+    ) -> impl rayon::iter::IndexedParallelIterator<Item = BitmapViewMut<'_>> {
+        use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+        let tiles = Tiles::new(self, width, height);
+        let (pixels, damage) = self.split();
+
+        (0..tiles.count()).into_par_iter().map(move |index| {
+            // SAFETY: Every index is visited once, and tiles with different indices are disjoint.
+            unsafe { pixels.view(tiles.get(index), damage) }
+        })
+    }
+
+    /// Split the [`Bitmap`] into disjoint bands of rows with the given height, to be processed in parallel.
+    ///
+    /// Each band marks its region as modified when it's dropped, and the new pixels are drawn
+    /// once the [`Bitmap`] is no longer borrowed. Use the
+    /// [`par_rows_mut`](super::swapchain::Producer::par_rows_mut) of a swapchain to draw every
+    /// band as soon as it's finished.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `height` is zero.
+    #[cfg(feature = "rayon")]
+    pub fn par_rows_mut(
+        &mut self,
+        height: u32,
+    ) -> impl rayon::iter::IndexedParallelIterator<Item = BitmapViewMut<'_>> {
+        let width = self.width();
+        self.par_tiles_mut(width, height)
+    }
+
+    /// Borrow the pixels mutably along with the damage they're marked in, without marking anything.
+    fn split(&mut self) -> (Pixels<'_>, &Damage) {
+        let width = self.width() as usize;
+        let inner = Arc::make_mut(&mut self.0);
+        let pixels: &mut [Rgba] =
+            bytemuck::cast_slice_mut(Arc::make_mut(&mut inner.buffer).as_mut_slice());
+
+        (
+            Pixels {
+                pointer: NonNull::from(pixels).cast(),
+                stride: width,
+                lifetime: PhantomData,
+            },
+            &inner.dirty,
+        )
+    }
+}
+
+impl<'a> BitmapView<'a> {
+    /// Get the width of the view.
+    pub fn width(&self) -> u32 {
+        self.region.width
+    }
+
+    /// Get the height of the view.
+    pub fn height(&self) -> u32 {
+        self.region.height
+    }
+
+    /// Get the rectangle of the [`Bitmap`] covered by the view.
+    pub fn region(&self) -> Rectangle<u32> {
+        self.region
+    }
+
+    /// Get the pixel at the given coordinates, if it's inside of the view.
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<Rgba> {
+        self.row(y)?.get(x as usize).copied()
+    }
+
+    /// Get a row of pixels, if it's inside of the view.
+    pub fn row(&self, y: u32) -> Option<&'a [Rgba]> {
+        (y < self.region.height).then(|| {
+            let start = y as usize * self.stride;
+            &self.pixels[start..start + self.region.width as usize]
+        })
+    }
+
+    /// Iterate over the rows of pixels from top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item = &'a [Rgba]> + use<'a> {
+        let width = self.region.width as usize;
+
+        self.pixels
+            .chunks(self.stride)
+            .take(self.region.height as usize)
+            .map(move |row| &row[..width])
+    }
+
+    /// Iterate over the pixels along with their `(x, y)` coordinates.
+    pub fn enumerate_pixels(&self) -> impl Iterator<Item = (u32, u32, Rgba)> + use<'a> {
+        self.rows()
+            .zip(0..)
+            .flat_map(|(row, y)| row.iter().zip(0..).map(move |(pixel, x)| (x, y, *pixel)))
+    }
+}
+
+impl<'a> BitmapViewMut<'a> {
+    /// Get the width of the view.
+    pub fn width(&self) -> u32 {
+        self.region.width
+    }
+
+    /// Get the height of the view.
+    pub fn height(&self) -> u32 {
+        self.region.height
+    }
+
+    /// Get the rectangle of the [`Bitmap`] covered by the view.
+    pub fn region(&self) -> Rectangle<u32> {
+        self.region
+    }
+
+    /// Get the pixel at the given coordinates, if it's inside of the view.
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<Rgba> {
+        self.row(y)?.get(x as usize).copied()
+    }
+
+    /// Get a row of pixels, if it's inside of the view.
+    pub fn row(&self, y: u32) -> Option<&[Rgba]> {
+        // SAFETY: The row is inside of the view, which is borrowed immutably.
+        (y < self.region.height).then(|| unsafe { &*self.pixels.row(y, self.region.width) })
+    }
+
+    /// Iterate over the rows of pixels from top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item = &[Rgba]> {
+        (0..self.region.height).map(|y| {
+            // SAFETY: The row is inside of the view, which is borrowed immutably.
+            unsafe { &*self.pixels.row(y, self.region.width) }
+        })
+    }
+
+    /// Iterate over the pixels along with their `(x, y)` coordinates.
+    pub fn enumerate_pixels(&self) -> impl Iterator<Item = (u32, u32, Rgba)> {
+        self.rows()
+            .zip(0..)
+            .flat_map(|(row, y)| row.iter().zip(0..).map(move |(pixel, x)| (x, y, *pixel)))
+    }
+
+    /// Set the pixel at the given coordinates.
+    ///
+    /// Returns `false` if the pixel is outside of the view.
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: impl Into<Rgba>) -> bool {
+        let Some(pixel) = self.row_mut(y).and_then(|row| row.get_mut(x as usize)) else {
+            return false;
+        };
+
+        *pixel = color.into();
+        true
+    }
+
+    /// Get a mutable row of pixels, if it's inside of the view.
+    pub fn row_mut(&mut self, y: u32) -> Option<&mut [Rgba]> {
+        // SAFETY: The row is inside of the view, which is borrowed mutably.
+        (y < self.region.height).then(|| unsafe { &mut *self.pixels.row(y, self.region.width) })
+    }
+
+    /// Convert the view into a mutable row of pixels, if it's inside of the view.
+    pub fn into_row_mut(self, y: u32) -> Option<&'a mut [Rgba]> {
+        // SAFETY: The row is inside of the view, which is consumed.
+        (y < self.region.height).then(|| unsafe { &mut *self.pixels.row(y, self.region.width) })
+    }
+
+    /// Iterate mutably over the rows of pixels from top to bottom.
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [Rgba]> {
+        let (pixels, width) = (self.pixels, self.region.width);
+
+        (0..self.region.height).map(move |y| {
+            // SAFETY: Every row is inside of the view, which is borrowed mutably, and yielded once.
+            unsafe { &mut *pixels.row(y, width) }
+        })
+    }
+
+    /// Iterate mutably over the pixels along with their `(x, y)` coordinates.
+    pub fn enumerate_pixels_mut(&mut self) -> impl Iterator<Item = (u32, u32, &mut Rgba)> {
+        self.rows_mut()
+            .zip(0..)
+            .flat_map(|(row, y)| row.iter_mut().zip(0..).map(move |(pixel, x)| (x, y, pixel)))
+    }
+
+    /// Fill the whole view with a single color.
+    pub fn fill(&mut self, color: impl Into<Rgba>) {
+        let color = color.into();
+        self.rows_mut().for_each(|row| row.fill(color));
+    }
+}
+
+impl Drop for BitmapViewMut<'_> {
+    fn drop(&mut self) {
+        self.damage.add(self.region);
+    }
+}
+
+/// The pixels of a [`Bitmap`] borrowed by one or more disjoint [`BitmapViewMut`]s.
+///
+/// Points to the top left pixel of a view, or of the whole [`Bitmap`] before it's split.
+#[derive(Debug, Clone, Copy)]
+struct Pixels<'a> {
+    pointer: NonNull<Rgba>,
+    stride: usize,
+    lifetime: PhantomData<&'a mut [Rgba]>,
+}
+
+// SAFETY: Views only access the pixels inside of their region, and the regions never overlap.
+unsafe impl Send for Pixels<'_> {}
+unsafe impl Sync for Pixels<'_> {}
+
+impl<'a> Pixels<'a> {
+    /// Create a view of a region, relative to these pixels.
+    ///
+    /// # Safety
+    ///
+    /// The region must fit inside of the pixels, and not overlap any other live view.
+    unsafe fn view(self, region: Rectangle<u32>, damage: &'a Damage) -> BitmapViewMut<'a> {
+        let offset = region.y as usize * self.stride + region.x as usize;
+
+        BitmapViewMut {
+            pixels: Pixels {
+                // SAFETY: The top left pixel of the region is inside of the pixels.
+                pointer: unsafe { self.pointer.add(offset) },
+                ..self
+            },
+            region,
+            damage,
+        }
+    }
+
+    /// Get a row of pixels, `width` pixels wide.
+    ///
+    /// # Safety
+    ///
+    /// The row must fit inside of the view the pixels belong to.
+    unsafe fn row(self, y: u32, width: u32) -> *mut [Rgba] {
+        // SAFETY: Upheld by the caller.
+        let start = unsafe { self.pointer.add(y as usize * self.stride) };
+
+        NonNull::slice_from_raw_parts(start, width as usize).as_ptr()
+    }
+}
+
+/// The disjoint tiles a [`Bitmap`] is split into, row by row.
+#[derive(Debug, Clone, Copy)]
+struct Tiles {
+    width: u32,
+    height: u32,
+    columns: u32,
+    rows: u32,
+    bounds: (u32, u32),
+}
+
+impl Tiles {
+    fn new(bitmap: &Bitmap, width: u32, height: u32) -> Self {
+        assert!(width > 0 && height > 0, "tiles must not be empty");
+
+        Self {
+            width,
+            height,
+            columns: bitmap.width().div_ceil(width),
+            rows: bitmap.height().div_ceil(height),
+            bounds: (bitmap.width(), bitmap.height()),
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.columns as usize * self.rows as usize
+    }
+
+    /// The region of a tile, cut to fit inside of the [`Bitmap`].
+    fn get(&self, index: usize) -> Rectangle<u32> {
+        let x = (index % self.columns as usize) as u32 * self.width;
+        let y = (index / self.columns as usize) as u32 * self.height;
+
+        Rectangle {
+            x,
+            y,
+            width: self.width.min(self.bounds.0 - x),
+            height: self.height.min(self.bounds.1 - y),
+        }
+    }
+}
+
+fn clip(bitmap: &Bitmap, rectangle: Rectangle<u32>) -> Option<Rectangle<u32>> {
+    let x = rectangle.x.min(bitmap.width());
+    let y = rectangle.y.min(bitmap.height());
+    let right = rectangle
+        .x
+        .saturating_add(rectangle.width)
+        .min(bitmap.width());
+    let bottom = rectangle
+        .y
+        .saturating_add(rectangle.height)
+        .min(bitmap.height());
+
+    (right > x && bottom > y).then(|| Rectangle {
+        x,
+        y,
+        width: right - x,
+        height: bottom - y,
+    })
+}
+
+fn offset(bitmap: &Bitmap, region: Rectangle<u32>) -> usize {
+    region.y as usize * bitmap.width() as usize + region.x as usize
+}
@@ -0,0 +1,276 @@
+//! A [`Surface`] over image data owned by someone else, like the framebuffer of an emulator core.
+//!
+//! An [`ExternalSurface`] is uploaded straight from the memory it wraps, without copying it into a
+//! [`Bitmap`](crate::Bitmap) first. Rows may be padded, as long as the `stride` between them is
+//! known.
+//!
+//! Since the memory is written to without the [`ExternalSurface`] knowing, it's only uploaded
+//! when the version of its [`FrameChanged`] signal changes. By default, this is a [`FrameSignal`]
+//! that is notified by hand, but any `Fn() -> u64`, like one reading the frame counter of an
+//! emulator core, can be used instead.
+//!
+//! ```no_run
+//! # use iced_texture_canvas::external::ExternalSurface;
+//! # use iced_texture_canvas::texture_canvas;
+//! # use std::sync::Arc;
+//! # #[derive(Clone)] enum Message {}
+//! # let (framebuffer, pitch): (*const u8, u32) = (std::ptr::null(), 1024);
+//! // The core owns its framebuffer and keeps it alive for as long as it's running.
+//! let surface = Arc::new(unsafe { ExternalSurface::from_raw_parts(framebuffer, 256, 240, pitch) });
+//! let signal = surface.signal().clone();
+//!
+//! // After the core has finished rendering a frame:
+//! signal.notify();
+//!
+//! // In the application:
+//! let canvas = texture_canvas::<Message, iced_core::Theme, _>(&surface);
+//! ```
+use crate::bitmap::Damage;
+use crate::widget::surface::{Surface, SurfaceId};
+
+use iced_core::Rectangle;
+
+use std::num::NonZeroU32;
+use std::ptr::NonNull;
+use std::sync::Arc;
+
+/// A [`Surface`] that displays `RGBA` image data it doesn't own.
+pub struct ExternalSurface<M = RawMemory, S = FrameSignal> {
+    memory: M,
+    width: NonZeroU32,
+    height: NonZeroU32,
+    stride: u32,
+    id: SurfaceId,
+    signal: S,
+}
+
+impl<M: AsRef<[u8]>> ExternalSurface<M> {
+    /// Wrap memory that holds `height` rows of `width` pixels, where each row starts `stride` bytes
+    /// after the previous one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either the `width` or `height` is zero, the `stride` is shorter than a row,
+    /// or the memory is too small to hold every row.
+    pub fn new(memory: M, width: u32, height: u32, stride: u32) -> Self {
+        let width = NonZeroU32::new(width).expect("width must be greater than 0");
+        let height = NonZeroU32::new(height).expect("height must be greater than 0");
+
+        assert!(
+            stride >= 4 * width.get(),
+            "stride must be at least 4 * width"
+        );
+        assert!(
+            memory.as_ref().len() >= required_len(width.get(), height.get(), stride),
+            "Size mismatch!"
+        );
+
+        Self {
+            memory,
+            width,
+            height,
+            stride,
+            id: SurfaceId::unique(),
+            signal: FrameSignal::new(),
+        }
+    }
+}
+
+impl ExternalSurface {
+    /// Wrap memory behind a raw pointer, like a framebuffer handed out over FFI.
+    ///
+    /// # Safety
+    ///
+    /// `data` must be valid for reads of `stride * (height - 1) + 4 * width` bytes for as long as
+    /// the [`ExternalSurface`] is alive, including any clone of the [`Arc`] it's displayed from.
+    ///
+    /// The memory must not be written to while it's being uploaded. Writing a frame and then
+    /// notifying the signal, without touching the memory until it's been drawn, is enough.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` is null, either the `width` or `height` is zero, or the `stride` is shorter
+    /// than a row.
+    pub unsafe fn from_raw_parts(data: *const u8, width: u32, height: u32, stride: u32) -> Self {
+        let ptr = NonNull::new(data.cast_mut()).expect("data must not be null");
+        let len = required_len(width, height, stride);
+
+        Self::new(RawMemory { ptr, len }, width, height, stride)
+    }
+}
+
+impl<M, S> ExternalSurface<M, S> {
+    /// Replace the signal that decides when the [`ExternalSurface`] is uploaded.
+    pub fn with_signal<T: FrameChanged>(self, signal: T) -> ExternalSurface<M, T> {
+        ExternalSurface {
+            memory: self.memory,
+            width: self.width,
+            height: self.height,
+            stride: self.stride,
+            id: SurfaceId::unique(),
+            signal,
+        }
+    }
+
+    /// Get the signal that decides when the [`ExternalSurface`] is uploaded.
+    pub fn signal(&self) -> &S {
+        &self.signal
+    }
+
+    /// Get the wrapped memory.
+    pub fn memory(&self) -> &M {
+        &self.memory
+    }
+
+    /// Get the number of bytes from the start of one row to the next.
+    pub fn stride(&self) -> u32 {
+        self.stride
+    }
+
+    /// Get the width of the [`ExternalSurface`]
+    pub fn width(&self) -> u32 {
+        self.width.get()
+    }
+
+    /// Get the height of the [`ExternalSurface`]
+    pub fn height(&self) -> u32 {
+        self.height.get()
+    }
+}
+
+impl<M, S> Surface for ExternalSurface<M, S>
+where
+    M: AsRef<[u8]> + Send + Sync + 'static,
+    S: FrameChanged,
+{
+    fn width(&self) -> u32 {
+        self.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.height()
+    }
+
+    fn data(&self) -> &[u8] {
+        &self.memory.as_ref()[..required_len(self.width(), self.height(), self.stride)]
+    }
+
+    fn stride(&self) -> u32 {
+        self.stride
+    }
+
+    fn id(&self) -> SurfaceId {
+        self.id
+    }
+
+    fn version(&self) -> u64 {
+        self.signal.version()
+    }
+
+    fn modified_since(&self, version: u64) -> Option<Rectangle<u32>> {
+        self.signal
+            .modified_since(version, self.width(), self.height())
+    }
+}
+
+impl<M, S> std::fmt::Debug for ExternalSurface<M, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("external surface")
+            .field("memory", &"...")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("stride", &self.stride)
+            .finish()
+    }
+}
+
+/// Decides when an [`ExternalSurface`] has a new frame to upload.
+pub trait FrameChanged: Send + Sync + 'static {
+    /// The version of the frame, which increases every time it changes.
+    fn version(&self) -> u64;
+
+    /// The region changed since the given version, clipped to the given size.
+    ///
+    /// By default, the whole frame is treated as changed.
+    fn modified_since(&self, version: u64, width: u32, height: u32) -> Option<Rectangle<u32>> {
+        (version != self.version()).then_some(Rectangle {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        })
+    }
+}
+
+/// The closure returns the version of the frame, like a frame counter.
+impl<F> FrameChanged for F
+where
+    F: Fn() -> u64 + Send + Sync + 'static,
+{
+    fn version(&self) -> u64 {
+        self()
+    }
+}
+
+/// A [`FrameChanged`] signal that is notified by hand, possibly from another thread.
+///
+/// Clones are notified together.
+#[derive(Debug, Clone, Default)]
+pub struct FrameSignal(Arc<Damage>);
+
+impl FrameSignal {
+    /// Create a new [`FrameSignal`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark the whole frame as changed.
+    pub fn notify(&self) {
+        self.0.add(Rectangle {
+            x: 0,
+            y: 0,
+            width: u32::MAX,
+            height: u32::MAX,
+        });
+    }
+
+    /// Mark a region of the frame as changed.
+    ///
+    /// Regions notified before the version is next read are merged into a single version.
+    pub fn notify_region(&self, region: Rectangle<u32>) {
+        self.0.add(region);
+    }
+}
+
+impl FrameChanged for FrameSignal {
+    fn version(&self) -> u64 {
+        self.0.version()
+    }
+
+    fn modified_since(&self, version: u64, width: u32, height: u32) -> Option<Rectangle<u32>> {
+        self.0.modified_since(version, width, height)
+    }
+}
+
+/// Memory behind a raw pointer, created with [`ExternalSurface::from_raw_parts`].
+#[derive(Debug)]
+pub struct RawMemory {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+// SAFETY: The memory is only ever read, and `from_raw_parts` requires it to stay valid.
+unsafe impl Send for RawMemory {}
+unsafe impl Sync for RawMemory {}
+
+impl AsRef<[u8]> for RawMemory {
+    fn as_ref(&self) -> &[u8] {
+        // SAFETY: Upheld by the caller of `from_raw_parts`.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+/// The number of bytes needed to hold every row, where the last row doesn't need any padding.
+fn required_len(width: u32, height: u32, stride: u32) -> usize {
+    stride as usize * (height as usize).saturating_sub(1) + 4 * width as usize
+}
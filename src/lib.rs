@@ -1,9 +1,17 @@
 pub mod bitmap;
+pub mod external;
+pub mod pyramid;
+#[cfg(all(feature = "shm", target_os = "linux"))]
+pub mod shm;
 pub mod widget;
 
-pub use bitmap::{Bitmap, bitmap};
-pub use widget::style::{self, Catalog, Status, Style, StyleFn};
-pub use widget::surface::{Surface, SurfaceHandler};
-pub use widget::{TextureCanvas, center_image, scale_image, texture_canvas};
+pub use bitmap::{Bitmap, Rgba, bitmap};
+pub use external::ExternalSurface;
+pub use pyramid::{Pyramid, TileSource};
+pub use widget::annotation::{self, Annotation, AnnotationChanged};
+pub use widget::guide::{self, Guide, GuideChanged, Snap};
+pub use widget::style::{self, Catalog, Checkerboard, Status, Style, StyleFn};
+pub use widget::surface::{Source, Surface, SurfaceHandler, SurfaceId};
+pub use widget::{TextureCanvas, TextureError, center_image, scale_image, texture_canvas};
 
 pub use iced_core::widget::Id;
@@ -0,0 +1,522 @@
+//! Images too large to hold in memory, loaded tile by tile from a resolution pyramid.
+//!
+//! A [`TileSource`] splits an image into square tiles at several levels of detail, where level
+//! `0` is the full resolution and each following level halves the width and height. A
+//! [`Pyramid`] displays it in a [`TextureCanvas`](crate::TextureCanvas), loading only the tiles
+//! visible at the current scale and offset on background threads.
+//!
+//! Recently used tiles are kept both in memory and on the GPU, up to a limit. While a tile is
+//! loading, the closest lower resolution tile that has already been loaded is drawn instead.
+//! [`Pyramid::subscription`] notifies the application whenever there's something new to draw.
+//!
+//! Deep Zoom images stored on disk can be read with a [`DeepZoom`](dzi::DeepZoom) source.
+//!
+//! ```no_run
+//! # fn main() -> Result<(), iced_texture_canvas::pyramid::Error> {
+//! # use iced_texture_canvas::pyramid::{Pyramid, dzi::DeepZoom};
+//! # use iced_texture_canvas::texture_canvas;
+//! # use iced_widget::runtime::futures::Subscription;
+//! # #[derive(Clone)] enum Message { Loaded }
+//! let pyramid = Pyramid::new(DeepZoom::open("slide.dzi")?);
+//!
+//! // In the application:
+//! let subscription: Subscription<Message> = pyramid.subscription().map(|_| Message::Loaded);
+//! let canvas = texture_canvas::<Message, iced_core::Theme, _>(&pyramid);
+//! # Ok(())
+//! # }
+//! ```
+pub mod dzi;
+
+use crate::bitmap::{Bitmap, load};
+use crate::widget::surface::SurfaceId;
+
+use iced_core::{Rectangle, Size};
+use iced_widget::runtime::futures::Subscription;
+use iced_widget::runtime::futures::futures::Stream;
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// How many decoded tiles are kept in memory by default.
+const MEMORY_TILES: usize = 256;
+
+/// The most threads loading tiles at once.
+const MAX_WORKERS: usize = 4;
+
+/// An image split into tiles at several levels of detail.
+///
+/// Tiles are loaded on background threads, so loading can block.
+pub trait TileSource: Send + Sync + 'static {
+    /// The width of the image at full resolution.
+    fn width(&self) -> u32;
+
+    /// The height of the image at full resolution.
+    fn height(&self) -> u32;
+
+    /// The width and height of every tile, except for the ones on the right and bottom edges,
+    /// which only cover what's left of the level.
+    fn tile_size(&self) -> u32;
+
+    /// Load the tile in the column `x` and row `y` of a level.
+    ///
+    /// Level `0` is the full resolution, and each following level halves the width and height,
+    /// rounding up.
+    fn tile(&self, level: u32, x: u32, y: u32) -> Result<Bitmap, Error>;
+
+    /// The number of levels.
+    ///
+    /// By default, levels continue until the whole image fits in a single tile.
+    fn levels(&self) -> u32 {
+        let mut levels = 1;
+        let mut size = self.width().max(self.height());
+
+        while size > self.tile_size() {
+            size = size.div_ceil(2);
+            levels += 1;
+        }
+
+        levels
+    }
+}
+
+/// An error that can occur when loading a tile.
+#[derive(Debug)]
+pub enum Error {
+    /// A file could not be read.
+    Io(io::Error),
+    /// A tile could not be decoded.
+    Decode(load::Error),
+    /// The description of the image is missing or has an invalid attribute.
+    InvalidDescriptor(&'static str),
+    /// The [`TileSource`] panicked while loading the tile.
+    Panicked,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "io error: {error}"),
+            Error::Decode(error) => error.fmt(f),
+            Error::InvalidDescriptor(attribute) => {
+                write!(f, "missing or invalid attribute: {attribute}")
+            }
+            Error::Panicked => write!(f, "the tile source panicked"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            Error::Decode(error) => Some(error),
+            Error::InvalidDescriptor(_) | Error::Panicked => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<load::Error> for Error {
+    fn from(error: load::Error) -> Self {
+        Error::Decode(error)
+    }
+}
+
+/// Identifies a tile of a [`TileSource`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TileId {
+    pub level: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+/// A [`TileSource`] that can be displayed in a [`TextureCanvas`](crate::TextureCanvas).
+///
+/// Clones share the same tiles, and the background threads stop once every clone is dropped.
+#[derive(Debug, Clone)]
+pub struct Pyramid(Arc<Inner>);
+
+struct Inner {
+    source: Arc<dyn TileSource>,
+    levels: u32,
+    id: SurfaceId,
+    loader: Arc<Loader>,
+}
+
+impl Pyramid {
+    /// Display the given [`TileSource`], keeping up to 256 decoded tiles in memory.
+    pub fn new(source: impl TileSource) -> Self {
+        Self::with_capacity(source, MEMORY_TILES)
+    }
+
+    /// Display the given [`TileSource`], keeping up to `capacity` decoded tiles in memory.
+    pub fn with_capacity(source: impl TileSource, capacity: usize) -> Self {
+        let source: Arc<dyn TileSource> = Arc::new(source);
+        let loader = Arc::new(Loader::new(capacity.max(1)));
+
+        let workers = std::thread::available_parallelism()
+            .map_or(1, |threads| threads.get())
+            .min(MAX_WORKERS);
+
+        for _ in 0..workers {
+            let source = Arc::clone(&source);
+            let loader = Arc::clone(&loader);
+
+            std::thread::spawn(move || loader.work(source.as_ref()));
+        }
+
+        Self(Arc::new(Inner {
+            levels: source.levels().max(1),
+            source,
+            id: SurfaceId::unique(),
+            loader,
+        }))
+    }
+
+    /// Get the [`TileSource`] of the [`Pyramid`].
+    pub fn source(&self) -> &dyn TileSource {
+        self.0.source.as_ref()
+    }
+
+    /// Get the width of the image at full resolution.
+    pub fn width(&self) -> u32 {
+        self.0.source.width()
+    }
+
+    /// Get the height of the image at full resolution.
+    pub fn height(&self) -> u32 {
+        self.0.source.height()
+    }
+
+    /// Get the number of levels of the [`TileSource`].
+    pub fn levels(&self) -> u32 {
+        self.0.levels
+    }
+
+    /// Whether any tiles that were asked for are still being loaded.
+    pub fn is_loading(&self) -> bool {
+        let state = self.0.loader.state.lock().unwrap();
+
+        !state.queue.is_empty() || !state.loading.is_empty()
+    }
+
+    /// Take the errors of the tiles that failed to load since this was last called.
+    ///
+    /// Tiles that failed to load aren't tried again.
+    pub fn take_errors(&self) -> Vec<(TileId, Error)> {
+        std::mem::take(&mut self.0.loader.state.lock().unwrap().errors)
+    }
+
+    /// A [`Subscription`] that produces a value every time there's something new to draw,
+    /// like a tile that finished loading, so the application can redraw.
+    ///
+    /// It ends once every clone of the [`Pyramid`] is dropped.
+    pub fn subscription(&self) -> Subscription<()> {
+        Subscription::run_with(Changes(Arc::clone(&self.0.loader)), |changes| Changed {
+            loader: Arc::clone(&changes.0),
+            seen: 0,
+        })
+    }
+
+    /// Identifies the tiles of the [`Pyramid`] on the GPU.
+    pub(crate) fn id(&self) -> SurfaceId {
+        self.0.id
+    }
+
+    /// The smallest scale, at which the lowest resolution level is displayed at its own size.
+    pub(crate) fn min_scale(&self) -> f32 {
+        1.0 / (1u64 << (self.levels() - 1).min(63)) as f32
+    }
+
+    /// The level with the lowest resolution that still has a texel for every screen pixel.
+    pub(crate) fn level(&self, scale: f32) -> u32 {
+        let level = (1.0 / scale).log2().floor().max(0.0) as u32;
+
+        level.min(self.levels() - 1)
+    }
+
+    /// The tiles of the `level` that overlap the `region` of the image at full resolution.
+    pub(crate) fn tiles(&self, level: u32, region: Rectangle<u32>) -> impl Iterator<Item = TileId> {
+        let span = (self.0.source.tile_size() as u64) << level;
+
+        let columns =
+            region.x as u64 / span..(region.x as u64 + region.width as u64).div_ceil(span);
+        let rows = region.y as u64 / span..(region.y as u64 + region.height as u64).div_ceil(span);
+
+        rows.flat_map(move |y| {
+            columns.clone().map(move |x| TileId {
+                level,
+                x: x as u32,
+                y: y as u32,
+            })
+        })
+    }
+
+    /// The tile of the next level down that covers the given tile, if any.
+    pub(crate) fn parent(&self, tile: TileId) -> Option<TileId> {
+        (tile.level + 1 < self.levels()).then_some(TileId {
+            level: tile.level + 1,
+            x: tile.x / 2,
+            y: tile.y / 2,
+        })
+    }
+
+    /// The region of the image at full resolution covered by a loaded tile of the given size.
+    pub(crate) fn placement(&self, tile: TileId, size: Size<u32>) -> Rectangle<u32> {
+        let span = self.0.source.tile_size() << tile.level;
+
+        let x = tile.x * span;
+        let y = tile.y * span;
+
+        Rectangle {
+            x,
+            y,
+            width: (size.width << tile.level).min(self.width().saturating_sub(x)),
+            height: (size.height << tile.level).min(self.height().saturating_sub(y)),
+        }
+    }
+
+    /// Ask for the tiles needed to display the `region` of the image at the given scale.
+    ///
+    /// Tiles asked for earlier that are no longer needed are skipped, unless they're already loading.
+    pub(crate) fn request(&self, region: Rectangle<u32>, scale: f32) {
+        let level = self.level(scale);
+
+        // The lowest resolution level is loaded first, to be drawn while the rest loads.
+        let coarsest = TileId {
+            level: self.levels() - 1,
+            x: 0,
+            y: 0,
+        };
+
+        let tiles = std::iter::once(coarsest).chain(self.tiles(level, region));
+
+        self.0.loader.request(tiles);
+    }
+
+    /// Get a loaded tile, marking it as recently used.
+    pub(crate) fn get(&self, tile: TileId) -> Option<Bitmap> {
+        self.0.loader.state.lock().unwrap().get(tile)
+    }
+
+    /// Notify the [`subscription`](Self::subscription) that there's something new to draw,
+    /// like loaded tiles that didn't fit in the last frame.
+    pub(crate) fn notify(&self) {
+        self.0.loader.notify();
+    }
+}
+
+impl std::fmt::Debug for Inner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("pyramid")
+            .field("width", &self.source.width())
+            .field("height", &self.source.height())
+            .field("tile_size", &self.source.tile_size())
+            .field("levels", &self.levels)
+            .finish()
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        self.loader.state.lock().unwrap().closed = true;
+        self.loader.ready.notify_all();
+        self.loader.wake();
+    }
+}
+
+/// The tiles shared between a [`Pyramid`] and the threads loading them.
+struct Loader {
+    state: Mutex<State>,
+    /// Notified when tiles are asked for, or the [`Pyramid`] is dropped.
+    ready: Condvar,
+    /// The number of times there was something new to draw.
+    changes: AtomicU64,
+    waker: Mutex<Option<Waker>>,
+}
+
+struct State {
+    /// The tiles waiting to be loaded, in order.
+    queue: VecDeque<TileId>,
+    loading: HashSet<TileId>,
+    /// The loaded tiles, along with when they were last used.
+    loaded: HashMap<TileId, (Bitmap, u64)>,
+    capacity: usize,
+    clock: u64,
+    failed: HashSet<TileId>,
+    errors: Vec<(TileId, Error)>,
+    closed: bool,
+}
+
+impl Loader {
+    fn new(capacity: usize) -> Self {
+        Self {
+            state: Mutex::new(State {
+                queue: VecDeque::new(),
+                loading: HashSet::new(),
+                loaded: HashMap::new(),
+                capacity,
+                clock: 0,
+                failed: HashSet::new(),
+                errors: Vec::new(),
+                closed: false,
+            }),
+            ready: Condvar::new(),
+            changes: AtomicU64::new(0),
+            waker: Mutex::new(None),
+        }
+    }
+
+    /// Replace the queue with the given tiles, skipping the ones that are loaded or loading.
+    fn request(&self, tiles: impl Iterator<Item = TileId>) {
+        let mut state = self.state.lock().unwrap();
+        state.queue.clear();
+
+        for tile in tiles {
+            if !state.loaded.contains_key(&tile)
+                && !state.loading.contains(&tile)
+                && !state.failed.contains(&tile)
+                && !state.queue.contains(&tile)
+            {
+                state.queue.push_back(tile);
+            }
+        }
+
+        if !state.queue.is_empty() {
+            self.ready.notify_all();
+        }
+    }
+
+    /// Load tiles from the queue until the [`Pyramid`] is dropped.
+    fn work(&self, source: &dyn TileSource) {
+        loop {
+            let tile = {
+                let mut state = self.state.lock().unwrap();
+
+                loop {
+                    if state.closed {
+                        return;
+                    }
+
+                    if let Some(tile) = state.queue.pop_front() {
+                        state.loading.insert(tile);
+                        break tile;
+                    }
+
+                    state = self.ready.wait(state).unwrap();
+                }
+            };
+
+            // A panicking source must not leave the tile loading forever.
+            let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                source.tile(tile.level, tile.x, tile.y)
+            }))
+            .unwrap_or(Err(Error::Panicked));
+
+            {
+                let mut state = self.state.lock().unwrap();
+                state.loading.remove(&tile);
+
+                match result {
+                    Ok(bitmap) => state.insert(tile, bitmap),
+                    Err(error) => {
+                        state.failed.insert(tile);
+                        state.errors.push((tile, error));
+                    }
+                }
+            }
+
+            self.notify();
+        }
+    }
+
+    fn notify(&self) {
+        self.changes.fetch_add(1, Ordering::Release);
+        self.wake();
+    }
+
+    fn wake(&self) {
+        if let Some(waker) = self.waker.lock().ok().and_then(|mut waker| waker.take()) {
+            waker.wake();
+        }
+    }
+}
+
+impl State {
+    fn get(&mut self, tile: TileId) -> Option<Bitmap> {
+        self.clock += 1;
+
+        let (bitmap, used) = self.loaded.get_mut(&tile)?;
+        *used = self.clock;
+
+        Some(bitmap.clone())
+    }
+
+    /// Insert a loaded tile, evicting the least recently used one if there's no room.
+    fn insert(&mut self, tile: TileId, bitmap: Bitmap) {
+        if self.loaded.len() >= self.capacity {
+            let oldest = self
+                .loaded
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(tile, _)| *tile);
+
+            if let Some(oldest) = oldest {
+                self.loaded.remove(&oldest);
+            }
+        }
+
+        self.clock += 1;
+        self.loaded.insert(tile, (bitmap, self.clock));
+    }
+}
+
+/// Identifies the [`Subscription`] of a [`Pyramid`].
+struct Changes(Arc<Loader>);
+
+impl Hash for Changes {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).hash(state);
+    }
+}
+
+/// A [`Stream`] of changes to draw.
+struct Changed {
+    loader: Arc<Loader>,
+    seen: u64,
+}
+
+impl Stream for Changed {
+    type Item = ();
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        // Register before checking, so a change in between isn't missed.
+        if let Ok(mut waker) = this.loader.waker.lock() {
+            *waker = Some(cx.waker().clone());
+        }
+
+        let changes = this.loader.changes.load(Ordering::Acquire);
+
+        if changes != this.seen {
+            this.seen = changes;
+            Poll::Ready(Some(()))
+        } else if this.loader.state.lock().is_ok_and(|state| state.closed) {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Deep Zoom images stored on disk.
+//!
+//! A Deep Zoom image is described by a `.dzi` file next to a directory with the same name
+//! followed by `_files`. The directory holds a directory per level, numbered from `0` for a
+//! single pixel up to the full resolution, each holding the tiles of the level named
+//! `<column>_<row>.<format>`.
+//!
+//! QOI and Netpbm tiles are always supported. Every other format requires the `image` feature.
+use super::{Error, TileSource};
+use crate::bitmap::Bitmap;
+
+use iced_core::Rectangle;
+
+use std::path::{Path, PathBuf};
+
+/// A [`TileSource`] reading a Deep Zoom image from disk.
+#[derive(Debug, Clone)]
+pub struct DeepZoom {
+    /// The directory holding the levels.
+    files: PathBuf,
+    width: u32,
+    height: u32,
+    tile_size: u32,
+    /// How many pixels each tile shares with its neighbours.
+    overlap: u32,
+    format: String,
+    /// The number of the level at full resolution.
+    max_level: u32,
+}
+
+impl DeepZoom {
+    /// Open the Deep Zoom image described by the `.dzi` file at the given path.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let descriptor = std::fs::read_to_string(path)?;
+
+        let mut files = path.with_extension("").into_os_string();
+        files.push("_files");
+
+        let parse = |name: &'static str| {
+            attribute(&descriptor, name)
+                .and_then(|value| value.parse::<u32>().ok())
+                .ok_or(Error::InvalidDescriptor(name))
+        };
+
+        let width = parse("Width")?;
+        let height = parse("Height")?;
+        let tile_size = parse("TileSize")?;
+        let overlap = parse("Overlap")?;
+
+        let format = attribute(&descriptor, "Format")
+            .filter(|format| !format.is_empty())
+            .ok_or(Error::InvalidDescriptor("Format"))?;
+
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidDescriptor("Size"));
+        }
+
+        if tile_size == 0 {
+            return Err(Error::InvalidDescriptor("TileSize"));
+        }
+
+        let largest = width.max(height);
+
+        Ok(Self {
+            files: files.into(),
+            width,
+            height,
+            tile_size,
+            overlap,
+            format: format.to_owned(),
+            max_level: u32::BITS - (largest - 1).leading_zeros(),
+        })
+    }
+
+    /// Get the number of pixels each tile shares with its neighbours.
+    pub fn overlap(&self) -> u32 {
+        self.overlap
+    }
+
+    /// Get the file extension of the tiles.
+    pub fn format(&self) -> &str {
+        &self.format
+    }
+}
+
+impl TileSource for DeepZoom {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn tile_size(&self) -> u32 {
+        self.tile_size
+    }
+
+    fn tile(&self, level: u32, x: u32, y: u32) -> Result<Bitmap, Error> {
+        let path = self
+            .files
+            .join((self.max_level.saturating_sub(level)).to_string())
+            .join(format!("{x}_{y}.{}", self.format));
+
+        let mut tile = decode(&path)?;
+
+        let level_width = self.width.div_ceil(1 << level.min(31));
+        let level_height = self.height.div_ceil(1 << level.min(31));
+
+        // Tiles include the pixels they share with the tiles before them.
+        let left = if x > 0 { self.overlap } else { 0 };
+        let top = if y > 0 { self.overlap } else { 0 };
+
+        let cropped = tile.crop(Rectangle {
+            x: left,
+            y: top,
+            width: level_width
+                .saturating_sub(x * self.tile_size)
+                .clamp(1, self.tile_size),
+            height: level_height
+                .saturating_sub(y * self.tile_size)
+                .clamp(1, self.tile_size),
+        });
+
+        if cropped.is_none() {
+            return Err(Error::InvalidDescriptor("Overlap"));
+        }
+
+        Ok(tile)
+    }
+}
+
+#[cfg(feature = "image")]
+fn decode(path: &Path) -> Result<Bitmap, Error> {
+    Bitmap::open(path).map_err(|error| Error::Decode(error.into()))
+}
+
+#[cfg(not(feature = "image"))]
+fn decode(path: &Path) -> Result<Bitmap, Error> {
+    let reader = std::io::BufReader::new(std::fs::File::open(path)?);
+
+    Bitmap::decode(reader).map_err(|error| Error::Decode(error.into()))
+}
+
+/// Find the value of the first attribute with the given name in an XML document.
+fn attribute<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = xml;
+
+    while let Some(start) = rest.find(name) {
+        let before = rest[..start].chars().next_back();
+        let after = rest[start + name.len()..].trim_start();
+        rest = &rest[start + name.len()..];
+
+        if !before.is_some_and(char::is_whitespace) {
+            continue;
+        }
+
+        let Some(value) = after.strip_prefix('=') else {
+            continue;
+        };
+
+        let value = value.trim_start();
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let value = &value[1..];
+
+        return value.find(quote).map(|end| &value[..end]);
+    }
+
+    None
+}
@@ -0,0 +1,619 @@
+//! Frames shared between processes through a Linux `memfd` or POSIX shared memory segment.
+//!
+//! A [`SharedWriter`] draws frames in one process, usually a renderer isolated from the
+//! application in case it crashes, and a [`SharedReader`] displays them in another with a
+//! [`TextureCanvas`](crate::TextureCanvas), without copying them through a pipe.
+//!
+//! The segment starts with a header holding the width, height, pixel [`Format`] and a sequence
+//! counter of published frames, followed by two frames. The writer draws into the back frame
+//! while the reader uploads the front one, and [`publish`](SharedWriter::publish)ing swaps
+//! them. The reader uploads the front frame whenever the sequence changes, and
+//! [`SharedReader::subscription`] polls the sequence so the application can redraw.
+//!
+//! Any number of readers can display the same segment, but there should only be one writer.
+//!
+//! ```no_run
+//! # fn main() -> Result<(), iced_texture_canvas::shm::Error> {
+//! # use iced_texture_canvas::shm::{SharedMemory, SharedReader, SharedWriter};
+//! # use iced_texture_canvas::{Rgba, texture_canvas};
+//! # use iced_widget::runtime::futures::Subscription;
+//! # use std::sync::Arc;
+//! # use std::time::Duration;
+//! # #[derive(Clone)] enum Message { Frame }
+//! // In the renderer process:
+//! let mut writer = SharedWriter::new(SharedMemory::create("/frames", 640, 480)?);
+//! writer.frame_mut().fill(Rgba::BLACK);
+//! writer.publish();
+//!
+//! // In the application process:
+//! let reader = Arc::new(SharedReader::new(SharedMemory::open("/frames")?));
+//! let subscription: Subscription<Message> =
+//!     reader.subscription(Duration::from_millis(8)).map(|_| Message::Frame);
+//! let canvas = texture_canvas::<Message, iced_core::Theme, _>(&reader);
+//! # Ok(())
+//! # }
+//! ```
+use crate::bitmap::Rgba;
+use crate::widget::surface::{Surface, SurfaceId};
+
+use iced_widget::runtime::futures::Subscription;
+use iced_widget::runtime::futures::futures::channel::mpsc;
+
+use std::ffi::CString;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+use std::ptr::NonNull;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Identifies a segment created by this crate.
+const MAGIC: u32 = u32::from_le_bytes(*b"ITCS");
+
+/// The offset of the first frame, leaving room for the header to grow.
+const FRAMES_OFFSET: usize = 64;
+
+/// The largest frame that can be shared, in pixels.
+const MAX_PIXELS: u64 = 400_000_000;
+
+/// How long the writer waits for readers to finish uploading a frame before it assumes they
+/// died in the middle of it.
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The format of the pixels in a [`SharedMemory`] segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u32)]
+pub enum Format {
+    /// 8 bits per channel `RGBA`, like a [`Bitmap`](crate::Bitmap).
+    #[default]
+    Rgba8 = 1,
+}
+
+impl Format {
+    fn from_u32(format: u32) -> Option<Self> {
+        match format {
+            1 => Some(Format::Rgba8),
+            _ => None,
+        }
+    }
+}
+
+/// An error that can occur when creating or opening a [`SharedMemory`] segment.
+#[derive(Debug)]
+pub enum Error {
+    /// The segment could not be created, opened or mapped.
+    Io(io::Error),
+    /// The segment wasn't created by a [`SharedMemory`], or is too small for its header.
+    InvalidHeader,
+    /// The segment holds pixels in an unknown [`Format`].
+    UnsupportedFormat,
+    /// The frames have more than 400 million pixels, or a width or height of zero.
+    InvalidSize,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "io error: {error}"),
+            Error::InvalidHeader => write!(f, "invalid shared memory header"),
+            Error::UnsupportedFormat => write!(f, "unsupported pixel format"),
+            Error::InvalidSize => write!(f, "invalid frame size"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+/// The header at the start of a [`SharedMemory`] segment.
+///
+/// Atomics in shared memory are only lock-free across processes, which is the case for 32 and
+/// 64 bit atomics on every platform Linux supports.
+#[repr(C)]
+struct Header {
+    magic: u32,
+    width: u32,
+    height: u32,
+    format: u32,
+    /// The number of frames published so far.
+    sequence: AtomicU64,
+    /// The index of the frame that was published last.
+    front: AtomicU32,
+    /// The number of readers uploading each frame.
+    reading: [AtomicU32; 2],
+}
+
+/// A mapped shared memory segment holding a header and two frames.
+///
+/// The size and [`Format`] of the frames are read once, when the segment is created or opened,
+/// so the other process can't change them afterwards.
+#[derive(Debug)]
+pub struct SharedMemory {
+    ptr: NonNull<u8>,
+    len: usize,
+    fd: OwnedFd,
+    width: u32,
+    height: u32,
+    format: Format,
+}
+
+// SAFETY: The header is only accessed through atomics, and the frames are only written through
+// a `SharedWriter`, which coordinates with the reader through the header.
+unsafe impl Send for SharedMemory {}
+unsafe impl Sync for SharedMemory {}
+
+impl SharedMemory {
+    /// Create an anonymous segment with `memfd_create`.
+    ///
+    /// It can be shared by passing its [file descriptor](AsFd) to the other process, either by
+    /// inheriting it or by sending it over a Unix socket, then [mapping](Self::from_fd) it there.
+    ///
+    /// The `name` is only used for debugging, and shows up in `/proc/self/fd`.
+    pub fn memfd(name: &str, width: u32, height: u32) -> Result<Self, Error> {
+        let name = c_string(name)?;
+
+        // SAFETY: `name` is a valid C string.
+        let fd = unsafe { libc::memfd_create(name.as_ptr(), libc::MFD_CLOEXEC) };
+
+        Self::init(owned(fd)?, width, height)
+    }
+
+    /// Create a named POSIX shared memory segment with `shm_open`, replacing any with the same name.
+    ///
+    /// The `name` should start with a `/`, like `/frames`. The segment can then be
+    /// [`open`](Self::open)ed by name in the other process, and should be [`unlink`](Self::unlink)ed
+    /// once it's no longer needed.
+    pub fn create(name: &str, width: u32, height: u32) -> Result<Self, Error> {
+        let name = c_string(name)?;
+
+        // SAFETY: `name` is a valid C string.
+        let fd = unsafe {
+            libc::shm_open(
+                name.as_ptr(),
+                libc::O_RDWR | libc::O_CREAT | libc::O_TRUNC | libc::O_CLOEXEC,
+                0o600,
+            )
+        };
+
+        Self::init(owned(fd)?, width, height)
+    }
+
+    /// Open a named POSIX shared memory segment created with [`create`](Self::create).
+    pub fn open(name: &str) -> Result<Self, Error> {
+        let name = c_string(name)?;
+
+        // SAFETY: `name` is a valid C string.
+        let fd = unsafe { libc::shm_open(name.as_ptr(), libc::O_RDWR | libc::O_CLOEXEC, 0) };
+
+        Self::from_fd(owned(fd)?)
+    }
+
+    /// Remove the name of a POSIX shared memory segment.
+    ///
+    /// Processes that have already opened it can keep using it.
+    pub fn unlink(name: &str) -> Result<(), Error> {
+        let name = c_string(name)?;
+
+        // SAFETY: `name` is a valid C string.
+        if unsafe { libc::shm_unlink(name.as_ptr()) } == -1 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        Ok(())
+    }
+
+    /// Map a segment created by another process, validating its header.
+    pub fn from_fd(fd: OwnedFd) -> Result<Self, Error> {
+        // SAFETY: An all zero `stat` is valid, and it's only read if `fstat` succeeds.
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+
+        // SAFETY: `fd` is a valid file descriptor and `stat` is writable.
+        if unsafe { libc::fstat(fd.as_raw_fd(), &mut stat) } == -1 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        let len = usize::try_from(stat.st_size).map_err(|_| Error::InvalidHeader)?;
+
+        if len < FRAMES_OFFSET {
+            return Err(Error::InvalidHeader);
+        }
+
+        let mut memory = Self::map(fd, len)?;
+
+        let (magic, width, height, format) = {
+            let header = memory.header();
+            (header.magic, header.width, header.height, header.format)
+        };
+
+        if magic != MAGIC {
+            return Err(Error::InvalidHeader);
+        }
+
+        let format = Format::from_u32(format).ok_or(Error::UnsupportedFormat)?;
+
+        match segment_len(width, height) {
+            Some(required) if required <= len => {
+                memory.width = width;
+                memory.height = height;
+                memory.format = format;
+
+                Ok(memory)
+            }
+            Some(_) => Err(Error::InvalidHeader),
+            None => Err(Error::InvalidSize),
+        }
+    }
+
+    /// Size a new segment and write its header.
+    fn init(fd: OwnedFd, width: u32, height: u32) -> Result<Self, Error> {
+        let len = segment_len(width, height).ok_or(Error::InvalidSize)?;
+
+        // SAFETY: `fd` is a valid file descriptor.
+        if unsafe { libc::ftruncate(fd.as_raw_fd(), len as libc::off_t) } == -1 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        let mut memory = Self::map(fd, len)?;
+        memory.width = width;
+        memory.height = height;
+
+        // SAFETY: The mapping is at least as large as the header, page aligned,
+        // and nobody else can have seen the header yet.
+        unsafe {
+            memory.ptr.cast::<Header>().as_ptr().write(Header {
+                magic: MAGIC,
+                width,
+                height,
+                format: Format::Rgba8 as u32,
+                sequence: AtomicU64::new(0),
+                front: AtomicU32::new(0),
+                reading: [AtomicU32::new(0), AtomicU32::new(0)],
+            });
+        }
+
+        Ok(memory)
+    }
+
+    /// Map the segment, leaving the frames empty until its header is written or validated.
+    fn map(fd: OwnedFd, len: usize) -> Result<Self, Error> {
+        // SAFETY: Mapping a valid file descriptor at an address chosen by the kernel.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fd.as_raw_fd(),
+                0,
+            )
+        };
+
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        Ok(Self {
+            ptr: NonNull::new(ptr.cast()).ok_or(Error::InvalidHeader)?,
+            len,
+            fd,
+            width: 0,
+            height: 0,
+            format: Format::default(),
+        })
+    }
+
+    /// Get the width of the frames.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Get the height of the frames.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Get the [`Format`] of the frames.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Get the number of frames published so far.
+    pub fn sequence(&self) -> u64 {
+        self.header().sequence.load(Ordering::Acquire)
+    }
+
+    fn header(&self) -> &Header {
+        // SAFETY: The header was written by `init`, or validated by `from_fd`.
+        unsafe { self.ptr.cast::<Header>().as_ref() }
+    }
+
+    /// Get the index of the front frame.
+    ///
+    /// Only its lowest bit is used, in case the other process stored something else.
+    fn front(&self) -> u32 {
+        self.header().front.load(Ordering::SeqCst) & 1
+    }
+
+    fn frame_len(&self) -> usize {
+        self.width as usize * self.height as usize * 4
+    }
+
+    /// Get a pointer to the start of the frame with the given index, either 0 or 1.
+    fn frame_ptr(&self, index: u32) -> *mut u8 {
+        let offset = FRAMES_OFFSET + (index & 1) as usize * self.frame_len();
+
+        // SAFETY: Both frames are inside of the mapping, which was checked to be large enough.
+        unsafe { self.ptr.as_ptr().add(offset) }
+    }
+
+    fn frame(&self, index: u32) -> &[u8] {
+        // SAFETY: The frame is inside of the mapping, and is only written to by a `SharedWriter`
+        // while the reader isn't uploading it.
+        unsafe { std::slice::from_raw_parts(self.frame_ptr(index), self.frame_len()) }
+    }
+}
+
+impl AsFd for SharedMemory {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl Drop for SharedMemory {
+    fn drop(&mut self) {
+        // SAFETY: The mapping was created by `map`, and every borrow of it has ended.
+        unsafe {
+            libc::munmap(self.ptr.as_ptr().cast(), self.len);
+        }
+    }
+}
+
+/// The writing half of a [`SharedMemory`] segment, usually in another process.
+///
+/// There should only ever be one writer per segment.
+#[derive(Debug)]
+pub struct SharedWriter {
+    memory: SharedMemory,
+}
+
+impl SharedWriter {
+    /// Write frames into the [`SharedMemory`] segment.
+    pub fn new(memory: SharedMemory) -> Self {
+        Self { memory }
+    }
+
+    /// Get the [`SharedMemory`] segment.
+    pub fn memory(&self) -> &SharedMemory {
+        &self.memory
+    }
+
+    /// Get the back frame to draw the next frame into, row by row.
+    ///
+    /// It holds whatever was drawn two frames ago.
+    ///
+    /// If a reader is still uploading the back frame, which can only happen right after
+    /// [`publish`](Self::publish)ing twice in a row, this waits until it's done. A reader that
+    /// takes longer than a second is assumed to have died, and the frame is taken back from it.
+    pub fn frame_mut(&mut self) -> &mut [Rgba] {
+        let header = self.memory.header();
+        let back = self.memory.front() ^ 1;
+        let reading = &header.reading[back as usize];
+        let start = Instant::now();
+
+        while reading.load(Ordering::SeqCst) > 0 {
+            if start.elapsed() > READ_TIMEOUT {
+                reading.store(0, Ordering::SeqCst);
+                break;
+            }
+
+            std::thread::yield_now();
+        }
+
+        // SAFETY: The back frame is inside of the mapping, and the reader won't start uploading
+        // it until it's published. The mapping is page aligned, so it's aligned for `Rgba`.
+        unsafe {
+            std::slice::from_raw_parts_mut(
+                self.memory.frame_ptr(back).cast::<Rgba>(),
+                self.memory.frame_len() / 4,
+            )
+        }
+    }
+
+    /// Publish the back frame, making it the front frame that the reader uploads.
+    pub fn publish(&mut self) {
+        let header = self.memory.header();
+        let back = self.memory.front() ^ 1;
+
+        header.front.store(back, Ordering::SeqCst);
+        header.sequence.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+/// The reading half of a [`SharedMemory`] segment, displayed by a
+/// [`TextureCanvas`](crate::TextureCanvas) from behind an [`Arc`].
+#[derive(Debug)]
+pub struct SharedReader {
+    memory: Arc<SharedMemory>,
+    id: SurfaceId,
+}
+
+impl SharedReader {
+    /// Read frames from the [`SharedMemory`] segment.
+    pub fn new(memory: SharedMemory) -> Self {
+        Self {
+            memory: Arc::new(memory),
+            id: SurfaceId::unique(),
+        }
+    }
+
+    /// Get the [`SharedMemory`] segment.
+    pub fn memory(&self) -> &SharedMemory {
+        &self.memory
+    }
+
+    /// A [`Subscription`] that produces a value every time a new frame is published,
+    /// so the application can redraw.
+    ///
+    /// The sequence is polled on a background thread at the given interval, since the writer
+    /// lives in another process.
+    pub fn subscription(&self, interval: Duration) -> Subscription<()> {
+        Subscription::run_with(
+            Polling {
+                memory: Arc::clone(&self.memory),
+                interval,
+            },
+            Polling::spawn,
+        )
+    }
+
+    /// Mark the front frame as being uploaded until the returned guard is dropped,
+    /// so the writer doesn't draw into it in the meantime.
+    fn lock_front(&self) -> Reading<'_> {
+        let header = self.memory.header();
+
+        loop {
+            let front = self.memory.front();
+            let reading = &header.reading[front as usize];
+            reading.fetch_add(1, Ordering::SeqCst);
+
+            // The writer may have published in between, in which case it could be drawing
+            // into the frame that was loaded.
+            if self.memory.front() == front {
+                return Reading {
+                    reading,
+                    frame: self.memory.frame(front),
+                };
+            }
+
+            release(reading);
+        }
+    }
+}
+
+impl Surface for SharedReader {
+    fn width(&self) -> u32 {
+        self.memory.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.memory.height()
+    }
+
+    /// Always empty.
+    ///
+    /// The writer may draw into a frame as soon as nobody is uploading it, so the front frame is
+    /// only handed out by [`with_data`](Surface::with_data), while it's kept from the writer.
+    fn data(&self) -> &[u8] {
+        &[]
+    }
+
+    fn id(&self) -> SurfaceId {
+        self.id
+    }
+
+    /// The number of frames published so far.
+    fn version(&self) -> u64 {
+        self.memory.sequence()
+    }
+
+    fn with_data(&self, upload: impl FnOnce(&[u8])) {
+        let reading = self.lock_front();
+        upload(reading.frame);
+    }
+}
+
+/// The front frame while it's being uploaded.
+struct Reading<'a> {
+    reading: &'a AtomicU32,
+    frame: &'a [u8],
+}
+
+impl Drop for Reading<'_> {
+    fn drop(&mut self) {
+        release(self.reading);
+    }
+}
+
+/// Stop reading a frame, unless the writer already took it back after timing out.
+fn release(reading: &AtomicU32) {
+    let _ = reading.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+        count.checked_sub(1)
+    });
+}
+
+/// Identifies the [`Subscription`] of a [`SharedReader`], and polls its sequence.
+struct Polling {
+    memory: Arc<SharedMemory>,
+    interval: Duration,
+}
+
+impl Polling {
+    fn spawn(&self) -> mpsc::Receiver<()> {
+        let (mut sender, receiver) = mpsc::channel(1);
+        let memory = Arc::clone(&self.memory);
+        let interval = self.interval;
+        let mut seen = memory.sequence();
+
+        // If the thread can't be started, the sender is dropped and the subscription ends.
+        let _ = std::thread::Builder::new()
+            .name(String::from("shared memory poller"))
+            .spawn(move || {
+                while !sender.is_closed() {
+                    std::thread::sleep(interval);
+
+                    let sequence = memory.sequence();
+
+                    if sequence != seen {
+                        seen = sequence;
+
+                        // A full channel already has a redraw queued.
+                        let _ = sender.try_send(());
+                    }
+                }
+            });
+
+        receiver
+    }
+}
+
+impl Hash for Polling {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.memory).hash(state);
+        self.interval.hash(state);
+    }
+}
+
+/// The length of a segment with two frames of the given size.
+fn segment_len(width: u32, height: u32) -> Option<usize> {
+    let pixels = width as u64 * height as u64;
+
+    (width > 0 && height > 0 && pixels <= MAX_PIXELS)
+        .then(|| FRAMES_OFFSET + 2 * pixels as usize * 4)
+}
+
+fn c_string(name: &str) -> Result<CString, Error> {
+    CString::new(name)
+        .map_err(|error| Error::Io(io::Error::new(io::ErrorKind::InvalidInput, error)))
+}
+
+fn owned(fd: libc::c_int) -> Result<OwnedFd, Error> {
+    if fd == -1 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    // SAFETY: The file descriptor was just opened, and nothing else owns it.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
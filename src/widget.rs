@@ -1,15 +1,26 @@
+pub mod annotation;
+pub mod guide;
+mod mesh;
 pub mod operation;
 mod primitive;
 pub mod style;
 pub mod surface;
 
-use primitive::Primitive;
+use crate::bitmap::selection::Mask;
+use crate::pyramid::Pyramid;
+use annotation::{Annotation, AnnotationChanged, Handle, Shape};
+use guide::{Guide, GuideChanged, Orientation};
+use mesh::Mesh;
+pub use primitive::TextureError;
+use primitive::{Canvas, Content, Grid, Primitive};
 use style::{Catalog, Status, Style, StyleFn};
-use surface::{Surface, SurfaceHandler};
+use surface::sealed::Displayed;
+use surface::{Source, Surface};
 
 use iced_core::{
-    Border, Element, Event, Layout, Length, Point, Rectangle, Shadow, Shell, Size, Widget, layout,
-    mouse, renderer,
+    Border, Color, Element, Event, Layout, Length, Point, Rectangle, Shadow, Shell, Size, Vector,
+    Widget, alignment, layout, mouse, renderer, text,
+    time::{Duration, Instant},
     widget::{self, Id},
     window,
 };
@@ -21,7 +32,29 @@ use iced_widget::runtime::{
 const MIN_SCALE: f32 = 1.0; //0.05; // TODO
 const MAX_SCALE: f32 = 64.0; // 6,400%
 
-/// Create a new [`TextureCanvas`] with the given [`SurfaceHandler`].
+/// The size of annotation handles in pixels.
+const HANDLE_SIZE: f32 = 8.0;
+/// The stroke width of annotations in pixels.
+const ANNOTATION_WIDTH: f32 = 1.5;
+/// The length of each dash of the selection outline in pixels.
+const ANTS_DASH: f32 = 4.0;
+/// How often the selection outline moves by one pixel.
+const ANTS_INTERVAL: Duration = Duration::from_millis(80);
+/// The thickness of the rulers in pixels.
+const RULER_SIZE: f32 = 20.0;
+/// The text size of the ruler labels.
+const RULER_TEXT_SIZE: f32 = 9.0;
+/// The minimum distance between labelled ruler ticks in pixels.
+const RULER_LABEL_SPACING: f32 = 50.0;
+/// How close the cursor needs to be to grab a guide in pixels.
+const GUIDE_TOLERANCE: f32 = 4.0;
+/// The maximum width of the loading progress bar in pixels.
+const PROGRESS_WIDTH: f32 = 200.0;
+/// The height of the loading progress bar in pixels.
+const PROGRESS_HEIGHT: f32 = 4.0;
+
+/// Create a new [`TextureCanvas`] with the given [`SurfaceHandler`](surface::SurfaceHandler)
+/// or [`Pyramid`].
 ///
 /// You can use the provided [`Bitmap`](crate::Bitmap).
 pub fn texture_canvas<'a, Message, Theme, Handler>(
@@ -30,7 +63,7 @@ pub fn texture_canvas<'a, Message, Theme, Handler>(
 where
     Message: 'a,
     Theme: Catalog,
-    Handler: SurfaceHandler,
+    Handler: Source,
 {
     TextureCanvas::new(buffer)
 }
@@ -53,7 +86,7 @@ pub struct TextureCanvas<'a, Message, Theme, Handler>
 where
     Theme: Catalog,
 {
-    buffer: &'a Handler,
+    source: &'a Handler,
     width: Length,
     height: Length,
 
@@ -69,20 +102,41 @@ where
     on_enter: Option<Box<dyn Fn() -> Message + 'a>>,
     on_exit: Option<Box<dyn Fn() -> Message + 'a>>,
 
+    annotations: &'a [Annotation],
+    on_annotation_change: Option<Box<dyn Fn(AnnotationChanged) -> Message + 'a>>,
+    on_annotation_select: Option<Box<dyn Fn(Option<annotation::Id>) -> Message + 'a>>,
+
+    selection: Option<&'a Mask>,
+
+    rulers: bool,
+    guides: &'a [Guide],
+    on_guide_change: Option<Box<dyn Fn(GuideChanged) -> Message + 'a>>,
+
+    pixel_grid: Option<f32>,
+    tile_grid: Option<Size<u32>>,
+
+    loading: Option<f32>,
+
+    tiling: bool,
+    on_texture_error: Option<Box<dyn Fn(TextureError) -> Message + 'a>>,
+
     interaction: Option<mouse::Interaction>,
 }
 
 impl<'a, Message, Theme, Handler> TextureCanvas<'a, Message, Theme, Handler>
 where
     Theme: style::Catalog,
-    Handler: SurfaceHandler,
+    Handler: Source,
 {
-    /// Create a new [`TextureCanvas`] with the given [`SurfaceHandler`].
+    /// Create a new [`TextureCanvas`] with the given [`SurfaceHandler`](surface::SurfaceHandler)
+    /// or [`Pyramid`].
     ///
-    /// You can use the provided [`Bitmap`](crate::Bitmap).
+    /// You can use the provided [`Bitmap`](crate::Bitmap). A [`Pyramid`] only loads the tiles
+    /// visible at the current scale and offset, and can be zoomed out until its lowest resolution
+    /// level is shown at its own size.
     pub fn new(buffer: &'a Handler) -> Self {
         Self {
-            buffer,
+            source: buffer,
             width: Length::Fill,
             height: Length::Fill,
             on_grab: None,
@@ -92,6 +146,18 @@ where
             on_release: None,
             on_enter: None,
             on_exit: None,
+            annotations: &[],
+            on_annotation_change: None,
+            on_annotation_select: None,
+            selection: None,
+            rulers: false,
+            guides: &[],
+            on_guide_change: None,
+            pixel_grid: None,
+            tile_grid: None,
+            loading: None,
+            tiling: true,
+            on_texture_error: None,
             interaction: None,
             class: Theme::default(),
             id: None,
@@ -237,14 +303,457 @@ where
         self.default_zoom = default_zoom;
         self
     }
+
+    /// Set the [`Annotation`]s displayed on top of the image.
+    pub fn annotations(mut self, annotations: &'a [Annotation]) -> Self {
+        self.annotations = annotations;
+        self
+    }
+
+    /// Set the message to emit when an [`Annotation`] is edited.
+    ///
+    /// Annotations can only be selected and edited if this is set.
+    ///
+    /// Drag the handles of the selected [`Annotation`] with the left mouse button
+    /// to move or resize it. Dragging the middle of an edge of a polygon or polyline
+    /// inserts a new vertex, and right clicking a vertex removes it.
+    pub fn on_annotation_change(
+        mut self,
+        on_annotation_change: impl Fn(AnnotationChanged) -> Message + 'a,
+    ) -> Self {
+        self.on_annotation_change = Some(Box::new(on_annotation_change));
+        self
+    }
+
+    /// Set the message to emit when an [`Annotation`] is selected or deselected.
+    pub fn on_annotation_select(
+        mut self,
+        on_annotation_select: impl Fn(Option<annotation::Id>) -> Message + 'a,
+    ) -> Self {
+        self.on_annotation_select = Some(Box::new(on_annotation_select));
+        self
+    }
+
+    /// Set the selection [`Mask`] to outline with marching ants.
+    pub fn selection(mut self, selection: impl Into<Option<&'a Mask>>) -> Self {
+        self.selection = selection.into();
+        self
+    }
+
+    /// Show a grid between the pixels of the image once it's scaled past the `threshold`.
+    ///
+    /// The grid fades in as the scale increases, using the `grid` color of the [`Style`].
+    pub fn pixel_grid(mut self, threshold: f32) -> Self {
+        self.pixel_grid = Some(threshold);
+        self
+    }
+
+    /// Show a coarser grid every `width` by `height` pixels, useful for tile and sprite work.
+    ///
+    /// The grid uses the `tile_grid` color of the [`Style`].
+    pub fn tile_grid(mut self, width: u32, height: u32) -> Self {
+        self.tile_grid = (width > 0 && height > 0).then_some(Size::new(width, height));
+        self
+    }
+
+    /// Show pixel rulers along the top and left edges of the [`TextureCanvas`].
+    pub fn rulers(mut self, rulers: bool) -> Self {
+        self.rulers = rulers;
+        self
+    }
+
+    /// Set the [`Guide`]s displayed across the image.
+    pub fn guides(mut self, guides: &'a [Guide]) -> Self {
+        self.guides = guides;
+        self
+    }
+
+    /// Set the message to emit when the [`Guide`]s are edited.
+    ///
+    /// Guides can only be edited if this is set. New guides are pulled out of the
+    /// [`rulers`](Self::rulers), and removed by dragging them back onto their ruler.
+    pub fn on_guide_change(
+        mut self,
+        on_guide_change: impl Fn(GuideChanged) -> Message + 'a,
+    ) -> Self {
+        self.on_guide_change = Some(Box::new(on_guide_change));
+        self
+    }
+
+    /// Show a loading placeholder with a progress bar over the canvas.
+    ///
+    /// The `progress` is between `0.0` and `1.0`. While loading, the canvas can display
+    /// a low resolution [`Preview`](crate::bitmap::load::Loading::Preview) or an empty [`Bitmap`](crate::Bitmap).
+    pub fn loading(mut self, progress: f32) -> Self {
+        self.loading = Some(progress.clamp(0.0, 1.0));
+        self
+    }
+
+    /// Split surfaces larger than the device supports in a single texture into tiles.
+    ///
+    /// This is enabled by default. If disabled, such surfaces aren't drawn and
+    /// [`on_texture_error`](Self::on_texture_error) is notified instead.
+    pub fn tiling(mut self, tiling: bool) -> Self {
+        self.tiling = tiling;
+        self
+    }
+
+    /// Set the message to emit when the surface can't be drawn.
+    ///
+    /// Each error is only reported once, the next time the [`TextureCanvas`] handles an event.
+    pub fn on_texture_error(
+        mut self,
+        on_texture_error: impl Fn(TextureError) -> Message + 'a,
+    ) -> Self {
+        self.on_texture_error = Some(Box::new(on_texture_error));
+        self
+    }
+
+    /// The smallest scale the image can be zoomed out to.
+    ///
+    /// A [`Pyramid`](crate::Pyramid) can be zoomed out until its lowest resolution level is shown.
+    fn min_scale(&self) -> f32 {
+        self.source
+            .displayed()
+            .pyramid()
+            .map_or(MIN_SCALE, |pyramid| pyramid.min_scale().min(MIN_SCALE))
+    }
+
+    /// Find the ruler under the `point`, relative to the bounds of the canvas.
+    ///
+    /// Returns the [`Orientation`] of the guides pulled out of it.
+    fn ruler_at(&self, point: Point) -> Option<Orientation> {
+        if !self.rulers {
+            return None;
+        }
+
+        match (point.x < RULER_SIZE, point.y < RULER_SIZE) {
+            (false, true) => Some(Orientation::Horizontal),
+            (true, false) => Some(Orientation::Vertical),
+            _ => None,
+        }
+    }
+
+    /// Find the [`Guide`] closest to the `point` in image coordinates.
+    fn guide_at(&self, point: Point, scale: f32) -> Option<(usize, Guide)> {
+        self.guides
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, guide)| guide.distance(point) <= GUIDE_TOLERANCE / scale)
+            .min_by(|(_, a), (_, b)| a.distance(point).total_cmp(&b.distance(point)))
+    }
+
+    /// Start dragging a [`Guide`], or pulling a new one out of a ruler.
+    ///
+    /// Returns `true` if the press was consumed by a [`Guide`].
+    fn press_guide(&self, state: &mut State, bounds: Rectangle, mouse: Point) -> bool {
+        if self.on_guide_change.is_none() {
+            return false;
+        }
+
+        let point = to_canvas_coords(bounds, mouse, state.canvas_offset, state.scale);
+
+        if let Some(orientation) = self.ruler_at(local_coords(bounds, mouse)) {
+            state.guide_drag = Some(GuideDrag {
+                index: None,
+                guide: guide_through(orientation, point),
+            });
+
+            return true;
+        }
+
+        if let Some((index, guide)) = self.guide_at(point, state.scale) {
+            state.guide_drag = Some(GuideDrag {
+                index: Some(index),
+                guide,
+            });
+
+            return true;
+        }
+
+        false
+    }
+
+    /// Tessellate the guides across the canvas bounds.
+    fn draw_guides(&self, mesh: &mut Mesh, state: &State, screen: Size, color: Color) {
+        // A new guide isn't part of `self.guides` until it's released.
+        let preview = state
+            .guide_drag
+            .as_ref()
+            .filter(|drag| drag.index.is_none())
+            .map(|drag| drag.guide);
+
+        for guide in self.guides.iter().copied().chain(preview) {
+            let bounds = match guide.orientation {
+                Orientation::Horizontal => Rectangle {
+                    x: 0.0,
+                    y: guide.position * state.scale + state.canvas_offset.y - 0.5,
+                    width: screen.width,
+                    height: 1.0,
+                },
+                Orientation::Vertical => Rectangle {
+                    x: guide.position * state.scale + state.canvas_offset.x - 0.5,
+                    y: 0.0,
+                    width: 1.0,
+                    height: screen.height,
+                },
+            };
+
+            mesh.fill_rectangle(bounds, color);
+        }
+    }
+
+    /// Find the [`Annotation`] and its [`Handle`] under the `point`.
+    ///
+    /// Only the selected [`Annotation`] exposes its vertex and edge handles.
+    fn annotation_at(
+        &self,
+        point: Point,
+        tolerance: f32,
+        selected: Option<annotation::Id>,
+    ) -> Option<(&'a Annotation, Handle)> {
+        let annotations = self.annotations;
+
+        annotations
+            .iter()
+            .find(|annotation| Some(annotation.id) == selected)
+            .and_then(|annotation| {
+                let handle = annotation.shape.handle_at(point, tolerance)?;
+                Some((annotation, handle))
+            })
+            .or_else(|| {
+                annotations
+                    .iter()
+                    .rev()
+                    .find(|annotation| annotation.shape.contains(point, tolerance))
+                    .map(|annotation| (annotation, Handle::Body))
+            })
+    }
+
+    fn select_annotation(
+        &self,
+        state: &mut State,
+        id: Option<annotation::Id>,
+        shell: &mut Shell<'_, Message>,
+    ) {
+        if state.selected_annotation != id {
+            state.selected_annotation = id;
+            shell.request_redraw();
+
+            if let Some(on_annotation_select) = &self.on_annotation_select {
+                shell.publish(on_annotation_select(id));
+            }
+        }
+    }
+
+    /// Start editing the [`Annotation`] under the `point`.
+    ///
+    /// Returns `true` if the press was consumed by an [`Annotation`].
+    fn press_annotation(
+        &self,
+        state: &mut State,
+        point: Point,
+        button: mouse::Button,
+        shell: &mut Shell<'_, Message>,
+    ) -> bool {
+        let Some(on_annotation_change) = &self.on_annotation_change else {
+            return false;
+        };
+
+        let tolerance = HANDLE_SIZE / state.scale;
+
+        let Some((annotation, handle)) =
+            self.annotation_at(point, tolerance, state.selected_annotation)
+        else {
+            if button == mouse::Button::Left {
+                self.select_annotation(state, None, shell);
+            }
+            return false;
+        };
+
+        let id = annotation.id;
+
+        match (button, handle) {
+            (mouse::Button::Left, Handle::Edge(edge))
+                if matches!(annotation.shape, Shape::Polygon(_) | Shape::Polyline(_)) =>
+            {
+                let mut shape = annotation.shape.clone();
+
+                if let Some(vertex) = shape.insert_vertex(edge, point) {
+                    shell.publish(on_annotation_change(AnnotationChanged {
+                        id,
+                        shape: shape.clone(),
+                    }));
+
+                    state.annotation_drag = Some(AnnotationDrag {
+                        id,
+                        handle: Handle::Vertex(vertex),
+                        origin: point,
+                        shape,
+                    });
+                }
+            }
+            (mouse::Button::Left, handle) => {
+                state.annotation_drag = Some(AnnotationDrag {
+                    id,
+                    handle,
+                    origin: point,
+                    shape: annotation.shape.clone(),
+                });
+            }
+            (mouse::Button::Right, Handle::Vertex(vertex)) => {
+                let mut shape = annotation.shape.clone();
+
+                if shape.remove_vertex(vertex) {
+                    shell.publish(on_annotation_change(AnnotationChanged { id, shape }));
+                }
+
+                return true;
+            }
+            _ => return false,
+        }
+
+        self.select_annotation(state, Some(id), shell);
+        true
+    }
+
+    /// Tessellate the animated outline of the selection.
+    fn draw_selection(&self, mesh: &mut Mesh, state: &State, screen: Size) {
+        let Some(mask) = self.selection else {
+            return;
+        };
+
+        let to_screen = |point: Point<u32>| {
+            to_screen_coords(
+                Point::new(point.x as f32, point.y as f32),
+                state.canvas_offset,
+                state.scale,
+            )
+        };
+
+        for [start, end] in mask.outline() {
+            let (start, end) = (to_screen(*start), to_screen(*end));
+            let horizontal = start.y == end.y;
+
+            // The position along the segment and the fixed coordinate across it.
+            let (from, to, across) = match horizontal {
+                true => (start.x.max(0.0), end.x.min(screen.width), start.y),
+                false => (start.y.max(0.0), end.y.min(screen.height), start.x),
+            };
+
+            let limit = match horizontal {
+                true => screen.height,
+                false => screen.width,
+            };
+
+            if from >= to || across < -1.0 || across > limit + 1.0 {
+                continue;
+            }
+
+            // Dashes run along the diagonals so that they line up at corners.
+            let mut position = from;
+
+            while position < to {
+                let dash = ((position + across + state.ants_offset) / ANTS_DASH).floor();
+                let dash_end = ((dash + 1.0) * ANTS_DASH - across - state.ants_offset).min(to);
+
+                if dash_end <= position {
+                    break;
+                }
+
+                let color = match dash as i64 % 2 == 0 {
+                    true => Color::BLACK,
+                    false => Color::WHITE,
+                };
+
+                let bounds = match horizontal {
+                    true => Rectangle {
+                        x: position,
+                        y: across - 0.5,
+                        width: dash_end - position,
+                        height: 1.0,
+                    },
+                    false => Rectangle {
+                        x: across - 0.5,
+                        y: position,
+                        width: 1.0,
+                        height: dash_end - position,
+                    },
+                };
+
+                mesh.fill_rectangle(bounds, color);
+                position = dash_end;
+            }
+        }
+    }
+
+    /// Tessellate the annotations in the coordinates of the canvas bounds.
+    fn draw_annotations(&self, mesh: &mut Mesh, state: &State, color: Color, handle: Color) {
+        let to_screen = |point: Point| to_screen_coords(point, state.canvas_offset, state.scale);
+
+        for annotation in self.annotations {
+            let color = annotation.color.unwrap_or(color);
+            let fill = Color {
+                a: color.a * 0.25,
+                ..color
+            };
+
+            match &annotation.shape {
+                Shape::Point(point) => {
+                    mesh.fill_rectangle(centered(to_screen(*point), ANNOTATION_WIDTH * 4.0), color);
+                }
+                Shape::Rectangle(rectangle) => {
+                    let corners = mesh::corners(*rectangle).map(to_screen);
+
+                    mesh.quad(corners, fill);
+                    mesh.polyline(&corners, true, ANNOTATION_WIDTH, color);
+                }
+                Shape::Ellipse { center, radii } => {
+                    let points = mesh::ellipse(
+                        to_screen(*center),
+                        Vector::new(radii.x * state.scale, radii.y * state.scale),
+                    );
+
+                    mesh.fill_convex(&points, fill);
+                    mesh.polyline(&points, true, ANNOTATION_WIDTH, color);
+                }
+                Shape::Polygon(points) => {
+                    let points: Vec<Point> = points.iter().copied().map(to_screen).collect();
+
+                    mesh.fill_polygon(&points, fill);
+                    mesh.polyline(&points, true, ANNOTATION_WIDTH, color);
+                }
+                Shape::Polyline(points) => {
+                    let points: Vec<Point> = points.iter().copied().map(to_screen).collect();
+
+                    mesh.polyline(&points, false, ANNOTATION_WIDTH, color);
+                }
+            }
+
+            if state.selected_annotation == Some(annotation.id) {
+                for (kind, point) in annotation.shape.handles() {
+                    let size = match kind {
+                        Handle::Edge(_) => HANDLE_SIZE * 0.75,
+                        _ => HANDLE_SIZE,
+                    };
+
+                    let bounds = centered(to_screen(point), size);
+
+                    mesh.fill_rectangle(bounds, handle);
+                    mesh.stroke_rectangle(bounds, 1.0, color);
+                }
+            }
+        }
+    }
 }
 
 impl<'a, Message, Theme, Renderer, Handler> Widget<Message, Theme, Renderer>
     for TextureCanvas<'a, Message, Theme, Handler>
 where
-    Renderer: iced_wgpu::primitive::Renderer,
+    Renderer: iced_wgpu::primitive::Renderer + text::Renderer,
     Theme: Catalog,
-    Handler: SurfaceHandler,
+    Handler: Source,
 {
     fn tag(&self) -> widget::tree::Tag {
         struct Tag<T>(T);
@@ -252,7 +761,7 @@ where
     }
 
     fn state(&self) -> widget::tree::State {
-        let default_zoom = self.default_zoom.clamp(MIN_SCALE, MAX_SCALE);
+        let default_zoom = self.default_zoom.clamp(self.min_scale(), MAX_SCALE);
         widget::tree::State::new(State::new(default_zoom))
     }
 
@@ -279,7 +788,7 @@ where
         theme: &Theme,
         _style: &renderer::Style,
         layout: Layout<'_>,
-        _cursor_position: mouse::Cursor,
+        cursor: mouse::Cursor,
         _viewport: &Rectangle,
     ) {
         let bounds = layout.bounds();
@@ -288,14 +797,24 @@ where
         let glam::Vec2 { x, y } = state.canvas_offset;
         let scale = state.scale;
 
-        let texture_width = self.buffer.width() as f32 * scale;
-        let texture_height = self.buffer.height() as f32 * scale;
+        let texture_width = self.source.displayed().width() as f32 * scale;
+        let texture_height = self.source.displayed().height() as f32 * scale;
 
         let style::Style {
             background,
             border_color,
             border_thickness,
             shadow,
+            annotation,
+            handle,
+            grid,
+            tile_grid,
+            checkerboard,
+            ruler,
+            ruler_marks,
+            guide,
+            placeholder,
+            progress,
         } = theme.style(
             &self.class,
             match state.is_hovered {
@@ -304,6 +823,11 @@ where
             },
         );
 
+        let mut overlay = Mesh::new();
+        self.draw_selection(&mut overlay, state, bounds.size());
+        self.draw_annotations(&mut overlay, state, annotation, handle);
+        self.draw_guides(&mut overlay, state, bounds.size(), guide);
+
         renderer.with_layer(bounds, |renderer| {
             // Draw the outlines, shadows and backdrop.
             renderer.fill_quad(
@@ -334,27 +858,94 @@ where
             renderer.draw_primitive(
                 bounds,
                 Primitive::new(
-                    self.buffer.create_weak(),
+                    self.source.displayed().content(),
+                    &state.canvas,
                     state.canvas_offset,
-                    state.scale.clamp(MIN_SCALE, MAX_SCALE),
-                    state.generation,
-                ),
+                    state.scale.clamp(self.min_scale(), MAX_SCALE),
+                )
+                .with_overlay(overlay.into_vertices())
+                .with_grid(Grid {
+                    threshold: self.pixel_grid,
+                    color: grid,
+                    tile_size: self.tile_grid,
+                    tile_color: tile_grid,
+                })
+                .with_checkerboard(checkerboard)
+                .with_tiling(self.tiling),
             );
         });
+
+        if let Some(loading) = self.loading {
+            renderer.with_layer(bounds, |renderer| {
+                draw_loading(renderer, bounds, loading, placeholder, progress);
+            });
+        }
+
+        if self.rulers {
+            renderer.with_layer(bounds, |renderer| {
+                draw_rulers(
+                    renderer,
+                    bounds,
+                    state,
+                    cursor.position_in(bounds),
+                    ruler,
+                    ruler_marks,
+                );
+            });
+        }
     }
 
     fn mouse_interaction(
         &self,
         tree: &widget::Tree,
-        _layout: Layout<'_>,
-        _cursor: mouse::Cursor,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
         _viewport: &Rectangle,
         _renderer: &Renderer,
     ) -> mouse::Interaction {
         let state: &State = tree.state.downcast_ref::<State>();
 
-        if state.grabbing {
+        let hovered_annotation = cursor
+            .position()
+            .filter(|_| self.on_annotation_change.is_some())
+            .and_then(|position| {
+                let point =
+                    to_canvas_coords(layout.bounds(), position, state.canvas_offset, state.scale);
+                self.annotation_at(point, HANDLE_SIZE / state.scale, state.selected_annotation)
+            });
+
+        let hovered_guide = cursor
+            .position()
+            .filter(|_| self.on_guide_change.is_some())
+            .and_then(|position| {
+                let bounds = layout.bounds();
+
+                self.ruler_at(local_coords(bounds, position)).or_else(|| {
+                    let point =
+                        to_canvas_coords(bounds, position, state.canvas_offset, state.scale);
+                    let (_, guide) = self.guide_at(point, state.scale)?;
+                    Some(guide.orientation)
+                })
+            });
+
+        let guide_orientation = state
+            .guide_drag
+            .as_ref()
+            .map(|drag| drag.guide.orientation)
+            .or(hovered_guide);
+
+        if state.grabbing || state.annotation_drag.is_some() {
             mouse::Interaction::Grabbing
+        } else if let Some(orientation) = guide_orientation {
+            match orientation {
+                Orientation::Horizontal => mouse::Interaction::ResizingVertically,
+                Orientation::Vertical => mouse::Interaction::ResizingHorizontally,
+            }
+        } else if let Some((_, handle)) = hovered_annotation {
+            match handle {
+                Handle::Body => mouse::Interaction::Grab,
+                Handle::Vertex(_) | Handle::Edge(_) => mouse::Interaction::Pointer,
+            }
         } else if state.is_hovered {
             self.interaction.unwrap_or_default()
         } else {
@@ -377,8 +968,33 @@ where
 
         let state = tree.state.downcast_mut::<State>();
 
-        let image_width = self.buffer.width() as f32;
-        let image_height = self.buffer.height() as f32;
+        let image_width = self.source.displayed().width() as f32;
+        let image_height = self.source.displayed().height() as f32;
+
+        // Report errors from the last time the surface was prepared.
+        let texture_error = state.canvas.error();
+
+        if texture_error != state.texture_error {
+            state.texture_error = texture_error;
+
+            if let Some(error) = texture_error
+                && let Some(on_texture_error) = &self.on_texture_error
+            {
+                shell.publish(on_texture_error(error));
+            }
+        }
+
+        // Animate the marching ants of the selection.
+        if let Event::Window(window::Event::RedrawRequested(now)) = event
+            && self
+                .selection
+                .is_some_and(|selection| !selection.outline().is_empty())
+        {
+            let steps = now.duration_since(state.created).as_millis() / ANTS_INTERVAL.as_millis();
+            state.ants_offset = (steps % (ANTS_DASH as u128 * 2)) as f32;
+
+            shell.request_redraw_at(*now + ANTS_INTERVAL);
+        }
 
         if state.should_center {
             state.should_center = false;
@@ -397,7 +1013,7 @@ where
             let x_percent = point.x / image_width;
             let y_percent = point.y / image_height;
 
-            state.scale = (new_scale).clamp(MIN_SCALE, MAX_SCALE);
+            state.scale = (new_scale).clamp(self.min_scale(), MAX_SCALE);
 
             // recalculate the bounds of the canvas
             let new_canvas_width = image_width * state.scale;
@@ -465,11 +1081,21 @@ where
                 }
 
                 Event::Mouse(mouse::Event::ButtonPressed(mouse_button)) => {
+                    if *mouse_button == mouse::Button::Left
+                        && self.press_guide(state, bounds, mouse_pos)
+                    {
+                        return;
+                    }
+
+                    let point =
+                        to_canvas_coords(bounds, mouse_pos, state.canvas_offset, state.scale);
+
+                    if self.press_annotation(state, point, *mouse_button, shell) {
+                        return;
+                    }
+
                     if let Some(on_press) = &self.on_pressed {
-                        shell.publish(on_press(
-                            to_canvas_coords(bounds, mouse_pos, state.canvas_offset, state.scale),
-                            *mouse_button,
-                        ));
+                        shell.publish(on_press(point, *mouse_button));
                     }
                 }
 
@@ -486,6 +1112,43 @@ where
                         }
                     }
 
+                    if let Some(drag) = &mut state.guide_drag
+                        && let Some(on_guide_change) = &self.on_guide_change
+                    {
+                        let point =
+                            to_canvas_coords(bounds, mouse_pos, state.canvas_offset, state.scale);
+
+                        drag.guide = guide_through(drag.guide.orientation, point);
+
+                        if let Some(index) = drag.index {
+                            shell.publish(on_guide_change(GuideChanged::Moved {
+                                index,
+                                guide: drag.guide,
+                            }));
+                        }
+
+                        shell.request_redraw();
+                    }
+
+                    if let Some(drag) = &state.annotation_drag
+                        && let Some(on_annotation_change) = &self.on_annotation_change
+                    {
+                        let point =
+                            to_canvas_coords(bounds, mouse_pos, state.canvas_offset, state.scale);
+
+                        let mut shape = drag.shape.clone();
+
+                        match drag.handle {
+                            Handle::Body => shape.translate(point - drag.origin),
+                            handle => shape.move_handle(handle, point),
+                        }
+
+                        shell.publish(on_annotation_change(AnnotationChanged {
+                            id: drag.id,
+                            shape,
+                        }));
+                    }
+
                     if let Some(on_move) = &self.on_move {
                         shell.publish(on_move(to_canvas_coords(
                             bounds,
@@ -499,6 +1162,32 @@ where
                 }
 
                 Event::Mouse(mouse::Event::ButtonReleased(mouse_button)) => {
+                    if *mouse_button == mouse::Button::Left
+                        && let Some(drag) = state.guide_drag.take()
+                        && let Some(on_guide_change) = &self.on_guide_change
+                    {
+                        let over_ruler = self.ruler_at(local_coords(bounds, mouse_pos))
+                            == Some(drag.guide.orientation);
+
+                        match (drag.index, over_ruler) {
+                            (None, false) => {
+                                shell.publish(on_guide_change(GuideChanged::Added(drag.guide)))
+                            }
+                            (Some(index), true) => {
+                                shell.publish(on_guide_change(GuideChanged::Removed(index)))
+                            }
+                            _ => shell.request_redraw(),
+                        }
+
+                        return;
+                    }
+
+                    if *mouse_button == mouse::Button::Left
+                        && state.annotation_drag.take().is_some()
+                    {
+                        return;
+                    }
+
                     if let Some(on_release) = &self.on_release {
                         shell.publish(on_release(
                             to_canvas_coords(bounds, mouse_pos, state.canvas_offset, state.scale),
@@ -533,7 +1222,17 @@ where
                         //     *y
                         // };
 
-                        state.scale = (state.scale + y).clamp(MIN_SCALE, MAX_SCALE);
+                        // Pyramids zoom out below 100%, where each line halves or doubles
+                        // the scale instead.
+                        let scale = if self.source.displayed().pyramid().is_some()
+                            && (state.scale < 1.0 || state.scale + y < 1.0)
+                        {
+                            (state.scale * 2f32.powf(*y)).min(1.0)
+                        } else {
+                            state.scale + y
+                        };
+
+                        state.scale = scale.clamp(self.min_scale(), MAX_SCALE);
 
                         // recalculate the bounds of the canvas
                         let new_canvas_width = image_width * state.scale;
@@ -583,8 +1282,8 @@ impl<'a, Message, Theme, Renderer, Handler> From<TextureCanvas<'a, Message, Them
 where
     Message: 'a,
     Theme: Catalog + 'a,
-    Renderer: iced_wgpu::primitive::Renderer,
-    Handler: SurfaceHandler,
+    Renderer: iced_wgpu::primitive::Renderer + text::Renderer,
+    Handler: Source,
 {
     fn from(value: TextureCanvas<'a, Message, Theme, Handler>) -> Self {
         Element::new(value)
@@ -599,17 +1298,334 @@ fn to_canvas_coords(bounds: Rectangle, mouse: Point, offset: glam::Vec2, scale:
     Point { x, y }
 }
 
+fn to_screen_coords(point: Point, offset: glam::Vec2, scale: f32) -> Point {
+    let glam::Vec2 { x, y } = glam::vec2(point.x, point.y) * scale + offset;
+
+    Point { x, y }
+}
+
+/// Convert a position on the screen to be relative to the bounds of the canvas.
+fn local_coords(bounds: Rectangle, position: Point) -> Point {
+    Point::new(position.x - bounds.x, position.y - bounds.y)
+}
+
+/// A [`Guide`] with the given [`Orientation`] through the nearest pixel boundary to the `point`.
+fn guide_through(orientation: Orientation, point: Point) -> Guide {
+    match orientation {
+        Orientation::Horizontal => Guide::horizontal(point.y.round()),
+        Orientation::Vertical => Guide::vertical(point.x.round()),
+    }
+}
+
+/// Pick the distance between labelled ruler ticks, and how many smaller ticks to divide it into.
+fn ruler_steps(scale: f32) -> (u32, u32) {
+    let step = (0..7)
+        .flat_map(|exponent| [1, 2, 5].map(|step| step * 10u32.pow(exponent)))
+        .find(|step| *step as f32 * scale >= RULER_LABEL_SPACING)
+        .unwrap_or(10_000_000);
+
+    let divisions = [10, 5, 2]
+        .into_iter()
+        .find(|divisions| step % divisions == 0 && (step / divisions) as f32 * scale >= 4.0)
+        .unwrap_or(1);
+
+    (step, divisions)
+}
+
+/// Draw the loading placeholder over the whole canvas, with a progress bar in the middle.
+fn draw_loading<Renderer>(
+    renderer: &mut Renderer,
+    bounds: Rectangle,
+    loading: f32,
+    placeholder: Color,
+    progress: Color,
+) where
+    Renderer: renderer::Renderer,
+{
+    let fill = |renderer: &mut Renderer, bounds: Rectangle, color: Color| {
+        renderer.fill_quad(
+            renderer::Quad {
+                bounds,
+                snap: true,
+                ..Default::default()
+            },
+            color,
+        );
+    };
+
+    fill(renderer, bounds, placeholder);
+
+    let width = PROGRESS_WIDTH.min(bounds.width * 0.5);
+    let track = Rectangle {
+        x: bounds.center_x() - width / 2.0,
+        y: bounds.center_y() - PROGRESS_HEIGHT / 2.0,
+        width,
+        height: PROGRESS_HEIGHT,
+    };
+
+    fill(
+        renderer,
+        track,
+        Color {
+            a: progress.a * 0.25,
+            ..progress
+        },
+    );
+    fill(
+        renderer,
+        Rectangle {
+            width: track.width * loading,
+            ..track
+        },
+        progress,
+    );
+}
+
+fn draw_rulers<Renderer>(
+    renderer: &mut Renderer,
+    bounds: Rectangle,
+    state: &State,
+    cursor: Option<Point>,
+    background: Color,
+    marks: Color,
+) where
+    Renderer: text::Renderer,
+{
+    let fill = |renderer: &mut Renderer, bounds: Rectangle, color: Color| {
+        renderer.fill_quad(
+            renderer::Quad {
+                bounds,
+                snap: true,
+                ..Default::default()
+            },
+            color,
+        );
+    };
+
+    let top = Rectangle {
+        height: RULER_SIZE,
+        ..bounds
+    };
+    let left = Rectangle {
+        width: RULER_SIZE,
+        ..bounds
+    };
+
+    fill(renderer, top, background);
+    fill(renderer, left, background);
+
+    // Separate the rulers from the canvas.
+    fill(
+        renderer,
+        Rectangle {
+            y: top.y + RULER_SIZE - 1.0,
+            height: 1.0,
+            ..top
+        },
+        marks,
+    );
+    fill(
+        renderer,
+        Rectangle {
+            x: left.x + RULER_SIZE - 1.0,
+            width: 1.0,
+            ..left
+        },
+        marks,
+    );
+
+    let (step, divisions) = ruler_steps(state.scale);
+    let tick = step as f32 / divisions as f32;
+
+    // The top ruler measures `x`, and the left ruler measures `y`.
+    for (ruler, offset, length, horizontal) in [
+        (top, state.canvas_offset.x, bounds.width, true),
+        (left, state.canvas_offset.y, bounds.height, false),
+    ] {
+        let first = ((RULER_SIZE - offset) / state.scale / tick).floor() as i64;
+        let last = ((length - offset) / state.scale / tick).ceil() as i64;
+
+        for i in first..=last {
+            let value = i as f32 * tick;
+            let position = (value * state.scale + offset).floor();
+
+            if position < RULER_SIZE || position > length {
+                continue;
+            }
+
+            let major = i % divisions as i64 == 0;
+            let size = if major { RULER_SIZE } else { RULER_SIZE * 0.3 };
+
+            let mark = match horizontal {
+                true => Rectangle {
+                    x: ruler.x + position,
+                    y: ruler.y + RULER_SIZE - size,
+                    width: 1.0,
+                    height: size,
+                },
+                false => Rectangle {
+                    x: ruler.x + RULER_SIZE - size,
+                    y: ruler.y + position,
+                    width: size,
+                    height: 1.0,
+                },
+            };
+
+            fill(renderer, mark, marks);
+
+            if !major {
+                continue;
+            }
+
+            let label = (value.round() as i64).to_string();
+
+            // Stack the digits on the left ruler so they fit within its width.
+            let (content, position) = match horizontal {
+                true => (label, Point::new(mark.x + 2.0, ruler.y + 1.0)),
+                false => (
+                    label
+                        .chars()
+                        .map(String::from)
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    Point::new(ruler.x + 2.0, mark.y + 2.0),
+                ),
+            };
+
+            renderer.fill_text(
+                text::Text {
+                    content,
+                    bounds: Size::new(f32::INFINITY, f32::INFINITY),
+                    size: RULER_TEXT_SIZE.into(),
+                    line_height: text::LineHeight::Relative(1.0),
+                    font: renderer.default_font(),
+                    align_x: text::Alignment::Left,
+                    align_y: alignment::Vertical::Top,
+                    shaping: text::Shaping::Basic,
+                    wrapping: text::Wrapping::None,
+                },
+                position,
+                marks,
+                ruler,
+            );
+        }
+    }
+
+    // Mark the position of the cursor on both rulers.
+    if let Some(cursor) = cursor {
+        if cursor.x >= RULER_SIZE {
+            fill(
+                renderer,
+                Rectangle {
+                    x: top.x + cursor.x.floor(),
+                    width: 1.0,
+                    ..top
+                },
+                marks,
+            );
+        }
+
+        if cursor.y >= RULER_SIZE {
+            fill(
+                renderer,
+                Rectangle {
+                    y: left.y + cursor.y.floor(),
+                    height: 1.0,
+                    ..left
+                },
+                marks,
+            );
+        }
+    }
+
+    // Cover the ticks where the rulers meet.
+    fill(
+        renderer,
+        Rectangle {
+            width: RULER_SIZE,
+            height: RULER_SIZE,
+            ..bounds
+        },
+        background,
+    );
+}
+
+/// A square of the given size centered on the `point`.
+fn centered(point: Point, size: f32) -> Rectangle {
+    Rectangle {
+        x: point.x - size / 2.0,
+        y: point.y - size / 2.0,
+        width: size,
+        height: size,
+    }
+}
+
+impl<S: Surface> Displayed<'_, S> {
+    fn width(&self) -> u32 {
+        match self {
+            Displayed::Surface(handler) => handler.width(),
+            Displayed::Pyramid(pyramid) => pyramid.width(),
+        }
+    }
+
+    fn height(&self) -> u32 {
+        match self {
+            Displayed::Surface(handler) => handler.height(),
+            Displayed::Pyramid(pyramid) => pyramid.height(),
+        }
+    }
+
+    fn pyramid(&self) -> Option<&Pyramid> {
+        match self {
+            Displayed::Surface(_) => None,
+            Displayed::Pyramid(pyramid) => Some(*pyramid),
+        }
+    }
+
+    fn content(&self) -> Content<S> {
+        match self {
+            Displayed::Surface(handler) => Content::Surface(handler.create_weak()),
+            Displayed::Pyramid(pyramid) => Content::Pyramid(Pyramid::clone(pyramid)),
+        }
+    }
+}
+
 pub(crate) struct State {
     canvas_grab: Option<glam::Vec2>,
     grabbing: bool,
     canvas_offset: glam::Vec2,
     pub scale: f32,
     is_hovered: bool,
-    /// Used to force the shader pipeline to update the texture
-    /// if there's a mismatch.
-    generation: u64,
+    /// Identifies the texture of the widget, which is freed once the widget is removed.
+    canvas: Canvas,
     pub should_center: bool,
     pub suggested_scale: Option<f32>,
+    selected_annotation: Option<annotation::Id>,
+    annotation_drag: Option<AnnotationDrag>,
+    /// When the widget was created, used to animate the selection outline.
+    created: Instant,
+    /// How far the dashes of the selection outline have moved.
+    ants_offset: f32,
+    guide_drag: Option<GuideDrag>,
+    /// The last error reported by the canvas.
+    texture_error: Option<TextureError>,
+}
+
+/// A [`Guide`] being dragged with the mouse.
+struct GuideDrag {
+    /// The index of the guide, or [`None`] if it's being pulled out of a ruler.
+    index: Option<usize>,
+    guide: Guide,
+}
+
+/// An [`Annotation`] being edited with the mouse.
+struct AnnotationDrag {
+    id: annotation::Id,
+    handle: Handle,
+    /// Where the drag started, in image coordinates.
+    origin: Point,
+    /// The shape of the annotation when the drag started.
+    shape: Shape,
 }
 
 impl State {
@@ -620,9 +1636,15 @@ impl State {
             canvas_offset: Default::default(),
             scale: default_scale,
             is_hovered: Default::default(),
-            generation: new_generation(),
+            canvas: Canvas::new(),
             should_center: true,
             suggested_scale: None,
+            selected_annotation: None,
+            annotation_drag: None,
+            created: Instant::now(),
+            ants_offset: 0.0,
+            guide_drag: None,
+            texture_error: None,
         }
     }
 }
@@ -632,13 +1654,7 @@ impl State {
         self.is_hovered = false;
         self.grabbing = false;
         self.canvas_grab = None;
+        self.annotation_drag = None;
+        self.guide_drag = None;
     }
 }
-
-fn new_generation() -> u64 {
-    use std::sync::atomic::AtomicU64;
-
-    static GENERATION: AtomicU64 = AtomicU64::new(0);
-
-    GENERATION.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
-}
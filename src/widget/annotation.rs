@@ -0,0 +1,399 @@
+//! Editable shapes displayed on top of the image in a [`TextureCanvas`](crate::TextureCanvas).
+//!
+//! All of the geometry is stored in image coordinates,
+//! so annotations stay in place when the image is panned or zoomed.
+use iced_core::{Color, Point, Rectangle, Vector};
+
+use std::sync::atomic::{self, AtomicU64};
+
+/// The identifier of an [`Annotation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Id(u64);
+
+impl Id {
+    /// Create an [`Id`] from a raw value.
+    pub const fn new(id: u64) -> Self {
+        Self(id)
+    }
+
+    /// Create a unique [`Id`].
+    ///
+    /// Unique ids are allocated from the upper half of the id space to avoid
+    /// colliding with ids created with [`Id::new`].
+    pub fn unique() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1 << 63);
+
+        Self(NEXT.fetch_add(1, atomic::Ordering::Relaxed))
+    }
+
+    /// Get the raw value of the [`Id`].
+    pub const fn get(self) -> u64 {
+        self.0
+    }
+}
+
+impl From<u64> for Id {
+    fn from(id: u64) -> Self {
+        Self::new(id)
+    }
+}
+
+/// A [`Shape`] drawn on top of the image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub id: Id,
+    pub shape: Shape,
+    /// Overrides the annotation color of the [`Style`](crate::Style).
+    pub color: Option<Color>,
+}
+
+impl Annotation {
+    /// Create a new [`Annotation`].
+    pub fn new(id: impl Into<Id>, shape: Shape) -> Self {
+        Self {
+            id: id.into(),
+            shape,
+            color: None,
+        }
+    }
+
+    /// Set the color of the [`Annotation`].
+    pub fn color(mut self, color: impl Into<Color>) -> Self {
+        self.color = Some(color.into());
+        self
+    }
+}
+
+/// The message produced when an [`Annotation`] is edited through the [`TextureCanvas`](crate::TextureCanvas).
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnotationChanged {
+    /// The [`Id`] of the edited [`Annotation`].
+    pub id: Id,
+    /// The new [`Shape`] of the edited [`Annotation`].
+    pub shape: Shape,
+}
+
+/// The geometry of an [`Annotation`] in image coordinates.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Point(Point),
+    Rectangle(Rectangle),
+    Ellipse { center: Point, radii: Vector },
+    Polygon(Vec<Point>),
+    Polyline(Vec<Point>),
+}
+
+/// A part of a [`Shape`] that can be dragged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Handle {
+    /// Moves the whole [`Shape`].
+    Body,
+    /// Moves a single vertex of the [`Shape`].
+    ///
+    /// For rectangles these are the corners (clockwise from the top left),
+    /// and for ellipses the extremes of each axis (clockwise from the right).
+    Vertex(usize),
+    /// The middle of an edge of the [`Shape`].
+    ///
+    /// For rectangles this moves a side (clockwise from the top),
+    /// for polygons and polylines dragging it inserts a new vertex.
+    Edge(usize),
+}
+
+impl Shape {
+    /// The smallest [`Rectangle`] containing the [`Shape`].
+    pub fn bounds(&self) -> Rectangle {
+        match self {
+            Shape::Point(point) => Rectangle::new(*point, iced_core::Size::ZERO),
+            Shape::Rectangle(rectangle) => *rectangle,
+            Shape::Ellipse { center, radii } => Rectangle {
+                x: center.x - radii.x.abs(),
+                y: center.y - radii.y.abs(),
+                width: radii.x.abs() * 2.0,
+                height: radii.y.abs() * 2.0,
+            },
+            Shape::Polygon(points) | Shape::Polyline(points) => {
+                let (min, max) = points.iter().fold(
+                    (
+                        Point::new(f32::INFINITY, f32::INFINITY),
+                        Point::new(f32::NEG_INFINITY, f32::NEG_INFINITY),
+                    ),
+                    |(min, max), p| {
+                        (
+                            Point::new(min.x.min(p.x), min.y.min(p.y)),
+                            Point::new(max.x.max(p.x), max.y.max(p.y)),
+                        )
+                    },
+                );
+
+                if points.is_empty() {
+                    return Rectangle::default();
+                }
+
+                Rectangle {
+                    x: min.x,
+                    y: min.y,
+                    width: max.x - min.x,
+                    height: max.y - min.y,
+                }
+            }
+        }
+    }
+
+    /// Check whether the `point` lies on the [`Shape`].
+    ///
+    /// Closed shapes are hit anywhere inside of them, while points and polylines
+    /// are hit within `tolerance`.
+    pub fn contains(&self, point: Point, tolerance: f32) -> bool {
+        match self {
+            Shape::Point(p) => p.distance(point) <= tolerance,
+            Shape::Rectangle(r) => {
+                point.x >= r.x - tolerance
+                    && point.x <= r.x + r.width + tolerance
+                    && point.y >= r.y - tolerance
+                    && point.y <= r.y + r.height + tolerance
+            }
+            Shape::Ellipse { center, radii } => {
+                let rx = radii.x.abs() + tolerance;
+                let ry = radii.y.abs() + tolerance;
+                let dx = (point.x - center.x) / rx;
+                let dy = (point.y - center.y) / ry;
+
+                dx * dx + dy * dy <= 1.0
+            }
+            Shape::Polygon(points) => {
+                contains_point(points, point)
+                    || edges(points, true)
+                        .any(|(a, b)| distance_to_segment(point, a, b) <= tolerance)
+            }
+            Shape::Polyline(points) => match points.as_slice() {
+                [p] => p.distance(point) <= tolerance,
+                _ => {
+                    edges(points, false).any(|(a, b)| distance_to_segment(point, a, b) <= tolerance)
+                }
+            },
+        }
+    }
+
+    /// The draggable [`Handle`]s of the [`Shape`] and their positions.
+    pub fn handles(&self) -> Vec<(Handle, Point)> {
+        match self {
+            Shape::Point(point) => vec![(Handle::Vertex(0), *point)],
+            Shape::Rectangle(r) => {
+                let corners = rectangle_corners(*r);
+
+                let vertices = corners
+                    .iter()
+                    .enumerate()
+                    .map(|(i, p)| (Handle::Vertex(i), *p));
+                let sides =
+                    (0..4).map(|i| (Handle::Edge(i), midpoint(corners[i], corners[(i + 1) % 4])));
+
+                vertices.chain(sides).collect()
+            }
+            Shape::Ellipse { center, radii } => {
+                let (rx, ry) = (radii.x.abs(), radii.y.abs());
+
+                [
+                    Point::new(center.x + rx, center.y),
+                    Point::new(center.x, center.y + ry),
+                    Point::new(center.x - rx, center.y),
+                    Point::new(center.x, center.y - ry),
+                ]
+                .into_iter()
+                .enumerate()
+                .map(|(i, p)| (Handle::Vertex(i), p))
+                .collect()
+            }
+            Shape::Polygon(points) | Shape::Polyline(points) => {
+                let closed = matches!(self, Shape::Polygon(_));
+
+                let vertices = points
+                    .iter()
+                    .enumerate()
+                    .map(|(i, p)| (Handle::Vertex(i), *p));
+                let edges = edges(points, closed)
+                    .enumerate()
+                    .map(|(i, (a, b))| (Handle::Edge(i), midpoint(a, b)));
+
+                vertices.chain(edges).collect()
+            }
+        }
+    }
+
+    /// Find the [`Handle`] under the `point`.
+    ///
+    /// Vertices take priority over edges, which take priority over the body.
+    pub fn handle_at(&self, point: Point, tolerance: f32) -> Option<Handle> {
+        let handles = self.handles();
+
+        let nearest = |edge: bool| {
+            handles
+                .iter()
+                .filter(|(handle, _)| matches!(handle, Handle::Edge(_)) == edge)
+                .map(|(handle, p)| (*handle, p.distance(point)))
+                .filter(|(_, distance)| *distance <= tolerance)
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(handle, _)| handle)
+        };
+
+        nearest(false)
+            .or_else(|| nearest(true))
+            .or_else(|| self.contains(point, tolerance).then_some(Handle::Body))
+    }
+
+    /// Move the whole [`Shape`] by the given amount.
+    pub fn translate(&mut self, delta: Vector) {
+        match self {
+            Shape::Point(point) => *point = *point + delta,
+            Shape::Rectangle(r) => {
+                r.x += delta.x;
+                r.y += delta.y;
+            }
+            Shape::Ellipse { center, .. } => *center = *center + delta,
+            Shape::Polygon(points) | Shape::Polyline(points) => {
+                points.iter_mut().for_each(|p| *p = *p + delta)
+            }
+        }
+    }
+
+    /// Move the given [`Handle`] to the `position`.
+    ///
+    /// Dragging the [`Handle::Body`] is done with [`Shape::translate`] instead.
+    pub fn move_handle(&mut self, handle: Handle, position: Point) {
+        match (self, handle) {
+            (_, Handle::Body) => (),
+            (Shape::Point(point), Handle::Vertex(0)) => *point = position,
+            (Shape::Rectangle(r), Handle::Vertex(i)) if i < 4 => {
+                let opposite = rectangle_corners(*r)[(i + 2) % 4];
+                *r = rectangle_from_points(opposite, position);
+            }
+            (Shape::Rectangle(r), Handle::Edge(i)) if i < 4 => {
+                let (mut min, mut max) = (
+                    Point::new(r.x, r.y),
+                    Point::new(r.x + r.width, r.y + r.height),
+                );
+
+                match i {
+                    0 => min.y = position.y,
+                    1 => max.x = position.x,
+                    2 => max.y = position.y,
+                    _ => min.x = position.x,
+                }
+
+                *r = rectangle_from_points(min, max);
+            }
+            (Shape::Ellipse { center, radii }, Handle::Vertex(i)) if i < 4 => {
+                if i % 2 == 0 {
+                    radii.x = (position.x - center.x).abs();
+                } else {
+                    radii.y = (position.y - center.y).abs();
+                }
+            }
+            (Shape::Polygon(points) | Shape::Polyline(points), Handle::Vertex(i)) => {
+                if let Some(point) = points.get_mut(i) {
+                    *point = position;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Insert a vertex at the `position` after the start of the given edge.
+    ///
+    /// Returns the index of the new vertex, or [`None`] if the [`Shape`] isn't
+    /// a polygon or polyline.
+    pub fn insert_vertex(&mut self, edge: usize, position: Point) -> Option<usize> {
+        match self {
+            Shape::Polygon(points) | Shape::Polyline(points) if edge < points.len() => {
+                points.insert(edge + 1, position);
+                Some(edge + 1)
+            }
+            _ => None,
+        }
+    }
+
+    /// Remove the vertex at `index`.
+    ///
+    /// Polygons keep at least 3 vertices, and polylines at least 2.
+    /// Returns whether the vertex was removed.
+    pub fn remove_vertex(&mut self, index: usize) -> bool {
+        let (points, min) = match self {
+            Shape::Polygon(points) => (points, 3),
+            Shape::Polyline(points) => (points, 2),
+            _ => return false,
+        };
+
+        if index >= points.len() || points.len() <= min {
+            return false;
+        }
+
+        points.remove(index);
+        true
+    }
+}
+
+/// Corners of a rectangle, clockwise from the top left.
+fn rectangle_corners(r: Rectangle) -> [Point; 4] {
+    [
+        Point::new(r.x, r.y),
+        Point::new(r.x + r.width, r.y),
+        Point::new(r.x + r.width, r.y + r.height),
+        Point::new(r.x, r.y + r.height),
+    ]
+}
+
+fn rectangle_from_points(a: Point, b: Point) -> Rectangle {
+    Rectangle {
+        x: a.x.min(b.x),
+        y: a.y.min(b.y),
+        width: (a.x - b.x).abs(),
+        height: (a.y - b.y).abs(),
+    }
+}
+
+fn midpoint(a: Point, b: Point) -> Point {
+    Point::new((a.x + b.x) / 2.0, (a.y + b.y) / 2.0)
+}
+
+/// Iterate over the line segments between consecutive points.
+fn edges(points: &[Point], closed: bool) -> impl Iterator<Item = (Point, Point)> + '_ {
+    let closing = (closed && points.len() > 2).then(|| (points[points.len() - 1], points[0]));
+
+    points
+        .windows(2)
+        .map(|pair| (pair[0], pair[1]))
+        .chain(closing)
+}
+
+fn distance_to_segment(p: Point, a: Point, b: Point) -> f32 {
+    let ab = b - a;
+    let length_squared = ab.x * ab.x + ab.y * ab.y;
+
+    if length_squared <= f32::EPSILON {
+        return p.distance(a);
+    }
+
+    let ap = p - a;
+    let t = ((ap.x * ab.x + ap.y * ab.y) / length_squared).clamp(0.0, 1.0);
+
+    p.distance(Point::new(a.x + ab.x * t, a.y + ab.y * t))
+}
+
+/// Even-odd point in polygon test.
+fn contains_point(points: &[Point], p: Point) -> bool {
+    let mut inside = false;
+    let mut j = points.len().wrapping_sub(1);
+
+    for (i, a) in points.iter().enumerate() {
+        let b = points[j];
+
+        if (a.y > p.y) != (b.y > p.y) && p.x < (b.x - a.x) * (p.y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+
+        j = i;
+    }
+
+    inside
+}
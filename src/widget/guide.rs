@@ -0,0 +1,113 @@
+//! Guides pulled out of the rulers of a [`TextureCanvas`](crate::TextureCanvas), and snapping.
+use iced_core::Point;
+
+/// The orientation of a [`Guide`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Orientation {
+    /// A horizontal line, pulled out of the top ruler.
+    Horizontal,
+    /// A vertical line, pulled out of the left ruler.
+    Vertical,
+}
+
+/// A horizontal or vertical line across the image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Guide {
+    pub orientation: Orientation,
+    /// The position of the [`Guide`] in image coordinates.
+    ///
+    /// This is the `y` coordinate for horizontal guides and the `x` coordinate for vertical ones.
+    pub position: f32,
+}
+
+impl Guide {
+    /// Create a horizontal [`Guide`] at `y`.
+    pub fn horizontal(y: f32) -> Self {
+        Self {
+            orientation: Orientation::Horizontal,
+            position: y,
+        }
+    }
+
+    /// Create a vertical [`Guide`] at `x`.
+    pub fn vertical(x: f32) -> Self {
+        Self {
+            orientation: Orientation::Vertical,
+            position: x,
+        }
+    }
+
+    /// The distance between the [`Guide`] and a point in image coordinates.
+    pub fn distance(&self, point: Point) -> f32 {
+        match self.orientation {
+            Orientation::Horizontal => (point.y - self.position).abs(),
+            Orientation::Vertical => (point.x - self.position).abs(),
+        }
+    }
+}
+
+/// The message produced when the guides are edited through the [`TextureCanvas`](crate::TextureCanvas).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GuideChanged {
+    /// A new [`Guide`] was pulled out of a ruler.
+    Added(Guide),
+    /// The [`Guide`] at the given index was moved.
+    Moved { index: usize, guide: Guide },
+    /// The [`Guide`] at the given index was dragged back onto its ruler.
+    Removed(usize),
+}
+
+/// Snaps points to guides and pixel boundaries.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Snap {
+    /// Snap to guides within the `tolerance`.
+    pub guides: bool,
+    /// Round to the nearest pixel boundary when no guide is close enough.
+    pub pixels: bool,
+    /// The maximum distance to snap to a guide, in image coordinates.
+    ///
+    /// Divide a distance in screen pixels by the scale of the canvas to
+    /// snap consistently at any zoom.
+    pub tolerance: f32,
+}
+
+impl Default for Snap {
+    fn default() -> Self {
+        Self {
+            guides: true,
+            pixels: true,
+            tolerance: 4.0,
+        }
+    }
+}
+
+impl Snap {
+    /// Snap a point in image coordinates.
+    ///
+    /// Each axis is snapped independently to the nearest guide.
+    pub fn point(&self, point: Point, guides: &[Guide]) -> Point {
+        Point::new(
+            self.axis(point.x, guides, Orientation::Vertical),
+            self.axis(point.y, guides, Orientation::Horizontal),
+        )
+    }
+
+    /// Snap a single coordinate to the guides with the given [`Orientation`].
+    ///
+    /// Vertical guides snap `x` coordinates, and horizontal guides snap `y` coordinates.
+    pub fn axis(&self, value: f32, guides: &[Guide], orientation: Orientation) -> f32 {
+        let guide = guides
+            .iter()
+            .filter(|_| self.guides)
+            .filter(|guide| guide.orientation == orientation)
+            .map(|guide| guide.position)
+            .filter(|position| (position - value).abs() <= self.tolerance)
+            .min_by(|a, b| (a - value).abs().total_cmp(&(b - value).abs()));
+
+        match guide {
+            Some(position) => position,
+            None if self.pixels => value.round(),
+            None => value,
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! Tessellation of the shapes drawn on top of the image.
+use super::primitive::overlay::Vertex;
+
+use iced_core::{Color, Point, Rectangle, Vector};
+
+use std::f32::consts::TAU;
+
+/// A list of triangles, in pixels relative to the bounds of the canvas.
+#[derive(Debug, Default)]
+pub(crate) struct Mesh {
+    vertices: Vec<Vertex>,
+}
+
+impl Mesh {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_vertices(self) -> Vec<Vertex> {
+        self.vertices
+    }
+
+    pub fn triangle(&mut self, a: Point, b: Point, c: Point, color: Color) {
+        let color = color.into_linear();
+
+        self.vertices.extend([a, b, c].map(|p| Vertex {
+            position: [p.x, p.y],
+            color,
+        }));
+    }
+
+    pub fn quad(&mut self, [a, b, c, d]: [Point; 4], color: Color) {
+        self.triangle(a, b, c, color);
+        self.triangle(a, c, d, color);
+    }
+
+    pub fn fill_rectangle(&mut self, rect: Rectangle, color: Color) {
+        self.quad(corners(rect), color);
+    }
+
+    pub fn stroke_rectangle(&mut self, rect: Rectangle, width: f32, color: Color) {
+        self.polyline(&corners(rect), true, width, color);
+    }
+
+    /// A straight line with square caps.
+    pub fn line(&mut self, a: Point, b: Point, width: f32, color: Color) {
+        let delta = b - a;
+        let length = (delta.x * delta.x + delta.y * delta.y).sqrt();
+
+        if length <= f32::EPSILON {
+            let half = width / 2.0;
+            self.fill_rectangle(
+                Rectangle {
+                    x: a.x - half,
+                    y: a.y - half,
+                    width,
+                    height: width,
+                },
+                color,
+            );
+            return;
+        }
+
+        let half = width / 2.0;
+        let dir = Vector::new(delta.x / length * half, delta.y / length * half);
+        let normal = Vector::new(-dir.y, dir.x);
+
+        let a = a - dir;
+        let b = b + dir;
+
+        self.quad([a + normal, b + normal, b - normal, a - normal], color);
+    }
+
+    pub fn polyline(&mut self, points: &[Point], closed: bool, width: f32, color: Color) {
+        for pair in points.windows(2) {
+            self.line(pair[0], pair[1], width, color);
+        }
+
+        if closed && points.len() > 2 {
+            self.line(points[points.len() - 1], points[0], width, color);
+        }
+    }
+
+    /// Fill a convex polygon with a triangle fan.
+    pub fn fill_convex(&mut self, points: &[Point], color: Color) {
+        if let [first, rest @ ..] = points {
+            for pair in rest.windows(2) {
+                self.triangle(*first, pair[0], pair[1], color);
+            }
+        }
+    }
+
+    /// Fill a simple (possibly concave) polygon using ear clipping.
+    ///
+    /// Self intersecting polygons are filled as well as possible, falling back to a triangle fan.
+    pub fn fill_polygon(&mut self, points: &[Point], color: Color) {
+        if points.len() < 3 {
+            return;
+        }
+
+        let mut indices: Vec<usize> = (0..points.len()).collect();
+
+        // Ear clipping expects counter-clockwise winding.
+        if signed_area(points) < 0.0 {
+            indices.reverse();
+        }
+
+        while indices.len() > 3 {
+            let n = indices.len();
+
+            let ear = (0..n).find(|&i| {
+                let a = points[indices[(i + n - 1) % n]];
+                let b = points[indices[i]];
+                let c = points[indices[(i + 1) % n]];
+
+                cross(a, b, c) > 0.0
+                    && indices
+                        .iter()
+                        .filter(|&&j| ![a, b, c].contains(&points[j]))
+                        .all(|&j| !in_triangle(points[j], a, b, c))
+            });
+
+            // Self intersecting polygons may not have any ears left,
+            // so fill what remains with a fan rather than leaving a hole.
+            let Some(i) = ear else {
+                let remaining: Vec<Point> = indices.iter().map(|&i| points[i]).collect();
+                self.fill_convex(&remaining, color);
+                return;
+            };
+
+            let a = points[indices[(i + n - 1) % n]];
+            let b = points[indices[i]];
+            let c = points[indices[(i + 1) % n]];
+
+            self.triangle(a, b, c, color);
+            indices.remove(i);
+        }
+
+        self.triangle(
+            points[indices[0]],
+            points[indices[1]],
+            points[indices[2]],
+            color,
+        );
+    }
+}
+
+/// Approximate an ellipse with enough segments to look smooth at its on-screen size.
+pub(crate) fn ellipse(center: Point, radii: Vector) -> Vec<Point> {
+    let segments = ((radii.x.abs().max(radii.y.abs()) * TAU / 4.0).ceil() as usize).clamp(12, 256);
+
+    (0..segments)
+        .map(|i| {
+            let angle = i as f32 / segments as f32 * TAU;
+            Point::new(
+                center.x + radii.x * angle.cos(),
+                center.y + radii.y * angle.sin(),
+            )
+        })
+        .collect()
+}
+
+pub(crate) fn corners(rect: Rectangle) -> [Point; 4] {
+    [
+        Point::new(rect.x, rect.y),
+        Point::new(rect.x + rect.width, rect.y),
+        Point::new(rect.x + rect.width, rect.y + rect.height),
+        Point::new(rect.x, rect.y + rect.height),
+    ]
+}
+
+fn signed_area(points: &[Point]) -> f32 {
+    let n = points.len();
+
+    (0..n)
+        .map(|i| {
+            let (a, b) = (points[i], points[(i + 1) % n]);
+            a.x * b.y - b.x * a.y
+        })
+        .sum::<f32>()
+        / 2.0
+}
+
+fn cross(a: Point, b: Point, c: Point) -> f32 {
+    (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)
+}
+
+fn in_triangle(p: Point, a: Point, b: Point, c: Point) -> bool {
+    cross(a, b, p) >= 0.0 && cross(b, c, p) >= 0.0 && cross(c, a, p) >= 0.0
+}
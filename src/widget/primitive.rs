@@ -1,35 +1,98 @@
+pub mod cache;
+pub mod overlay;
 pub mod pipeline;
 pub mod texture;
 pub mod uniforms;
 
+use crate::pyramid::Pyramid;
+use crate::style::Checkerboard;
 use crate::widget::surface::Surface;
 
-use pipeline::Pipeline;
+use overlay::Vertex;
+use pipeline::{Instance, Pipeline};
 use uniforms::UniformsRaw;
 
-use iced_core::Rectangle;
+use iced_core::{Color, Rectangle, Size};
 use iced_wgpu::wgpu;
 use iced_widget::shader;
 
-use std::sync::{Arc, Weak};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
 
 #[derive(Debug)]
 pub struct Primitive<Buffer: Surface> {
-    surface: Weak<Buffer>,
+    content: Content<Buffer>,
+    canvas: CanvasRef,
     offset: glam::Vec2,
     scale: f32,
-    generation: u64,
+    overlay: Vec<Vertex>,
+    grid: Grid,
+    checkerboard: Option<Checkerboard>,
+    tiling: bool,
+}
+
+/// What a [`Primitive`] draws.
+#[derive(Debug)]
+pub enum Content<Buffer: Surface> {
+    Surface(Weak<Buffer>),
+    /// The visible tiles of a [`Pyramid`].
+    Pyramid(Pyramid),
+}
+
+/// The grids drawn between the pixels of the image.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Grid {
+    /// The scale at which the pixel grid starts fading in.
+    pub threshold: Option<f32>,
+    pub color: Color,
+    /// The size of each tile of the coarse grid.
+    pub tile_size: Option<Size<u32>>,
+    pub tile_color: Color,
 }
 
 impl<Buffer: Surface> Primitive<Buffer> {
-    pub fn new(pixmap: Weak<Buffer>, offset: glam::Vec2, scale: f32, generation: u64) -> Self {
+    pub fn new(content: Content<Buffer>, canvas: &Canvas, offset: glam::Vec2, scale: f32) -> Self {
         Self {
-            surface: pixmap,
+            content,
+            canvas: CanvasRef {
+                id: canvas.id,
+                shared: Arc::downgrade(&canvas.shared),
+            },
             offset,
             scale,
-            generation,
+            overlay: Vec::new(),
+            grid: Grid::default(),
+            checkerboard: None,
+            tiling: true,
         }
     }
+
+    /// Whether to split surfaces larger than the device supports into tiles.
+    ///
+    /// If disabled, such surfaces aren't drawn and a [`TextureError`] is reported instead.
+    pub fn with_tiling(mut self, tiling: bool) -> Self {
+        self.tiling = tiling;
+        self
+    }
+
+    /// Draw a [`Checkerboard`] behind the image.
+    pub fn with_checkerboard(mut self, checkerboard: Option<Checkerboard>) -> Self {
+        self.checkerboard = checkerboard;
+        self
+    }
+
+    /// Draw the given grids between the pixels of the image.
+    pub fn with_grid(mut self, grid: Grid) -> Self {
+        self.grid = grid;
+        self
+    }
+
+    /// Draw the given triangles on top of the image.
+    pub fn with_overlay(mut self, overlay: Vec<Vertex>) -> Self {
+        self.overlay = overlay;
+        self
+    }
 }
 
 impl<Buffer: Surface> shader::Primitive for Primitive<Buffer> {
@@ -42,67 +105,130 @@ impl<Buffer: Surface> shader::Primitive for Primitive<Buffer> {
         bounds: &Rectangle,
         _viewport: &shader::Viewport,
     ) {
-        let Some(surface) = self.surface.upgrade() else {
-            return;
+        let (surface, pyramid) = match &self.content {
+            Content::Surface(surface) => (surface.upgrade(), None),
+            Content::Pyramid(pyramid) => (None, Some(pyramid)),
         };
 
-        let mut force_update = false;
-
-        if !storage.has::<Pipeline>() {
-            force_update = true;
+        let size = match (pyramid, &surface) {
+            (Some(pyramid), _) => Size::new(pyramid.width(), pyramid.height()),
+            (None, Some(surface)) => Size::new(surface.width(), surface.height()),
+            (None, None) => return,
+        };
 
-            storage.store(Pipeline::new(
-                device,
-                format,
-                &surface,
-                self.generation,
-                get_surface_ptr(&surface),
-            ));
+        if !storage.has::<Canvases>() {
+            storage.store(Canvases {
+                pipeline: Pipeline::new(device, format),
+                instances: HashMap::new(),
+            });
         }
 
-        let pipeline = storage.get_mut::<Pipeline>().unwrap();
+        let Canvases {
+            pipeline,
+            instances,
+        } = storage.get_mut::<Canvases>().unwrap();
 
-        let texture_size = pipeline.texture.size;
+        // Free the resources of canvases that no longer exist.
+        instances.retain(|_, (canvas, _)| canvas.strong_count() > 0);
 
-        // TODO: Optimise this.
-        if surface.width() != texture_size.width || surface.height() != texture_size.height {
-            force_update = true;
-            *pipeline = Pipeline::new(
-                device,
-                format,
-                &surface,
-                self.generation,
-                get_surface_ptr(&surface),
-            );
-        }
+        let max = device.limits().max_texture_dimension_2d;
 
-        // Update the texture when the widget tree changes
-        if pipeline.generation != self.generation {
-            force_update = true;
-            pipeline.generation = self.generation;
+        // The tiles of a pyramid always fit.
+        let error = (pyramid.is_none() && !self.tiling && (size.width > max || size.height > max))
+            .then_some(TextureError::TooLarge {
+                width: size.width,
+                height: size.height,
+                max,
+            });
+
+        if let Some(shared) = self.canvas.shared.upgrade() {
+            *shared.error.lock().unwrap() = error;
         }
 
-        // Update the texture if the pointer to the surface changes.
-        //
-        // This lets you swap multiple images.
-        let new_surface_ptr = get_surface_ptr(&surface);
-        if pipeline.surface_ptr != new_surface_ptr {
-            force_update = true;
-            pipeline.surface_ptr = new_surface_ptr
+        if error.is_some() {
+            instances.remove(&self.canvas.id);
+            return;
         }
 
-        pipeline.uniform.upload(
+        let (_, instance) = instances.entry(self.canvas.id).or_insert_with(|| {
+            (
+                Weak::clone(&self.canvas.shared),
+                pipeline.instance(device, format),
+            )
+        });
+
+        let image = Size::new(size.width as f32, size.height as f32);
+
+        instance.uniform.upload(
             queue,
-            UniformsRaw::new(self.offset, self.scale, bounds.size(), surface.size()),
+            UniformsRaw::new(self.offset, self.scale, bounds.size(), image)
+                .with_grid(self.grid)
+                .with_checkerboard(self.checkerboard),
         );
 
-        if force_update {
-            pipeline
-                .texture
-                .upload(queue, surface.width(), surface.height(), surface.data());
-        } else {
-            surface.run_if_modified(|width, height, buffer| {
-                pipeline.texture.upload(queue, width, height, buffer);
+        instance
+            .overlay
+            .upload(device, queue, bounds.size(), &self.overlay);
+
+        instance.visible = visible_region(self.offset, self.scale, bounds.size(), image);
+
+        if let Some(pyramid) = pyramid {
+            // Free the texture of a surface displayed before.
+            if instance.texture.size.width != 0 {
+                pipeline.resize(device, queue, instance, 0, 0);
+                instance.content = None;
+            }
+
+            instance
+                .tiles
+                .prepare(device, queue, pyramid, self.scale, instance.visible);
+
+            return;
+        }
+
+        let Some(surface) = surface else {
+            return;
+        };
+
+        instance.tiles.clear();
+
+        let texture_size = instance.texture.size;
+
+        if surface.width() != texture_size.width || surface.height() != texture_size.height {
+            pipeline.resize(device, queue, instance, surface.width(), surface.height());
+
+            // Whatever is left in the texture no longer lines up with the surface.
+            instance.content = None;
+        }
+
+        // Read the version first, so anything modified while uploading is uploaded next time.
+        let id = surface.id();
+        let version = surface.version();
+        let stride = surface.stride();
+
+        match instance.content {
+            Some((held, held_version)) if held == id => {
+                if let Some(region) = surface.modified_since(held_version) {
+                    instance.texture.invalidate(region);
+                }
+            }
+            // The texture holds another surface, or nothing at all.
+            _ => instance.texture.invalidate(Rectangle {
+                x: 0,
+                y: 0,
+                width: surface.width(),
+                height: surface.height(),
+            }),
+        }
+
+        instance.content = Some((id, version));
+
+        // Tiles outside of the bounds are uploaded once they're scrolled into view.
+        if instance.texture.needs_upload(instance.visible) {
+            surface.with_data(|data| {
+                instance
+                    .texture
+                    .upload(queue, stride, data, instance.visible);
             });
         }
     }
@@ -114,12 +240,102 @@ impl<Buffer: Surface> shader::Primitive for Primitive<Buffer> {
         target: &wgpu::TextureView,
         clip_bounds: &Rectangle<u32>,
     ) {
-        if let Some(pipeline) = storage.get::<Pipeline>() {
-            pipeline.render(target, clip_bounds, encoder);
+        let Some(canvases) = storage.get::<Canvases>() else {
+            return;
+        };
+
+        if let Some((_, instance)) = canvases.instances.get(&self.canvas.id) {
+            canvases
+                .pipeline
+                .render(instance, target, clip_bounds, encoder);
         }
     }
 }
 
-fn get_surface_ptr<S: Surface>(surface: &Arc<S>) -> usize {
-    Arc::as_ptr(surface) as *const u8 as usize
+/// The render pipeline, and the resources of every canvas along with whether it still exists.
+struct Canvases {
+    pipeline: Pipeline,
+    instances: HashMap<u64, (Weak<Shared>, Instance)>,
+}
+
+/// Identifies the GPU resources of a canvas, which are freed once it's dropped.
+#[derive(Debug)]
+pub struct Canvas {
+    id: u64,
+    shared: Arc<Shared>,
+}
+
+impl Canvas {
+    pub fn new() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+
+        Self {
+            id: NEXT.fetch_add(1, Ordering::Relaxed),
+            shared: Arc::new(Shared::default()),
+        }
+    }
+
+    /// The error that kept the surface from being drawn the last time it was prepared.
+    pub fn error(&self) -> Option<TextureError> {
+        *self.shared.error.lock().unwrap()
+    }
+}
+
+/// The state of a [`Canvas`] written to by the renderer.
+#[derive(Debug, Default)]
+struct Shared {
+    error: Mutex<Option<TextureError>>,
+}
+
+/// A reference to a [`Canvas`] that doesn't keep it alive.
+#[derive(Debug, Clone)]
+struct CanvasRef {
+    id: u64,
+    shared: Weak<Shared>,
+}
+
+/// An error that keeps a surface from being drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureError {
+    /// The surface is larger than the device supports in a single texture,
+    /// and [tiling](crate::TextureCanvas::tiling) is disabled.
+    TooLarge {
+        width: u32,
+        height: u32,
+        /// The largest width and height of a texture on the device.
+        max: u32,
+    },
+}
+
+impl std::fmt::Display for TextureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextureError::TooLarge { width, height, max } => write!(
+                f,
+                "surface of {width}x{height} is larger than the maximum texture size of {max}x{max}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+/// The region of the image inside of the bounds of the canvas, with a pixel of margin.
+pub fn visible_region(offset: glam::Vec2, scale: f32, screen: Size, image: Size) -> Rectangle<u32> {
+    let image = glam::Vec2::new(image.width, image.height);
+    let screen = glam::Vec2::new(screen.width, screen.height);
+
+    let min = (-offset / scale - 1.0)
+        .floor()
+        .clamp(glam::Vec2::ZERO, image);
+    let max = ((screen - offset) / scale + 1.0)
+        .ceil()
+        .clamp(glam::Vec2::ZERO, image);
+
+    Rectangle {
+        x: min.x as u32,
+        y: min.y as u32,
+        width: (max.x - min.x).max(0.0) as u32,
+        height: (max.y - min.y).max(0.0) as u32,
+    }
 }
@@ -0,0 +1,213 @@
+use super::texture::{self, TileRaw};
+use crate::pyramid::{Pyramid, TileId};
+use crate::widget::surface::SurfaceId;
+
+use iced_core::{Rectangle, Size};
+use iced_wgpu::wgpu;
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+/// The most tiles kept on the GPU by each canvas.
+const CAPACITY: usize = 512;
+
+/// The most tiles uploaded in a single frame, so scrolling doesn't stall on uploads.
+const MAX_UPLOADS: usize = 16;
+
+/// The tiles of a [`Pyramid`] uploaded to the GPU.
+///
+/// Once full, the least recently drawn tiles are evicted.
+pub struct TileCache {
+    tiles: HashMap<TileId, CachedTile>,
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    /// The pyramid the tiles belong to.
+    pyramid: Option<SurfaceId>,
+    frame: u64,
+    /// The tiles to draw, lowest resolution first.
+    visible: Vec<TileId>,
+}
+
+struct CachedTile {
+    _texture: wgpu::Texture,
+    _uniform: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    /// The last frame the tile was drawn in.
+    used: u64,
+}
+
+impl TileCache {
+    pub fn new(layout: &wgpu::BindGroupLayout, sampler: &wgpu::Sampler) -> Self {
+        Self {
+            tiles: HashMap::new(),
+            layout: layout.clone(),
+            sampler: sampler.clone(),
+            pyramid: None,
+            frame: 0,
+            visible: Vec::new(),
+        }
+    }
+
+    /// Free every tile.
+    pub fn clear(&mut self) {
+        self.tiles.clear();
+        self.visible.clear();
+        self.pyramid = None;
+    }
+
+    /// Upload the tiles needed to draw the `visible` region of the image at the given scale,
+    /// asking the [`Pyramid`] to load the ones it doesn't have.
+    ///
+    /// Tiles that haven't been loaded yet are replaced by the closest lower resolution tile
+    /// that has.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        pyramid: &Pyramid,
+        scale: f32,
+        visible: Rectangle<u32>,
+    ) {
+        if self.pyramid != Some(pyramid.id()) {
+            self.clear();
+            self.pyramid = Some(pyramid.id());
+        }
+
+        pyramid.request(visible, scale);
+
+        self.frame += 1;
+        self.visible.clear();
+
+        let mut uploads = 0;
+
+        for tile in pyramid.tiles(pyramid.level(scale), visible) {
+            let mut tile = Some(tile);
+
+            while let Some(id) = tile {
+                if self.upload(device, queue, pyramid, id, &mut uploads) {
+                    self.visible.push(id);
+                    break;
+                }
+
+                tile = pyramid.parent(id);
+            }
+        }
+
+        // Higher resolution tiles are drawn over their placeholders.
+        self.visible
+            .sort_unstable_by_key(|tile| (Reverse(tile.level), tile.y, tile.x));
+        self.visible.dedup();
+
+        for id in &self.visible {
+            if let Some(tile) = self.tiles.get_mut(id) {
+                tile.used = self.frame;
+            }
+        }
+
+        self.evict();
+
+        // Draw the tiles that didn't fit in this frame in the next one.
+        if uploads == MAX_UPLOADS {
+            pyramid.notify();
+        }
+    }
+
+    /// Draw the visible tiles.
+    pub fn draw(&self, pass: &mut wgpu::RenderPass<'_>) {
+        for id in &self.visible {
+            if let Some(tile) = self.tiles.get(id) {
+                pass.set_bind_group(0, &tile.bind_group, &[]);
+                pass.draw(0..6, 0..1);
+            }
+        }
+    }
+
+    /// Make sure a tile is on the GPU, uploading it if it has been loaded.
+    ///
+    /// Returns `false` if the tile isn't available yet.
+    fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        pyramid: &Pyramid,
+        id: TileId,
+        uploads: &mut usize,
+    ) -> bool {
+        if self.tiles.contains_key(&id) {
+            return true;
+        }
+
+        if *uploads >= MAX_UPLOADS {
+            return false;
+        }
+
+        let Some(bitmap) = pyramid.get(id) else {
+            return false;
+        };
+
+        *uploads += 1;
+
+        let size = wgpu::Extent3d {
+            width: bitmap.width(),
+            height: bitmap.height(),
+            depth_or_array_layers: 1,
+        };
+
+        let region = pyramid.placement(id, Size::new(size.width, size.height));
+
+        let uniform = texture::tile_uniform(device);
+        queue.write_buffer(
+            &uniform,
+            0,
+            bytemuck::bytes_of(&TileRaw::new(region, [1.0, 1.0])),
+        );
+
+        let (texture, bind_group) =
+            texture::create_texture(device, &self.layout, &self.sampler, &uniform, size);
+
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            bitmap.raw(),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * size.width),
+                rows_per_image: Some(size.height),
+            },
+            size,
+        );
+
+        self.tiles.insert(
+            id,
+            CachedTile {
+                _texture: texture,
+                _uniform: uniform,
+                bind_group,
+                used: self.frame,
+            },
+        );
+
+        true
+    }
+
+    /// Evict the least recently drawn tiles until the cache fits, keeping the ones drawn this frame.
+    fn evict(&mut self) {
+        while self.tiles.len() > CAPACITY {
+            let oldest = self
+                .tiles
+                .iter()
+                .filter(|(_, tile)| tile.used < self.frame)
+                .min_by_key(|(_, tile)| tile.used)
+                .map(|(id, _)| *id);
+
+            match oldest {
+                Some(id) => self.tiles.remove(&id),
+                None => break,
+            };
+        }
+    }
+}
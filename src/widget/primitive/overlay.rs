@@ -0,0 +1,133 @@
+use super::uniforms::{Uniform, UniformsRaw};
+
+use iced_core::Size;
+use iced_wgpu::wgpu;
+
+/// A coloured vertex of the overlay drawn on top of the texture.
+///
+/// Positions are in pixels, relative to the bounds of the canvas.
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable, Default)]
+#[repr(C)]
+pub struct Vertex {
+    pub position: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl Vertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4];
+
+    fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// Draws a list of triangles over the texture.
+pub struct Overlay {
+    pipeline: wgpu::RenderPipeline,
+    uniform: Uniform,
+    buffer: wgpu::Buffer,
+    capacity: usize,
+    len: u32,
+}
+
+impl Overlay {
+    const INITIAL_CAPACITY: usize = 1024;
+
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Overlay shader"),
+            ..wgpu::include_wgsl!("overlay.wgsl")
+        });
+
+        let uniform_layout = Uniform::bind_group_layout(device);
+        let uniform = Uniform::new(device, &uniform_layout);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Overlay Pipeline layout"),
+            bind_group_layouts: &[&uniform_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Overlay Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[Vertex::layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            uniform,
+            buffer: create_vertex_buffer(device, Self::INITIAL_CAPACITY),
+            capacity: Self::INITIAL_CAPACITY,
+            len: 0,
+        }
+    }
+
+    /// Upload the vertices of the overlay, growing the vertex buffer if needed.
+    pub fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        screen: Size,
+        vertices: &[Vertex],
+    ) {
+        self.len = vertices.len() as u32;
+
+        if vertices.is_empty() {
+            return;
+        }
+
+        if vertices.len() > self.capacity {
+            self.capacity = vertices.len().next_power_of_two();
+            self.buffer = create_vertex_buffer(device, self.capacity);
+        }
+
+        self.uniform.upload(queue, UniformsRaw::screen(screen));
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(vertices));
+    }
+
+    pub fn render(&self, pass: &mut wgpu::RenderPass<'_>) {
+        if self.len == 0 {
+            return;
+        }
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.uniform.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.buffer.slice(..));
+        pass.draw(0..self.len, 0..1);
+    }
+}
+
+fn create_vertex_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("overlay vertices"),
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        size: (capacity * std::mem::size_of::<Vertex>()) as u64,
+        mapped_at_creation: false,
+    })
+}
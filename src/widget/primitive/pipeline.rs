@@ -1,37 +1,33 @@
+use super::cache::TileCache;
+use super::overlay::Overlay;
 use super::texture;
 use super::uniforms::{self, Uniform};
-use crate::widget::Surface;
+use crate::widget::surface::SurfaceId;
 
 use iced_core::Rectangle;
 use iced_wgpu::wgpu;
 
+/// The render pipeline shared by every canvas.
 pub(crate) struct Pipeline {
     pipeline: wgpu::RenderPipeline,
-    pub uniform: uniforms::Uniform,
-    pub texture: texture::Texture,
-    pub generation: u64,
-    pub surface_ptr: usize,
+    texture_layout: wgpu::BindGroupLayout,
+    uniform_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
 }
 
 impl Pipeline {
-    pub fn new<Buffer: Surface>(
-        device: &wgpu::Device,
-        format: wgpu::TextureFormat,
-        pixmap: &Buffer,
-        generation: u64,
-        surface_ptr: usize,
-    ) -> Self {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Pipeline shader"),
             ..wgpu::include_wgsl!("shader.wgsl")
         });
 
-        let texture = texture::Texture::new(device, pixmap.width(), pixmap.height());
-        let uniform = Uniform::new(device);
+        let texture_layout = texture::bind_group_layout(device);
+        let uniform_layout = Uniform::bind_group_layout(device);
 
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Render Pipeline layout"),
-            bind_group_layouts: &[&texture.bind_group_layout, &uniform.bind_group_layout], // order matters
+            bind_group_layouts: &[&texture_layout, &uniform_layout], // order matters
             push_constant_ranges: &[],
         });
 
@@ -63,15 +59,46 @@ impl Pipeline {
 
         Self {
             pipeline,
-            uniform,
-            texture,
-            generation,
-            surface_ptr,
+            texture_layout,
+            uniform_layout,
+            sampler: texture::sampler(device),
         }
     }
 
+    /// Create the resources of a canvas, with an empty texture.
+    pub fn instance(&self, device: &wgpu::Device, format: wgpu::TextureFormat) -> Instance {
+        Instance {
+            uniform: Uniform::new(device, &self.uniform_layout),
+            texture: texture::Texture::new(),
+            tiles: TileCache::new(&self.texture_layout, &self.sampler),
+            overlay: Overlay::new(device, format),
+            content: None,
+            visible: Rectangle::default(),
+        }
+    }
+
+    /// Resize the texture of a canvas, only reallocating the tiles whose new size doesn't fit.
+    pub fn resize(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        instance: &mut Instance,
+        width: u32,
+        height: u32,
+    ) {
+        instance.texture.resize(
+            device,
+            queue,
+            &self.texture_layout,
+            &self.sampler,
+            width,
+            height,
+        );
+    }
+
     pub fn render(
         &self,
+        instance: &Instance,
         target: &wgpu::TextureView,
         viewport: &Rectangle<u32>,
         encoder: &mut wgpu::CommandEncoder,
@@ -102,9 +129,24 @@ impl Pipeline {
             1.0,
         );
 
-        pass.set_bind_group(0, &self.texture.bind_group, &[]);
-        pass.set_bind_group(1, &self.uniform.bind_group, &[]);
+        pass.set_bind_group(1, &instance.uniform.bind_group, &[]);
+
+        instance.texture.draw(&mut pass, instance.visible);
+        instance.tiles.draw(&mut pass);
 
-        pass.draw(0..6, 0..1)
+        instance.overlay.render(&mut pass);
     }
 }
+
+/// The resources of a single canvas.
+pub(crate) struct Instance {
+    pub uniform: uniforms::Uniform,
+    pub texture: texture::Texture,
+    /// The tiles of the [`Pyramid`](crate::Pyramid) displayed instead of the texture, if any.
+    pub tiles: TileCache,
+    pub overlay: Overlay,
+    /// The surface and version of its content held by the texture.
+    pub content: Option<(SurfaceId, u64)>,
+    /// The region of the image inside of the bounds of the canvas.
+    pub visible: Rectangle<u32>,
+}
@@ -1,5 +1,27 @@
 struct Uniforms {
-    @location(0) projection: mat4x4<f32>
+    @location(0) projection: mat4x4<f32>,
+    texture_size: vec2<f32>,
+    scale: f32,
+    // The scale at which the pixel grid starts fading in, zero if disabled.
+    grid_threshold: f32,
+    grid_color: vec4<f32>,
+    tile_color: vec4<f32>,
+    // The size of each tile of the coarse grid, zero if disabled.
+    tile_size: vec2<f32>,
+    // The size of each square of the checkerboard, zero if disabled.
+    checker_size: f32,
+    // Whether the checkerboard is scaled with the image.
+    checker_scaled: u32,
+    checker_even: vec4<f32>,
+    checker_odd: vec4<f32>,
+}
+
+// The region of the image held by a tile, in image pixels.
+struct Tile {
+    origin: vec2<f32>,
+    size: vec2<f32>,
+    // The fraction of the texture covered by the tile.
+    scale: vec2<f32>,
 }
 
 @group(1) @binding(0) 
@@ -11,13 +33,19 @@ var t_color: texture_2d<f32>;
 @group(0) @binding(1)
 var t_sampler: sampler;
 
+@group(0) @binding(2)
+var<uniform> tile: Tile;
+
 struct VertexIn {
     @builtin(vertex_index) vertex_index: u32,
 }
 
 struct VertexOut {
     @builtin(position) position: vec4<f32>,
+    // Relative to the whole image.
     @location(0) tex_coord: vec2<f32>,
+    // Relative to the texture of the tile.
+    @location(1) tile_coord: vec2<f32>,
 }
 
 @vertex
@@ -35,14 +63,71 @@ fn vs_main(in: VertexIn) -> VertexOut {
     );
 
     let xy = pos[in.vertex_index];
+    let image = (tile.origin + xy * tile.size) / uniforms.texture_size;
     
     var out: VertexOut;
-    out.tex_coord = xy;
-    out.position = uniforms.projection * vec4f(xy, 0.0, 1.0); // TODO: opacity
+    out.tex_coord = image;
+    out.tile_coord = xy * tile.scale;
+    out.position = uniforms.projection * vec4f(image, 0.0, 1.0); // TODO: opacity
     return out;
 }
 
+// Composite `top` over `bottom`.
+fn over(top: vec4<f32>, bottom: vec4<f32>) -> vec4<f32> {
+    let alpha = top.a + bottom.a * (1.0 - top.a);
+
+    if alpha <= 0.0 {
+        return vec4f(0.0);
+    }
+
+    let rgb = (top.rgb * top.a + bottom.rgb * bottom.a * (1.0 - top.a)) / alpha;
+    return vec4f(rgb, alpha);
+}
+
+// How much a grid line with the given cell size covers this fragment.
+//
+// `cell` is the size of a grid cell in screen pixels.
+fn grid_coverage(position: vec2<f32>, cell: vec2<f32>) -> f32 {
+    let offset = fract(position);
+    let distance = min(offset, 1.0 - offset) * cell;
+
+    return clamp(1.0 - min(distance.x, distance.y), 0.0, 1.0);
+}
+
 @fragment
 fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
-    return textureSample(t_color, t_sampler, in.tex_coord);
+    var color = textureSample(t_color, t_sampler, in.tile_coord);
+
+    let texel = in.tex_coord * uniforms.texture_size;
+
+    if uniforms.checker_size > 0.0 {
+        // Squares start from the top left corner of the image.
+        var cell = texel * uniforms.scale / uniforms.checker_size;
+
+        if uniforms.checker_scaled != 0u {
+            cell = texel / uniforms.checker_size;
+        }
+
+        let odd = ((i32(floor(cell.x)) + i32(floor(cell.y))) & 1) == 1;
+        color = over(color, select(uniforms.checker_even, uniforms.checker_odd, odd));
+    }
+
+    if uniforms.grid_threshold > 0.0 {
+        let fade = smoothstep(uniforms.grid_threshold, uniforms.grid_threshold * 1.5, uniforms.scale);
+        let coverage = grid_coverage(texel, vec2f(uniforms.scale));
+
+        color = over(vec4f(uniforms.grid_color.rgb, uniforms.grid_color.a * coverage * fade), color);
+    }
+
+    if uniforms.tile_size.x > 0.0 && uniforms.tile_size.y > 0.0 {
+        let cell = uniforms.tile_size * uniforms.scale;
+
+        // Hide the tile grid when the tiles become too small to tell apart.
+        let fade = smoothstep(4.0, 8.0, min(cell.x, cell.y));
+        let coverage = grid_coverage(texel / uniforms.tile_size, cell);
+
+        color = over(vec4f(uniforms.tile_color.rgb, uniforms.tile_color.a * coverage * fade), color);
+    }
+
+    return color;
 }
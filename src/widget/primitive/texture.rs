@@ -1,101 +1,408 @@
+use iced_core::Rectangle;
 use iced_wgpu::wgpu;
 
+/// The image of a canvas on the GPU.
+///
+/// Images larger than the device allows in a single texture are split into a grid of tiles,
+/// each drawn as its own quad. Tiles line up on whole pixels and are sampled with the nearest
+/// filter, so there are no seams between them.
 pub struct Texture {
-    pub texture: wgpu::Texture,
+    /// The size of the image.
     pub size: wgpu::Extent3d,
-    pub bind_group: wgpu::BindGroup,
-    pub bind_group_layout: wgpu::BindGroupLayout,
+    tiles: Vec<Tile>,
 }
 
 impl Texture {
-    pub fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
-        let size = wgpu::Extent3d {
+    /// An image without any tiles, until it's [resized](Self::resize).
+    pub fn new() -> Self {
+        Self {
+            size: wgpu::Extent3d {
+                width: 0,
+                height: 0,
+                depth_or_array_layers: 1,
+            },
+            tiles: Vec::new(),
+        }
+    }
+
+    /// Resize the image, reusing the textures of the tiles that are still needed.
+    ///
+    /// A tile is only reallocated if its new region doesn't fit. Its capacity grows by half when
+    /// outgrown, so repeated small resizes don't allocate at all, and it shrinks to fit once less
+    /// than a quarter of it is used.
+    ///
+    /// The whole image has to be uploaded again afterwards.
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        width: u32,
+        height: u32,
+    ) {
+        let max = device.limits().max_texture_dimension_2d;
+        let regions: Vec<_> = grid(width, height, max).collect();
+
+        self.tiles.truncate(regions.len());
+
+        for (index, region) in regions.into_iter().enumerate() {
+            match self.tiles.get_mut(index) {
+                Some(tile) => tile.resize(device, queue, layout, sampler, region, max),
+                None => self
+                    .tiles
+                    .push(Tile::new(device, queue, layout, sampler, region)),
+            }
+        }
+
+        self.size = wgpu::Extent3d {
             width,
             height,
             depth_or_array_layers: 1,
         };
+    }
 
-        let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Texture"),
-            size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb, // srgb or no srgb
-            usage: wgpu::TextureUsages::TEXTURE_BINDING
-                | wgpu::TextureUsages::COPY_DST
-                | wgpu::TextureUsages::COPY_SRC,
-            view_formats: &[],
-        });
-
-        let texture_view = texture.create_view(&Default::default());
-
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            label: None,
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
-
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("texture_bind_group_layout"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        multisampled: false,
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                },
-            ],
-        });
-
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("diffuse_bind_group"),
-            layout: &bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&texture_view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&sampler),
-                },
-            ],
-        });
+    /// Mark a region of the image as modified.
+    ///
+    /// It's uploaded the next time a tile it touches is visible.
+    pub fn invalidate(&mut self, region: Rectangle<u32>) {
+        for tile in &mut self.tiles {
+            if let Some(modified) = intersection(tile.region, region) {
+                tile.pending = Some(match tile.pending {
+                    Some(pending) => union(pending, modified),
+                    None => modified,
+                });
+            }
+        }
+    }
 
-        Self {
+    /// Whether any of the tiles inside of the `visible` region of the image have been modified.
+    pub fn needs_upload(&self, visible: Rectangle<u32>) -> bool {
+        self.visible_tiles(visible)
+            .any(|tile| tile.pending.is_some())
+    }
+
+    /// Upload the modified parts of the tiles inside of the `visible` region of the image,
+    /// where each row of `data` starts `stride` bytes after the previous one.
+    pub fn upload(
+        &mut self,
+        queue: &wgpu::Queue,
+        stride: u32,
+        data: &[u8],
+        visible: Rectangle<u32>,
+    ) {
+        for tile in &mut self.tiles {
+            if intersection(tile.region, visible).is_none() {
+                continue;
+            }
+
+            if let Some(region) = tile.pending.take() {
+                tile.upload(queue, stride, data, region);
+            }
+        }
+    }
+
+    /// Draw the tiles inside of the `visible` region of the image.
+    pub fn draw(&self, pass: &mut wgpu::RenderPass<'_>, visible: Rectangle<u32>) {
+        for tile in self.visible_tiles(visible) {
+            pass.set_bind_group(0, &tile.bind_group, &[]);
+            pass.draw(0..6, 0..1);
+        }
+    }
+
+    fn visible_tiles(&self, visible: Rectangle<u32>) -> impl Iterator<Item = &Tile> {
+        self.tiles
+            .iter()
+            .filter(move |tile| intersection(tile.region, visible).is_some())
+    }
+}
+
+/// A texture holding a region of the image.
+struct Tile {
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+    uniform: wgpu::Buffer,
+    capacity: wgpu::Extent3d,
+    /// The region of the image held by the tile, in the top left corner of the texture.
+    region: Rectangle<u32>,
+    /// The region of the image modified since the tile was last uploaded.
+    pending: Option<Rectangle<u32>>,
+}
+
+impl Tile {
+    fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        region: Rectangle<u32>,
+    ) -> Self {
+        let capacity = wgpu::Extent3d {
+            width: region.width,
+            height: region.height,
+            depth_or_array_layers: 1,
+        };
+
+        let uniform = tile_uniform(device);
+
+        let (texture, bind_group) = create_texture(device, layout, sampler, &uniform, capacity);
+
+        let tile = Self {
             texture,
-            size,
             bind_group,
-            bind_group_layout,
+            uniform,
+            capacity,
+            region,
+            pending: Some(region),
+        };
+
+        tile.write_uniform(queue);
+        tile
+    }
+
+    /// Move the tile to another region of the image, only reallocating it if it doesn't fit.
+    fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        region: Rectangle<u32>,
+        max: u32,
+    ) {
+        self.region = region;
+        self.pending = Some(region);
+
+        let fits = region.width <= self.capacity.width && region.height <= self.capacity.height;
+        let wasted = (region.width as u64 * region.height as u64) * 4
+            < self.capacity.width as u64 * self.capacity.height as u64;
+
+        if !fits || wasted {
+            self.capacity = wgpu::Extent3d {
+                width: grow(region.width, self.capacity.width, max),
+                height: grow(region.height, self.capacity.height, max),
+                depth_or_array_layers: 1,
+            };
+
+            (self.texture, self.bind_group) =
+                create_texture(device, layout, sampler, &self.uniform, self.capacity);
         }
+
+        self.write_uniform(queue);
     }
 
-    pub fn upload(&mut self, queue: &wgpu::Queue, width: u32, height: u32, data: &[u8]) {
+    fn write_uniform(&self, queue: &wgpu::Queue) {
+        let tile = TileRaw::new(
+            self.region,
+            [
+                self.region.width as f32 / self.capacity.width as f32,
+                self.region.height as f32 / self.capacity.height as f32,
+            ],
+        );
+
+        queue.write_buffer(&self.uniform, 0, bytemuck::bytes_of(&tile));
+    }
+
+    /// Upload a region of the image, where each row of `data` starts `stride` bytes after the previous one.
+    fn upload(&self, queue: &wgpu::Queue, stride: u32, data: &[u8], region: Rectangle<u32>) {
         queue.write_texture(
-            self.texture.as_image_copy(),
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: region.x - self.region.x,
+                    y: region.y - self.region.y,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
             data,
             wgpu::TexelCopyBufferLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * width),
-                rows_per_image: Some(height),
+                offset: region.y as u64 * stride as u64 + region.x as u64 * 4,
+                bytes_per_row: Some(stride),
+                rows_per_image: Some(region.height),
+            },
+            wgpu::Extent3d {
+                width: region.width,
+                height: region.height,
+                depth_or_array_layers: 1,
             },
-            self.size,
         );
     }
 }
+
+/// The placement of a tile, in image pixels.
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, Default)]
+#[repr(C)]
+pub(super) struct TileRaw {
+    origin: [f32; 2],
+    size: [f32; 2],
+    /// The fraction of the texture covered by the tile.
+    scale: [f32; 2],
+    _padding: [f32; 2],
+}
+
+impl TileRaw {
+    /// Draw the given fraction of the texture over the `region` of the image.
+    pub(super) fn new(region: Rectangle<u32>, scale: [f32; 2]) -> Self {
+        Self {
+            origin: [region.x as f32, region.y as f32],
+            size: [region.width as f32, region.height as f32],
+            scale,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+/// Create the buffer holding the [`TileRaw`] of a tile.
+pub(super) fn tile_uniform(device: &wgpu::Device) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("tile uniform"),
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        size: std::mem::size_of::<TileRaw>() as u64,
+        mapped_at_creation: false,
+    })
+}
+
+/// The layout of the bind group of every tile.
+pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("texture_bind_group_layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+    })
+}
+
+/// The sampler shared by every tile.
+pub fn sampler(device: &wgpu::Device) -> wgpu::Sampler {
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        label: None,
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Nearest,
+        min_filter: wgpu::FilterMode::Nearest,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        ..Default::default()
+    })
+}
+
+/// Create a texture of the given size, bound along with the uniform of its tile.
+pub(super) fn create_texture(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    uniform: &wgpu::Buffer,
+    size: wgpu::Extent3d,
+) -> (wgpu::Texture, wgpu::BindGroup) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Texture"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb, // srgb or no srgb
+        usage: wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_DST
+            | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+
+    let texture_view = texture.create_view(&Default::default());
+
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("diffuse_bind_group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&texture_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: uniform.as_entire_binding(),
+            },
+        ],
+    });
+
+    (texture, bind_group)
+}
+
+/// Split an image into a grid of tiles no larger than `max` on either side, row by row.
+fn grid(width: u32, height: u32, max: u32) -> impl Iterator<Item = Rectangle<u32>> {
+    (0..height).step_by(max as usize).flat_map(move |y| {
+        (0..width).step_by(max as usize).map(move |x| Rectangle {
+            x,
+            y,
+            width: (width - x).min(max),
+            height: (height - y).min(max),
+        })
+    })
+}
+
+/// The capacity needed for the `required` size, growing the `current` capacity by half.
+fn grow(required: u32, current: u32, max: u32) -> u32 {
+    if required > current {
+        (current + current / 2).max(required).min(max)
+    } else {
+        required
+    }
+}
+
+fn intersection(a: Rectangle<u32>, b: Rectangle<u32>) -> Option<Rectangle<u32>> {
+    let left = a.x.max(b.x);
+    let top = a.y.max(b.y);
+    let right = (a.x + a.width).min(b.x + b.width);
+    let bottom = (a.y + a.height).min(b.y + b.height);
+
+    (left < right && top < bottom).then(|| Rectangle {
+        x: left,
+        y: top,
+        width: right - left,
+        height: bottom - top,
+    })
+}
+
+fn union(a: Rectangle<u32>, b: Rectangle<u32>) -> Rectangle<u32> {
+    let left = a.x.min(b.x);
+    let top = a.y.min(b.y);
+    let right = (a.x + a.width).max(b.x + b.width);
+    let bottom = (a.y + a.height).max(b.y + b.height);
+
+    Rectangle {
+        x: left,
+        y: top,
+        width: right - left,
+        height: bottom - top,
+    }
+}
@@ -1,3 +1,6 @@
+use super::Grid;
+use crate::style::{Checkerboard, Units};
+
 use glam::Vec2;
 use iced_core::Size;
 use iced_wgpu::wgpu;
@@ -5,11 +8,10 @@ use iced_wgpu::wgpu;
 pub struct Uniform {
     pub buffer: wgpu::Buffer,
     pub bind_group: wgpu::BindGroup,
-    pub bind_group_layout: wgpu::BindGroupLayout,
 }
 
 impl Uniform {
-    pub fn new(device: &wgpu::Device) -> Self {
+    pub fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout) -> Self {
         let buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("uniform"),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
@@ -17,7 +19,21 @@ impl Uniform {
             mapped_at_creation: false,
         });
 
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("camera bind group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Self { buffer, bind_group }
+    }
+
+    /// The layout of the bind group of every [`Uniform`].
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("Uniform Bind Group"),
             entries: &[wgpu::BindGroupLayoutEntry {
                 binding: 0,
@@ -29,22 +45,7 @@ impl Uniform {
                 },
                 count: None,
             }],
-        });
-
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("camera bind group"),
-            layout: &bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: buffer.as_entire_binding(),
-            }],
-        });
-
-        Self {
-            buffer,
-            bind_group,
-            bind_group_layout,
-        }
+        })
     }
 
     /// Upload uniform buffer
@@ -57,6 +58,19 @@ impl Uniform {
 #[repr(C)]
 pub struct UniformsRaw {
     pub transform: [f32; 16],
+    pub texture_size: [f32; 2],
+    pub scale: f32,
+    /// The scale at which the pixel grid starts fading in, zero if disabled.
+    pub grid_threshold: f32,
+    pub grid_color: [f32; 4],
+    pub tile_color: [f32; 4],
+    /// The size of each tile of the coarse grid, zero if disabled.
+    pub tile_size: [f32; 2],
+    /// The size of each square of the checkerboard, zero if disabled.
+    pub checker_size: f32,
+    /// Whether the checkerboard is scaled with the image.
+    pub checker_scaled: u32,
+    pub checker_colors: [[f32; 4]; 2],
 }
 
 impl UniformsRaw {
@@ -75,6 +89,41 @@ impl UniformsRaw {
 
         UniformsRaw {
             transform: *(projection * transform).as_ref(),
+            texture_size: [texture.width, texture.height],
+            scale: zoom,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_grid(mut self, grid: Grid) -> Self {
+        if let Some(threshold) = grid.threshold {
+            self.grid_threshold = threshold.max(f32::EPSILON);
+            self.grid_color = grid.color.into_linear();
+        }
+
+        if let Some(tile_size) = grid.tile_size {
+            self.tile_size = [tile_size.width as f32, tile_size.height as f32];
+            self.tile_color = grid.tile_color.into_linear();
+        }
+
+        self
+    }
+
+    pub fn with_checkerboard(mut self, checkerboard: Option<Checkerboard>) -> Self {
+        if let Some(checkerboard) = checkerboard {
+            self.checker_size = checkerboard.size.max(f32::EPSILON);
+            self.checker_scaled = (checkerboard.units == Units::Image) as u32;
+            self.checker_colors = checkerboard.colors.map(|color| color.into_linear());
+        }
+
+        self
+    }
+
+    /// Maps pixel coordinates relative to the canvas bounds to clip space.
+    pub fn screen(screen: Size<f32>) -> Self {
+        UniformsRaw {
+            transform: *screen_to_mat(0.0, screen.width, screen.height, 0.).as_ref(),
+            ..Default::default()
         }
     }
 }
@@ -11,6 +11,26 @@ pub struct Style {
     pub border_color: Color,
     pub border_thickness: f32,
     pub shadow: Shadow,
+    /// The default color of [`Annotation`](crate::annotation::Annotation)s.
+    pub annotation: Color,
+    /// The fill color of the handles of the selected annotation.
+    pub handle: Color,
+    /// The color of the grid between pixels.
+    pub grid: Color,
+    /// The color of the coarse tile grid.
+    pub tile_grid: Color,
+    /// The [`Checkerboard`] drawn behind transparent pixels of the image.
+    pub checkerboard: Option<Checkerboard>,
+    /// The background color of the rulers.
+    pub ruler: Color,
+    /// The color of the ticks and labels of the rulers.
+    pub ruler_marks: Color,
+    /// The color of the guides.
+    pub guide: Color,
+    /// The color drawn over the canvas while an image is [`loading`](crate::TextureCanvas::loading).
+    pub placeholder: Color,
+    /// The color of the loading progress bar.
+    pub progress: Color,
 }
 
 impl Default for Style {
@@ -20,10 +40,51 @@ impl Default for Style {
             border_color: Color::BLACK,
             border_thickness: 1.0,
             shadow: Shadow::default(),
+            annotation: Color::from_rgb(0.2, 0.6, 1.0),
+            handle: Color::WHITE,
+            grid: Color::from_rgba(0.5, 0.5, 0.5, 0.5),
+            tile_grid: Color::from_rgba(0.2, 0.6, 1.0, 0.75),
+            checkerboard: None,
+            ruler: Color::from_rgb(0.95, 0.95, 0.95),
+            ruler_marks: Color::from_rgb(0.3, 0.3, 0.3),
+            guide: Color::from_rgb(0.0, 0.8, 0.8),
+            placeholder: Color::from_rgba(0.5, 0.5, 0.5, 0.25),
+            progress: Color::from_rgb(0.2, 0.6, 1.0),
         }
     }
 }
 
+/// A two-tone checkerboard drawn behind the image to make transparency visible.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Checkerboard {
+    /// The size of each square.
+    pub size: f32,
+    /// Whether the `size` is measured in screen or image pixels.
+    pub units: Units,
+    /// The colors of the alternating squares.
+    pub colors: [Color; 2],
+}
+
+impl Default for Checkerboard {
+    fn default() -> Self {
+        Checkerboard {
+            size: 8.0,
+            units: Units::Screen,
+            colors: [Color::WHITE, Color::from_rgb(0.8, 0.8, 0.8)],
+        }
+    }
+}
+
+/// The units of a size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Units {
+    /// Fixed in screen space, regardless of the scale of the image.
+    #[default]
+    Screen,
+    /// Scaled with the image.
+    Image,
+}
+
 pub trait Catalog: Sized {
     /// The item class of the [`Catalog`].
     type Class<'a>;
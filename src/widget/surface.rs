@@ -1,4 +1,7 @@
+use crate::pyramid::Pyramid;
+
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Weak};
 
 /// A type that can provide information about the [`Surface`],
@@ -23,6 +26,11 @@ pub trait SurfaceHandler {
 }
 
 /// RGBA image data stored on the CPU to be uploaded to the GPU.
+///
+/// Changes are tracked with a content [`version`](Self::version) that increases every time the
+/// [`Surface`] is modified. Each texture on the GPU remembers the [`SurfaceId`] and version it
+/// holds, and only uploads what was [`modified_since`](Self::modified_since) then. This way,
+/// any number of canvases can display the same [`Surface`].
 pub trait Surface: Send + Sync + Debug + 'static {
     /// The width of the [`Surface`]
     fn width(&self) -> u32;
@@ -33,15 +41,45 @@ pub trait Surface: Send + Sync + Debug + 'static {
     /// The image data of [`Surface`]
     fn data(&self) -> &[u8];
 
+    /// The number of bytes from the start of one row of the [`data`](Self::data) to the next.
+    ///
+    /// By default, rows are tightly packed.
+    fn stride(&self) -> u32 {
+        4 * self.width()
+    }
+
     /// The size of the [`Surface`]
     fn size(&self) -> iced_core::Size {
         (self.width() as f32, self.height() as f32).into()
     }
 
-    /// Call the update closure if the [`Surface`] was modified.
+    /// Identifies the content of the [`Surface`].
     ///
-    /// The data provided in update will be uploaded to the GPU.
-    fn run_if_modified(&self, update: impl FnOnce(u32, u32, &[u8]));
+    /// Versions are only comparable between surfaces with the same [`SurfaceId`].
+    fn id(&self) -> SurfaceId;
+
+    /// The version of the content, which increases every time the [`Surface`] is modified.
+    fn version(&self) -> u64;
+
+    /// The region modified since the given version, or [`None`] if it's the current version.
+    ///
+    /// By default, the whole [`Surface`] is treated as modified.
+    fn modified_since(&self, version: u64) -> Option<iced_core::Rectangle<u32>> {
+        (version != self.version()).then(|| iced_core::Rectangle {
+            x: 0,
+            y: 0,
+            width: self.width(),
+            height: self.height(),
+        })
+    }
+
+    /// Call the closure with the [`data`](Self::data) to be uploaded to the GPU.
+    ///
+    /// Surfaces written to by someone else can override this to keep the data from changing
+    /// during the upload.
+    fn with_data(&self, upload: impl FnOnce(&[u8])) {
+        upload(self.data())
+    }
 }
 
 impl<T: Surface> Surface for Arc<T> {
@@ -57,7 +95,124 @@ impl<T: Surface> Surface for Arc<T> {
         Arc::as_ref(&self).data()
     }
 
-    fn run_if_modified(&self, update: impl FnOnce(u32, u32, &[u8])) {
-        Arc::as_ref(&self).run_if_modified(update)
+    fn stride(&self) -> u32 {
+        Arc::as_ref(&self).stride()
+    }
+
+    fn id(&self) -> SurfaceId {
+        Arc::as_ref(&self).id()
+    }
+
+    fn version(&self) -> u64 {
+        Arc::as_ref(&self).version()
+    }
+
+    fn modified_since(&self, version: u64) -> Option<iced_core::Rectangle<u32>> {
+        Arc::as_ref(&self).modified_since(version)
+    }
+
+    fn with_data(&self, upload: impl FnOnce(&[u8])) {
+        Arc::as_ref(&self).with_data(upload)
+    }
+}
+
+/// Any [`Surface`] behind an [`Arc`] can be displayed directly.
+impl<T: Surface> SurfaceHandler for Arc<T> {
+    type Surface = T;
+
+    fn width(&self) -> u32 {
+        Arc::as_ref(self).width()
+    }
+
+    fn height(&self) -> u32 {
+        Arc::as_ref(self).height()
+    }
+
+    fn create_weak(&self) -> Weak<Self::Surface> {
+        Arc::downgrade(self)
+    }
+}
+
+/// Anything a [`TextureCanvas`](crate::TextureCanvas) can display: the [`Surface`] of a
+/// [`SurfaceHandler`], or a [`Pyramid`].
+///
+/// It can't be implemented outside of this crate, implement [`SurfaceHandler`] instead.
+pub trait Source: sealed::Sealed {}
+
+impl<Handler: SurfaceHandler> Source for Handler {}
+
+impl Source for Pyramid {}
+
+pub(super) mod sealed {
+    use super::{Pyramid, Surface, SurfaceHandler, SurfaceId};
+
+    pub trait Sealed {
+        /// The [`Surface`] that is uploaded, if any.
+        type Surface: Surface;
+
+        fn displayed(&self) -> Displayed<'_, Self::Surface>;
+    }
+
+    /// What a [`TextureCanvas`](crate::TextureCanvas) displays.
+    pub enum Displayed<'a, S> {
+        Surface(&'a dyn SurfaceHandler<Surface = S>),
+        Pyramid(&'a Pyramid),
+    }
+
+    impl<Handler: SurfaceHandler> Sealed for Handler {
+        type Surface = Handler::Surface;
+
+        fn displayed(&self) -> Displayed<'_, Self::Surface> {
+            Displayed::Surface(self)
+        }
+    }
+
+    impl Sealed for Pyramid {
+        type Surface = NoSurface;
+
+        fn displayed(&self) -> Displayed<'_, Self::Surface> {
+            Displayed::Pyramid(self)
+        }
+    }
+
+    /// The [`Surface`] of a [`Pyramid`], which never uploads one.
+    #[derive(Debug)]
+    pub enum NoSurface {}
+
+    impl Surface for NoSurface {
+        fn width(&self) -> u32 {
+            match *self {}
+        }
+
+        fn height(&self) -> u32 {
+            match *self {}
+        }
+
+        fn data(&self) -> &[u8] {
+            match *self {}
+        }
+
+        fn id(&self) -> SurfaceId {
+            match *self {}
+        }
+
+        fn version(&self) -> u64 {
+            match *self {}
+        }
+    }
+}
+
+/// Identifies the content of a [`Surface`].
+///
+/// Unlike a pointer, an id is never reused, even after the [`Surface`] is dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SurfaceId(u64);
+
+impl SurfaceId {
+    /// Create a new [`SurfaceId`] that is unique for the lifetime of the program.
+    pub fn unique() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
     }
 }